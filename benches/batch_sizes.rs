@@ -21,6 +21,9 @@ extern crate rand;
 use test::Bencher;
 use rand::{Rng, thread_rng};
 
+use dream_go::go::{Board, Color};
+use dream_go::mcts::{self, predict, Budget};
+use dream_go::mcts::tree::DefaultValue;
 use dream_go::nn::*;
 use dream_go::util::types::*;
 
@@ -42,7 +45,7 @@ fn bench_batch_size_aux<T>(
     network: &Network,
     batch_size: usize
 )
-    where T: From<f32> + Clone
+    where T: From<f32> + Clone, f32: From<T>
 {
     let mut workspace = network.get_workspace(batch_size);
     let features = (0..batch_size).map(|_| {
@@ -86,3 +89,27 @@ fn bench_batch_size(b: &mut Bencher, batch_size: usize) {
 #[bench] fn batch_size_16(b: &mut Bencher) { bench_batch_size(b, 16); }
 #[bench] fn batch_size_32(b: &mut Bencher) { bench_batch_size(b, 32); }
 #[bench] fn batch_size_64(b: &mut Bencher) { bench_batch_size(b, 64); }
+
+/// Benchmark a search from the empty board using the default number of search
+/// threads, batch size, and latency bound. The batches that were evaluated
+/// during the search are printed afterwards, which can be used to tune the
+/// `--batch-size` and `--batch-latency` options.
+#[bench]
+fn search_workload(b: &mut Bencher) {
+    let server = predict::service(Network::new().unwrap());
+    let board = Board::new();
+
+    b.iter(|| {
+        mcts::predict::<DefaultValue, _>(
+            &server.lock(),
+            None,
+            Budget::rollouts(1600),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        )
+    });
+
+    eprintln!("{}", server.statistics());
+}
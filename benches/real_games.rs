@@ -21,6 +21,9 @@ use test::Bencher;
 
 use dream_go::go::{Board, Color, CHW, HWC};
 use dream_go::go::symmetry::Transform;
+use dream_go::mcts::{self, predict, Budget};
+use dream_go::mcts::evaluator::RandomEvaluator;
+use dream_go::mcts::tree::DefaultValue;
 use dream_go::util::types::*;
 
 /// Benchmark the full playout of a game as a serie of `is_valid` and `place` calls.
@@ -197,3 +200,53 @@ fn get_features_32(b: &mut Bencher) {
         board.get_features::<f32, CHW>(white, Transform::FlipLR)
     });
 }
+
+/// Benchmark a search from the opening of a real game, with or without
+/// sharing the statistics of transposed positions.
+///
+/// # Arguments
+///
+/// * `b` - the bencher
+/// * `transpositions` - whether to share the statistics of transposed positions
+///
+fn predict_opening(b: &mut Bencher, transpositions: bool) {
+    let rina_fujisawa_jeong_chio = [
+        (Color::Black, 15,  3), (Color::White,  3,  3), (Color::Black, 15, 16), (Color::White,  3, 15)
+    ];
+
+    let mut board = Board::new();
+
+    for &(color, x, y) in rina_fujisawa_jeong_chio.iter() {
+        assert!(board.is_valid(color, x, y));
+
+        board.place(color, x, y);
+    }
+
+    let server = predict::service(RandomEvaluator);
+
+    b.iter(move || {
+        mcts::predict::<DefaultValue, _>(
+            &server.lock(),
+            Some(1),
+            Budget::rollouts(400).with_transpositions(transpositions),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        )
+    });
+}
+
+/// Benchmark a search from the opening of a real game, where many move orders
+/// transpose into the same position and share their statistics.
+#[bench]
+fn predict_opening_transpositions(b: &mut Bencher) {
+    predict_opening(b, true);
+}
+
+/// Benchmark a search from the opening of a real game, where every move order
+/// keeps its own statistics.
+#[bench]
+fn predict_opening_no_transpositions(b: &mut Bencher) {
+    predict_opening(b, false);
+}
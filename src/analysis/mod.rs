@@ -0,0 +1,561 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, sync_channel, SyncSender, Receiver};
+use std::thread::{self, JoinHandle};
+
+use go::sgf::{self, GameTree, Property, Sabaki, SgfCoordinate};
+use go::{self, Board, Color, Rules};
+use gtp::Vertex;
+use mcts::evaluator::Evaluator;
+use mcts::predict::{PredictGuard, PredictService};
+use mcts::tree;
+use mcts::{self, Budget};
+use util::config;
+
+/// The number of win rate drops to report for each player.
+const NUM_MISTAKES: usize = 5;
+
+/// The evaluation of a single move in an analysed game.
+struct Evaluation {
+    /// The index of the node (in the main line) that contains the move.
+    node: usize,
+
+    /// The color of the player who played the move.
+    color: Color,
+
+    /// The move that was played.
+    played: usize,
+
+    /// The move that the engine would have played instead.
+    best: usize,
+
+    /// The win rate for black before the move was played.
+    before: f32,
+
+    /// The win rate for black after the move was played.
+    after: f32,
+
+    /// The principal variation of the engine, starting with `best`.
+    variation: Option<GameTree>
+}
+
+impl Evaluation {
+    /// Returns how much the win rate of the player who played this move
+    /// dropped because of it.
+    fn drop(&self) -> f32 {
+        if self.color == Color::Black {
+            self.before - self.after
+        } else {
+            self.after - self.before
+        }
+    }
+}
+
+/// Append the given text to the comment of the given node, adding a comment
+/// if the node does not already have one.
+///
+/// # Arguments
+///
+/// * `node` - the node to comment on
+/// * `text` - the text to add
+///
+fn add_comment(node: &mut sgf::Node, text: String) {
+    for property in node.properties.iter_mut() {
+        if let Property::Comment(ref mut comment) = *property {
+            comment.push_str("\n\n");
+            comment.push_str(&text);
+            return;
+        }
+    }
+
+    node.properties.push(Property::Comment(text));
+}
+
+/// Returns the win rate for black of the given board position, the most
+/// visited move, and the search tree.
+///
+/// # Arguments
+///
+/// * `server` - the server to use during evaluation
+/// * `starting_tree` - the search tree to continue from, if any
+/// * `board` - the board position to search
+/// * `color` - the color of the player to move
+/// * `komi` - the komi of the game
+///
+fn search<N>(
+    server: &PredictGuard<N>,
+    starting_tree: Option<tree::Node<tree::DefaultValue>>,
+    board: &Board,
+    color: Color,
+    komi: f32
+) -> (f32, usize, tree::Node<tree::DefaultValue>)
+    where N: Evaluator + 'static
+{
+    // only the win rate and the best move are of interest, so stop as soon as
+    // the best move can no longer change
+    let num_threads = ::std::cmp::max(*config::NUM_THREADS / *config::NUM_GAMES, 1);
+    let budget = Budget::rollouts(*config::NUM_ROLLOUT).with_early_stop(true);
+    let (_, _, tree) = mcts::predict::<tree::DefaultValue, _>(
+        server,
+        Some(num_threads),
+        budget,
+        starting_tree,
+        board,
+        color,
+        komi
+    );
+
+    // always use the most visited move, since `predict` may pick a move at
+    // random early in the game
+    let (value, best) = tree.best(0.0);
+    let value = if color == Color::Black { value } else { 1.0 - value };
+
+    (value, best, tree)
+}
+
+/// Returns the main line of the given game annotated with the win rate and
+/// the preferred move of the engine at every move, the principal variation
+/// of the engine as a variation wherever it disagrees with the game, and a
+/// summary of the biggest win rate drops of each player. If the game
+/// contains invalid moves then `None` is returned.
+///
+/// # Arguments
+///
+/// * `game` - the game to analyse
+/// * `server` - the server to use during evaluation
+///
+pub fn analyze_game<N>(game: &GameTree, server: &PredictGuard<N>) -> Option<GameTree>
+    where N: Evaluator + 'static
+{
+    let mut size = 19;
+    let mut rules = None;
+    let mut komi = None;
+    let mut handicap = 0;
+
+    for property in game.root().properties.iter() {
+        match *property {
+            Property::Size(other) => { size = other; },
+            Property::Rules(ref other) => { rules = other.parse::<Rules>().ok(); },
+            Property::Komi(other) => { komi = Some(other); },
+            Property::Handicap(other) => { handicap = other; },
+            _ => {}
+        }
+    }
+
+    if size < 5 || size > 19 {
+        return None;
+    }
+
+    let rules = rules.unwrap_or(Rules::Chinese);
+    let komi = komi.unwrap_or_else(|| rules.komi());
+
+    // replay the main line of the game, and record the board position before
+    // each move
+    let mut nodes = game.main_line().cloned().collect::<Vec<sgf::Node>>();
+    let mut positions: Vec<(usize, Board, Color, usize)> = vec! [];
+    let mut board = Board::with_rules(size, rules);
+
+    for (i, node) in nodes.iter().enumerate() {
+        for property in node.properties.iter() {
+            let (color, vertices) = match *property {
+                Property::AddBlack(ref vertices) => (Color::Black, vertices),
+                Property::AddWhite(ref vertices) => (Color::White, vertices),
+                Property::AddEmpty(_) => { return None; },  // not supported
+                _ => { continue; }
+            };
+
+            for &(x, y) in vertices.iter() {
+                if x >= size || y >= size {
+                    return None;
+                }
+
+                let (x, y) = Sabaki::to_point(x, y, size);
+
+                if board.at(x, y).is_some() {
+                    return None;
+                }
+
+                board.setup(color, x, y);
+            }
+        }
+
+        if i == 0 && handicap >= 2 && board.zobrist_hash() == 0 {
+            for (x, y) in go::fixed_handicap(size, handicap)? {
+                board.setup(Color::Black, x, y);
+            }
+        }
+
+        for property in node.properties.iter() {
+            let (color, vertex) = match *property {
+                Property::Move(color, vertex) => (color, vertex),
+                _ => { continue; }
+            };
+
+            match vertex.filter(|&(x, y)| x < size && y < size) {
+                None => {
+                    positions.push((i, board.clone(), color, 361));
+                },
+                Some((x, y)) => {
+                    let (x, y) = Sabaki::to_point(x, y, size);
+
+                    if !board.is_valid(color, x, y) {
+                        return None;
+                    }
+
+                    positions.push((i, board.clone(), color, 19 * y + x));
+                    board.place(color, x, y);
+                }
+            }
+        }
+    }
+
+    // search every position, including the final one, and re-use the
+    // sub-tree of the played move for the next search
+    let final_color = positions.last().map(|&(_, _, color, _)| color.opposite()).unwrap_or(Color::Black);
+    let mut evaluations: Vec<Evaluation> = vec! [];
+    let mut starting_tree = None;
+    let mut previous: Option<(usize, Color, usize, f32, usize, Option<GameTree>)> = None;
+
+    positions.push((nodes.len(), board, final_color, 361));
+
+    for (node, board, color, played) in positions.into_iter() {
+        let (value, best, tree) = search(server, starting_tree.take(), &board, color, komi);
+
+        if let Some((node, color, played, before, best, variation)) = previous.take() {
+            evaluations.push(Evaluation {
+                node: node,
+                color: color,
+                played: played,
+                best: best,
+                before: before,
+                after: value,
+                variation: variation
+            });
+        }
+
+        let variation = if best != played { tree.to_principal_variation::<Sabaki>(size) } else { None };
+
+        starting_tree = tree.forward(played);
+        previous = Some((node, color, played, value, best, variation));
+    }
+
+    // annotate every move, and add the principal variation of the engine
+    // as a variation wherever it disagrees with the played move
+    let mut variations: Vec<Option<GameTree>> = (0..nodes.len()).map(|_| None).collect();
+
+    for evaluation in evaluations.iter_mut() {
+        let drop = evaluation.drop();
+
+        add_comment(&mut nodes[evaluation.node], format!(
+            "Black win rate: {:.1}%\nWin rate change for {}: {:+.1}%\nPreferred move: {}",
+            100.0 * evaluation.after,
+            evaluation.color,
+            -100.0 * drop,
+            Vertex::from_index(evaluation.best)
+        ));
+
+        if evaluation.node > 0 && variations[evaluation.node].is_none() {
+            variations[evaluation.node] = evaluation.variation.take();
+        }
+    }
+
+    // summarize the biggest mistakes of each player in the root node
+    let summary = [Color::Black, Color::White].iter()
+        .map(|&color| {
+            let mut mistakes = evaluations.iter().enumerate()
+                .filter(|&(_, e)| e.color == color && e.drop() > 0.0)
+                .collect::<Vec<(usize, &Evaluation)>>();
+            mistakes.sort_by(|a, b| b.1.drop().partial_cmp(&a.1.drop()).unwrap());
+
+            format!("Biggest mistakes by {}: {}", color, mistakes.iter()
+                .take(NUM_MISTAKES)
+                .map(|&(i, e)| format!("{} {} ({:.1}%)", i + 1, Vertex::from_index(e.played), -100.0 * e.drop()))
+                .collect::<Vec<String>>()
+                .join(", ")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    add_comment(&mut nodes[0], summary);
+
+    // re-assemble the game from the last node to the first, splitting the
+    // game into variations wherever the engine disagrees
+    let mut out = GameTree { nodes: vec! [], variations: vec! [] };
+
+    for (node, variation) in nodes.into_iter().zip(variations.into_iter()).rev() {
+        out.nodes.insert(0, node);
+
+        if let Some(variation) = variation {
+            out = GameTree { nodes: vec! [], variations: vec! [out, variation] };
+        }
+    }
+
+    Some(out)
+}
+
+/// The analysis of every game in a single SGF file.
+pub struct AnalysedFile {
+    /// The path to the SGF file that was analysed.
+    pub path: String,
+
+    /// The analysis of each game in the file, in the same order as in the
+    /// file. Games that are invalid are skipped.
+    pub games: Vec<GameTree>
+}
+
+impl AnalysedFile {
+    /// Returns the path that the analysis of this file should be written
+    /// to, which is the path of the file with the extension replaced by
+    /// `analyzed.sgf`.
+    pub fn output_path(&self) -> PathBuf {
+        Path::new(&self.path).with_extension("analyzed.sgf")
+    }
+}
+
+/// The messages sent from the worker threads to an `Analysis`.
+enum Message {
+    /// The file with the given index and path has been read, and contains
+    /// the given number of games.
+    File(usize, String, usize),
+
+    /// The game with the given index, in the file with the given index, has
+    /// been analysed.
+    Game(usize, usize, Option<GameTree>)
+}
+
+/// A file whose games are still being analysed.
+struct PendingFile {
+    path: String,
+    games: Vec<Option<GameTree>>,
+    remaining: usize
+}
+
+/// Iterator over the analysis of every SGF file in a set of SGF files, in the
+/// same order as the files were given.
+pub struct Analysis<'a> {
+    /// The channel where the analysed games are delivered by the worker
+    /// threads.
+    receiver: Receiver<Message>,
+
+    /// The files that are still being analysed, by index.
+    pending: HashMap<usize, PendingFile>,
+
+    /// The index of the next file to return.
+    next_file: usize,
+
+    /// The lifetime of the server that each worker thread holds
+    lifetime: PhantomData<&'a usize>
+}
+
+impl<'a> Iterator for Analysis<'a> {
+    type Item = AnalysedFile;
+
+    fn next(&mut self) -> Option<AnalysedFile> {
+        loop {
+            if self.pending.get(&self.next_file).map_or(false, |pending| pending.remaining == 0) {
+                let pending = self.pending.remove(&self.next_file).unwrap();
+
+                self.next_file += 1;
+
+                return Some(AnalysedFile {
+                    path: pending.path,
+                    games: pending.games.into_iter().filter_map(|game| game).collect()
+                });
+            }
+
+            match self.receiver.recv().ok()? {
+                Message::File(file, path, num_games) => {
+                    // the file is always announced before any of its games
+                    // are sent to the worker threads, so it will always be
+                    // pending before any of its games are received
+                    self.pending.insert(file, PendingFile {
+                        path: path,
+                        games: (0..num_games).map(|_| None).collect(),
+                        remaining: num_games
+                    });
+                },
+                Message::Game(file, index, game) => {
+                    let pending = self.pending.get_mut(&file).unwrap();
+
+                    pending.games[index] = game;
+                    pending.remaining -= 1;
+                }
+            }
+        }
+    }
+}
+
+/// Returns an iterator over the analysis of all SGF files in the given paths,
+/// where `NUM_GAMES` games are analysed in parallel. Files that cannot be
+/// read are skipped.
+///
+/// # Arguments
+///
+/// * `src` - the path to the SGF files
+/// * `server` - the server to use during evaluation
+///
+pub fn of<'a, N>(src: &[String], server: &'a PredictService<N>) -> Analysis<'a>
+    where N: Evaluator + 'static
+{
+    let src = src.to_vec();
+
+    // spawn the worker threads
+    let num_games = *config::NUM_GAMES;
+    let (t_game, r_game) = channel();
+    let workers = (0..num_games).map(|_| {
+        let (t_src, r_src) = sync_channel::<(usize, usize, GameTree)>(1);
+        let t_game = t_game.clone();
+        let server = server.lock().clone_static();
+        let worker = thread::spawn(move || {
+            for (file, index, game) in r_src.iter() {
+                let analysis = analyze_game(&game, &server);
+
+                if t_game.send(Message::Game(file, index, analysis)).is_err() {
+                    break
+                }
+            }
+        });
+
+        (worker, t_src)
+    }).collect::<Vec<(JoinHandle<()>, SyncSender<(usize, usize, GameTree)>)>>();
+
+    // spawn the thread that is responsible for distributing the games
+    // over to all of the worker threads
+    thread::spawn(move || {
+        let files = src.into_iter()
+            .filter_map(|path| {
+                let mut content = String::new();
+                let games = File::open(&path)
+                    .and_then(|mut file| file.read_to_string(&mut content))
+                    .ok()
+                    .and_then(|_| sgf::parse(&content).ok());
+
+                if games.is_none() {
+                    eprintln!("Could not read the SGF file {}", path);
+                }
+
+                games.map(|games| (path, games))
+            });
+        let mut count = 0;
+
+        'outer: for (file, (path, games)) in files.enumerate() {
+            if t_game.send(Message::File(file, path, games.len())).is_err() {
+                break
+            }
+
+            for (index, game) in games.into_iter().enumerate() {
+                let tx = &workers[count % workers.len()].1;
+
+                if tx.send((file, index, game)).is_err() {
+                    break 'outer
+                }
+
+                count += 1;
+            }
+        }
+
+        // terminate all worker threads
+        drop(t_game);
+
+        for (worker, tx) in workers.into_iter() {
+            drop(tx);
+            worker.join().unwrap();
+        }
+    });
+
+    Analysis {
+        receiver: r_game,
+        pending: HashMap::new(),
+        next_file: 0,
+        lifetime: PhantomData
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use analysis::*;
+    use mcts::evaluator::RandomEvaluator;
+    use mcts::predict;
+
+    #[test]
+    fn analyze_random() {
+        let server = predict::service(RandomEvaluator);
+        let games = sgf::parse("(;GM[1]FF[4]SZ[9]KM[7.5]C[Hello];B[ee];W[cc];B[gc];W[])").unwrap();
+        let analysis = analyze_game(&games[0], &server.lock()).unwrap();
+        let nodes = analysis.main_line().collect::<Vec<&sgf::Node>>();
+
+        assert_eq!(nodes.len(), 5);
+
+        match nodes[0].properties.last() {
+            Some(&Property::Comment(ref comment)) => {
+                assert!(comment.starts_with("Hello\n\nBiggest mistakes by B: "), "{}", comment);
+                assert!(comment.contains("\nBiggest mistakes by W: "), "{}", comment);
+            },
+            other => panic!("{:?}", other)
+        }
+
+        for node in nodes.iter().skip(1) {
+            assert!(node.properties.iter().any(|p| {
+                match *p {
+                    Property::Comment(ref comment) => comment.starts_with("Black win rate: "),
+                    _ => false
+                }
+            }));
+        }
+    }
+
+    #[test]
+    fn analyze_invalid() {
+        let server = predict::service(RandomEvaluator);
+        let games = sgf::parse("(;GM[1]FF[4]SZ[9];B[ee];W[ee])").unwrap();
+
+        assert!(analyze_game(&games[0], &server.lock()).is_none());
+    }
+
+    #[test]
+    fn analyze_files_in_order() {
+        let dir = ::std::env::temp_dir();
+        let paths = ["a", "b", "c"].iter()
+            .map(|name| {
+                let path = dir.join(format!("dream_go_analyze_{}.sgf", name));
+                let content = match *name {
+                    "a" => "(;GM[1]FF[4]SZ[9];B[ee];W[cc])(;GM[1]FF[4]SZ[9];B[ee];W[ee])",
+                    "b" => "(;GM[1]FF[4]SZ[9];B[ee])",
+                    _ => "(;GM[1]FF[4]SZ[9];B[ee];W[cc])(;GM[1]FF[4]SZ[9];B[cc])"
+                };
+
+                ::std::fs::write(&path, content).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect::<Vec<String>>();
+
+        let server = predict::service(RandomEvaluator);
+        let files = of(&paths, &server).collect::<Vec<AnalysedFile>>();
+
+        assert_eq!(files.iter().map(|f| f.path.clone()).collect::<Vec<String>>(), paths);
+        assert_eq!(files.iter().map(|f| f.games.len()).collect::<Vec<usize>>(), vec! [1, 1, 2]);
+        assert_eq!(files[1].output_path(), dir.join("dream_go_analyze_b.analyzed.sgf"));
+
+        for path in paths.iter() {
+            ::std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod sprt;
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use time;
+
+pub use self::sprt::{Decision, Sprt};
+use go::sgf::{self, CGoban, Outcome, Property, SgfCoordinate, WinBy};
+use go::{Board, Color, Ownership};
+use mcts::evaluator::Evaluator;
+use mcts::predict::{PredictGuard, PredictService};
+use mcts::tree;
+use mcts::{self, Budget};
+use util::config;
+
+/// The number of playouts that each player performs to estimate which stones
+/// are dead at the end of a game.
+const NUM_STATUS_PLAYOUTS: usize = 32;
+
+/// The settings that a player in a match uses, written as any number of
+/// comma separated settings, e.g. `weights=a.json,rollouts=800,time=1.5`. Any
+/// setting that is not given uses the default value from the command-line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// The path to the network weights.
+    pub weights: Option<String>,
+
+    /// The number of rollouts to perform for each move.
+    pub rollouts: Option<usize>,
+
+    /// The maximum amount of time to search for each move.
+    pub time_limit: Option<Duration>,
+
+    /// The number of search threads to use for each game.
+    pub num_threads: Option<usize>
+}
+
+impl FromStr for Profile {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Profile, ()> {
+        let mut profile = Profile {
+            weights: None,
+            rollouts: None,
+            time_limit: None,
+            num_threads: None
+        };
+
+        for part in s.split(',').filter(|part| !part.is_empty()) {
+            let mut key_value = part.splitn(2, '=');
+            let key = key_value.next().ok_or(())?;
+            let value = key_value.next().ok_or(())?;
+
+            match key {
+                "weights" => {
+                    profile.weights = Some(value.to_string());
+                },
+                "rollouts" => {
+                    profile.rollouts = Some(value.parse::<usize>().map_err(|_| ())?);
+                },
+                "time" => {
+                    let secs = value.parse::<f32>().map_err(|_| ())?;
+
+                    profile.time_limit = Some(Duration::from_millis((1000.0 * secs) as u64));
+                },
+                "threads" => {
+                    profile.num_threads = Some(value.parse::<usize>().map_err(|_| ())?);
+                },
+                _ => { return Err(()); }
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+impl Profile {
+    /// Returns the search budget for each move of this profile.
+    pub fn budget(&self) -> Budget {
+        Budget::rollouts(self.rollouts.unwrap_or(*config::NUM_ROLLOUT))
+            .with_time_limit(self.time_limit)
+            .with_early_stop(true)
+    }
+}
+
+/// A player in a match, which is a network together with the settings to
+/// use during the search.
+pub struct Player<N: Evaluator + 'static> {
+    /// The name of this player, which is recorded in the SGF files.
+    pub name: String,
+
+    /// The server that evaluates positions using this players network.
+    pub server: PredictService<N>,
+
+    /// The settings of this player.
+    pub profile: Profile
+}
+
+/// The result of a game played during a match.
+pub struct MatchResult {
+    /// The index of the player who played black.
+    pub black: usize,
+
+    /// The index of the player who won, or `None` if the game was drawn.
+    pub winner: Option<usize>,
+
+    /// The game record.
+    pub game: sgf::GameTree
+}
+
+/// The settings of a player in a single game, and its search tree.
+struct Seat<'a, N: Evaluator + 'static> {
+    server: PredictGuard<'a, N>,
+    profile: Profile,
+    name: String,
+    root: Option<tree::Node<tree::DefaultValue>>
+}
+
+/// Returns the SGF game tree of a finished match game.
+///
+/// # Arguments
+///
+/// * `seats` - the black and the white player
+/// * `moves` - the moves that were played
+/// * `board` - the final board position
+/// * `komi` - the komi of the game
+/// * `outcome` - the outcome of the game
+///
+fn to_game_tree<N>(seats: &[Seat<N>; 2], moves: Vec<sgf::Node>, board: &Board, komi: f32, outcome: &Outcome) -> sgf::GameTree
+    where N: Evaluator + 'static
+{
+    let now = time::now_utc();
+    let iso8601 = time::strftime("%Y-%m-%dT%H:%M:%S%z", &now).unwrap();
+    let root = sgf::Node {
+        properties: vec! [
+            Property::Unknown("GM".to_string(), vec! ["1".to_string()]),
+            Property::Unknown("FF".to_string(), vec! ["4".to_string()]),
+            Property::Application(format!("{}:{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+            Property::Date(iso8601),
+            Property::Size(board.size()),
+            Property::Rules(board.rules().to_string()),
+            Property::Komi(komi),
+            Property::PlayerBlack(seats[0].name.clone()),
+            Property::PlayerWhite(seats[1].name.clone()),
+            Property::Result(outcome.clone())
+        ]
+    };
+
+    sgf::GameTree {
+        nodes: ::std::iter::once(root).chain(moves.into_iter()).collect(),
+        variations: vec! []
+    }
+}
+
+/// Play a single game between the given black and white player, and returns
+/// the winner (if any) and the game record.
+///
+/// # Arguments
+///
+/// * `seats` - the black and the white player
+/// * `komi` - the komi of the game
+///
+fn play_one<N>(mut seats: [Seat<N>; 2], komi: f32) -> (Option<Color>, sgf::GameTree)
+    where N: Evaluator + 'static
+{
+    let mut board = Board::with_rules(19, *config::RULES);
+    let mut moves = vec! [];
+    let mut current = Color::Black;
+    let mut pass_count = 0;
+    let mut count = 0;
+
+    // limit the maximum number of moves to `2 * 19 * 19` to avoid the
+    // engines playing pointless capture sequences at the end of the game
+    while count < 722 && pass_count < 2 {
+        let c = if current == Color::Black { 0 } else { 1 };
+        let (value, index, tree) = {
+            let seat = &mut seats[c];
+
+            mcts::predict::<tree::DefaultValue, N>(
+                &seat.server,
+                seat.profile.num_threads,
+                seat.profile.budget(),
+                seat.root.take(),
+                &board,
+                current,
+                komi
+            )
+        };
+
+        if value < 0.05 {  // resign the game if the evaluation looks bad
+            let outcome = Outcome::Win(current.opposite(), WinBy::Resign);
+            let game = to_game_tree(&seats, moves, &board, komi, &outcome);
+
+            return (Some(current.opposite()), game);
+        } else if index == 361 {
+            moves.push(sgf::Node { properties: vec! [Property::Move(current, None)] });
+            pass_count += 1;
+        } else {
+            let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
+
+            moves.push(sgf::Node {
+                properties: vec! [Property::Move(current, Some(CGoban::to_point(x, y, board.size())))]
+            });
+            board.place(current, x, y);
+            pass_count = 0;
+        }
+
+        // keep both search trees in sync with the board position
+        seats[c].root = tree.forward(index);
+        seats[1 - c].root = seats[1 - c].root.take().and_then(|other| other.forward(index));
+
+        current = current.opposite();
+        count += 1;
+    }
+
+    // remove the dead stones before scoring the game, using both players to
+    // estimate which stones are dead so that neither player decides alone
+    let mut ownership = Ownership::new();
+
+    for seat in seats.iter() {
+        ownership.merge(&mcts::ownership(&seat.server, &board, current, NUM_STATUS_PLAYOUTS));
+    }
+
+    let status = ownership.get_status(&board);
+    let (black, white) = board.get_final_score(&status);
+    let black = black as f32;
+    let white = white as f32 + komi;
+    let (winner, outcome) = if black > white {
+        (Some(Color::Black), Outcome::Win(Color::Black, WinBy::Score(black - white)))
+    } else if white > black {
+        (Some(Color::White), Outcome::Win(Color::White, WinBy::Score(white - black)))
+    } else {
+        (None, Outcome::Draw)
+    };
+
+    (winner, to_game_tree(&seats, moves, &board, komi, &outcome))
+}
+
+/// Play `num_games` games between the two given players, alternating which
+/// player plays black, and return the result of the games over the channel.
+/// At most `NUM_GAMES` games are played in parallel, and no more games are
+/// started once the receiver has been dropped.
+///
+/// # Arguments
+///
+/// * `players` - the two players
+/// * `num_games` - the number of games to play
+/// * `komi` - the komi to use for each pair of games, both games in a pair
+///   are played with the same komi so that neither player is favoured by it
+///
+pub fn play<N>(players: &[Player<N>; 2], num_games: usize, komi: &config::KomiStrategy) -> Receiver<MatchResult>
+    where N: Evaluator + 'static
+{
+    let (sender, receiver) = channel();
+    let num_parallel = ::std::cmp::min(num_games, *config::NUM_GAMES);
+    let started = Arc::new(AtomicUsize::new(0));
+    let komi_list = Arc::new((0..(num_games + 1) / 2).map(|_| komi.sample()).collect::<Vec<f32>>());
+
+    for _ in 0..num_parallel {
+        let started = started.clone();
+        let sender = sender.clone();
+        let servers = [players[0].server.lock().clone_static(), players[1].server.lock().clone_static()];
+        let profiles = [players[0].profile.clone(), players[1].profile.clone()];
+        let names = [players[0].name.clone(), players[1].name.clone()];
+        let komi_list = komi_list.clone();
+
+        thread::spawn(move || {
+            loop {
+                let i = started.fetch_add(1, Ordering::SeqCst);
+
+                if i >= num_games {
+                    break
+                }
+
+                // alternate which player plays black for every game, and play
+                // both games of each pair with the same komi
+                let black = i % 2;
+                let komi = komi_list[i / 2];
+                let white = 1 - black;
+                let num_threads = ::std::cmp::max(*config::NUM_THREADS / num_parallel, 1);
+                let seat = |p: usize| {
+                    Seat {
+                        server: servers[p].clone_static(),
+                        profile: Profile {
+                            num_threads: profiles[p].num_threads.or(Some(num_threads)),
+                            ..profiles[p].clone()
+                        },
+                        name: names[p].clone(),
+                        root: None
+                    }
+                };
+
+                let (winner, game) = play_one([seat(black), seat(white)], komi);
+                let result = MatchResult {
+                    black: black,
+                    winner: winner.map(|color| if color == Color::Black { black } else { white }),
+                    game: game
+                };
+
+                if sender.send(result).is_err() {
+                    break
+                }
+            }
+        });
+    }
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use arena::*;
+    use mcts::evaluator::RandomEvaluator;
+    use mcts::predict;
+    use util::config::KomiStrategy;
+
+    #[test]
+    fn profile() {
+        assert_eq!("".parse::<Profile>(), Ok(Profile {
+            weights: None,
+            rollouts: None,
+            time_limit: None,
+            num_threads: None
+        }));
+        assert_eq!("weights=b.json,rollouts=800,time=1.5,threads=4".parse::<Profile>(), Ok(Profile {
+            weights: Some("b.json".to_string()),
+            rollouts: Some(800),
+            time_limit: Some(Duration::from_millis(1500)),
+            num_threads: Some(4)
+        }));
+        assert_eq!("visits=4".parse::<Profile>(), Err(()));
+        assert_eq!("time".parse::<Profile>(), Err(()));
+    }
+
+    #[test]
+    fn play_random() {
+        let profile = "time=0.01,threads=2".parse::<Profile>().unwrap();
+        let players = [
+            Player { name: "a".to_string(), server: predict::service(RandomEvaluator), profile: profile.clone() },
+            Player { name: "b".to_string(), server: predict::service(RandomEvaluator), profile: profile }
+        ];
+        let komi = KomiStrategy::Choice(vec! [5.5, 6.5, 7.5]);
+        let results = play(&players, 2, &komi).iter().collect::<Vec<MatchResult>>();
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|result| result.black == 0));
+        assert!(results.iter().any(|result| result.black == 1));
+
+        for result in results.iter() {
+            let sgf = format!("{}", result.game);
+            let (pb, pw) = if result.black == 0 { ("PB[a]", "PW[b]") } else { ("PB[b]", "PW[a]") };
+
+            assert!(sgf.contains(pb) && sgf.contains(pw), "{}", sgf);
+        }
+
+        // both games in the pair are played with the same komi
+        let komi = results.iter()
+            .map(|result| {
+                let sgf = format!("{}", result.game);
+                let start = sgf.find("KM[").unwrap();
+                let end = start + sgf[start..].find("]").unwrap();
+
+                sgf[start..end].to_string()
+            })
+            .collect::<Vec<String>>();
+
+        assert_eq!(komi[0], komi[1]);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The z-score of the two-sided 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+/// The decision of a sequential probability ratio test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    /// There are not enough games to accept either hypothesis yet.
+    Continue,
+
+    /// The difference in strength is at most `elo0`.
+    AcceptH0,
+
+    /// The difference in strength is at least `elo1`.
+    AcceptH1
+}
+
+/// Sequential probability ratio test of whether the first player is at least
+/// `elo1` stronger than the second player (H1), or at most `elo0` stronger
+/// (H0). Draws are counted as half a win and half a loss.
+pub struct Sprt {
+    /// The win probability of the first player under H0.
+    p0: f64,
+
+    /// The win probability of the first player under H1.
+    p1: f64,
+
+    /// The lower bound of the log-likelihood ratio, H0 is accepted below it.
+    lower: f64,
+
+    /// The upper bound of the log-likelihood ratio, H1 is accepted above it.
+    upper: f64,
+
+    /// The number of games that the first player won.
+    pub wins: usize,
+
+    /// The number of games that the first player lost.
+    pub losses: usize,
+
+    /// The number of games that were drawn.
+    pub draws: usize
+}
+
+/// Returns the expected score of a player that is `elo` stronger than its
+/// opponent.
+///
+/// # Arguments
+///
+/// * `elo` - the difference in strength
+///
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10.0f64.powf(-elo / 400.0))
+}
+
+/// Returns the difference in strength of a player with the given expected
+/// score against its opponent.
+///
+/// # Arguments
+///
+/// * `score` - the expected score
+///
+fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl Sprt {
+    /// Returns a test without any games of the hypotheses `elo0` and `elo1`,
+    /// with the given probability of false positives and false negatives.
+    ///
+    /// # Arguments
+    ///
+    /// * `elo0` - the difference in strength under H0
+    /// * `elo1` - the difference in strength under H1
+    /// * `alpha` - the probability of accepting H1 when H0 is true
+    /// * `beta` - the probability of accepting H0 when H1 is true
+    ///
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Sprt {
+        assert!(elo0 < elo1);
+
+        Sprt {
+            p0: elo_to_score(elo0),
+            p1: elo_to_score(elo1),
+            lower: (beta / (1.0 - alpha)).ln(),
+            upper: ((1.0 - beta) / alpha).ln(),
+            wins: 0,
+            losses: 0,
+            draws: 0
+        }
+    }
+
+    /// Add the outcome of a game to this test.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_won` - whether the first player won, or `None` if the game
+    ///   was drawn
+    ///
+    pub fn add(&mut self, first_won: Option<bool>) {
+        match first_won {
+            Some(true) => { self.wins += 1 },
+            Some(false) => { self.losses += 1 },
+            None => { self.draws += 1 }
+        }
+    }
+
+    /// Returns the total number of games in this test.
+    pub fn total(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Returns the log-likelihood ratio of H1 over H0.
+    pub fn llr(&self) -> f64 {
+        let wins = self.wins as f64 + 0.5 * self.draws as f64;
+        let losses = self.losses as f64 + 0.5 * self.draws as f64;
+
+        wins * (self.p1 / self.p0).ln() + losses * ((1.0 - self.p1) / (1.0 - self.p0)).ln()
+    }
+
+    /// Returns the lower and upper bounds of the log-likelihood ratio.
+    pub fn bounds(&self) -> (f64, f64) {
+        (self.lower, self.upper)
+    }
+
+    /// Returns which hypothesis, if any, should be accepted.
+    pub fn decision(&self) -> Decision {
+        let llr = self.llr();
+
+        if llr <= self.lower {
+            Decision::AcceptH0
+        } else if llr >= self.upper {
+            Decision::AcceptH1
+        } else {
+            Decision::Continue
+        }
+    }
+
+    /// Returns the win rate of the first player, and the lower and upper
+    /// bound of its 95% (Wilson score) confidence interval.
+    pub fn win_rate(&self) -> (f64, f64, f64) {
+        let n = self.total() as f64;
+
+        if n == 0.0 {
+            return (0.5, 0.0, 1.0);
+        }
+
+        let p = (self.wins as f64 + 0.5 * self.draws as f64) / n;
+        let z2 = Z_95 * Z_95;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+
+        (p, center - margin, center + margin)
+    }
+
+    /// Returns the estimated difference in strength between the first and
+    /// the second player, and the 95% confidence interval of it.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let (p, lower, upper) = self.win_rate();
+        let clamp = |x: f64| x.max(1e-4).min(1.0 - 1e-4);
+
+        (score_to_elo(clamp(p)), score_to_elo(clamp(lower)), score_to_elo(clamp(upper)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arena::sprt::*;
+
+    #[test]
+    fn elo() {
+        assert!((elo_to_score(0.0) - 0.5).abs() < 1e-6);
+        assert!((score_to_elo(elo_to_score(35.0)) - 35.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn accept_h1() {
+        let mut sprt = Sprt::new(0.0, 35.0, 0.05, 0.05);
+
+        while sprt.decision() == Decision::Continue {
+            sprt.add(Some(sprt.total() % 4 != 0));
+        }
+
+        assert_eq!(sprt.decision(), Decision::AcceptH1);
+        assert!(sprt.llr() >= sprt.bounds().1);
+    }
+
+    #[test]
+    fn accept_h0() {
+        let mut sprt = Sprt::new(0.0, 35.0, 0.05, 0.05);
+
+        while sprt.decision() == Decision::Continue {
+            sprt.add(Some(sprt.total() % 2 == 0));
+        }
+
+        assert_eq!(sprt.decision(), Decision::AcceptH0);
+        assert!(sprt.llr() <= sprt.bounds().0);
+    }
+
+    #[test]
+    fn win_rate() {
+        let mut sprt = Sprt::new(0.0, 35.0, 0.05, 0.05);
+
+        for i in 0..100 {
+            sprt.add(if i < 60 { Some(true) } else if i < 90 { Some(false) } else { None });
+        }
+
+        let (p, lower, upper) = sprt.win_rate();
+
+        assert!((p - 0.65).abs() < 1e-6, "{}", p);
+        assert!(lower < p && p < upper, "{} {} {}", lower, p, upper);
+        assert!((lower - 0.552).abs() < 0.01, "{}", lower);
+        assert!((upper - 0.737).abs() < 0.01, "{}", upper);
+    }
+}
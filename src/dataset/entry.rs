@@ -12,7 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use go::{Board, Color, symmetry, CHW};
+use go::sgf::{self, Outcome, Property, Sabaki, SgfCoordinate, WinBy};
+use go::{self, Board, Color, Rules, symmetry, CHW};
 use mcts::predict::PredictGuard;
 use mcts;
 use util::b85;
@@ -23,7 +24,6 @@ use std::mem::transmute;
 use std::io::{self, Cursor};
 
 use rand::{self, Rng};
-use regex::Regex;
 
 #[derive(Clone)]
 enum PolicyEntry {
@@ -57,6 +57,7 @@ pub struct EntryIterator<'a> {
     entries: Vec<((Board, Color, PolicyEntry), &'static symmetry::Transform)>,
     original_size: usize,
     winner: Color,
+    komi: f32,
     server: &'a Option<PredictGuard<'a>>
 }
 
@@ -77,17 +78,20 @@ impl<'a> Iterator for EntryIterator<'a> {
                 let features = board.get_features::<f16, CHW>(current_color, s);
                 let mut policy: Box<[f16]> = if self.server.is_some() && policy.is_partial() {
                     // if this is a partial policy then perform a search at this
-                    // board position and output the result as the policy
+                    // board position and output the result as the policy, which
+                    // requires the full search since every visit counts
                     let num_threads = ::std::cmp::max(
                         *config::NUM_THREADS / *config::NUM_GAMES,
                         1
                     );
-                    let (_, _, tree) = mcts::predict::<mcts::tree::DefaultValue>(
+                    let (_, _, tree) = mcts::predict::<mcts::tree::DefaultValue, _>(
                         self.server.as_ref().unwrap(),
                         Some(num_threads),
+                        mcts::Budget::rollouts(*config::NUM_ROLLOUT),
                         None,
                         board,
-                        current_color
+                        current_color,
+                        self.komi
                     );
 
                     tree.softmax::<f16>()
@@ -96,7 +100,7 @@ impl<'a> Iterator for EntryIterator<'a> {
                 };
 
                 // transform the policy using the same symmetry as the features
-                symmetry::apply(&mut policy, s);
+                symmetry::apply(&mut policy, board.size(), s);
 
                 Entry::new(
                     &features,
@@ -129,82 +133,136 @@ pub struct Entry {
 }
 
 impl Entry {
-    /// Returns all entries that can be extracted from the SGF file contained
-    /// in the given string. If the given game contains invalid moves, or does
+    /// Returns all entries that can be extracted from the main line of the
+    /// given SGF game tree. If the given game contains invalid moves, or does
     /// not have a recorded winner then `None` is returned.
     ///
     /// # Arguments
     ///
-    /// * `src` - the SGF game
+    /// * `game` - the SGF game tree
     /// * `server` - the server to use when transforming partial policies to
     ///   full policies. If no server is given then it will emit partial
     ///   policies.
     ///
-    pub fn all<'a>(src: &String, server: &'a Option<PredictGuard>) -> Option<EntryIterator<'a>> {
-        lazy_static! {
-            static ref LETTERS: [char; 26] = [
-                'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k',
-                'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v',
-                'w', 'x', 'y', 'z'
-            ];
-            static ref WINNER: Regex = Regex::new(r"RE\[([^\]]*)\]").unwrap();
-            static ref SCORED: Regex = Regex::new(r"RE\[[BW]\+[0-9\.]+\]").unwrap();
-            static ref MOVE: Regex = Regex::new(r";([BW])\[([a-z]*)\](?:P\[([^\]]*)\])?").unwrap();
+    pub fn all<'a>(game: &sgf::GameTree, server: &'a Option<PredictGuard>) -> Option<EntryIterator<'a>> {
+        let mut winner = None;
+        let mut is_scored = false;
+        let mut size = 19;
+        let mut rules = None;
+        let mut komi = None;
+        let mut handicap = 0;
+
+        for property in game.root().properties.iter() {
+            match *property {
+                Property::Result(Outcome::Win(color, ref by)) => {
+                    winner = Some(color);
+                    is_scored = match *by {
+                        WinBy::Score(_) => true,
+                        _ => false
+                    };
+                },
+                Property::Size(other) => { size = other; },
+                Property::Rules(ref other) => { rules = other.parse::<Rules>().ok(); },
+                Property::Komi(other) => { komi = Some(other); },
+                Property::Handicap(other) => { handicap = other; },
+                _ => {}
+            }
         }
 
-        let winner = {
-            if let Some(caps) = WINNER.captures(src) {
-                match caps[1].chars().nth(0) {
-                    Some('B') => Color::Black,
-                    Some('W') => Color::White,
-                    _   => { return None; }
-                }
-            } else {
-                return None
-            }
-        };
+        let winner = winner?;
+
+        if size < 5 || size > 19 {
+            return None;
+        }
+
+        // fallback to the chinese rules for any rules that we do not know
+        // about, since they are the closest to most other rule sets
+        let rules = rules.unwrap_or(Rules::Chinese);
+        let komi = komi.unwrap_or_else(|| rules.komi());
 
         let mut entries: Vec<(Board, Color, PolicyEntry)> = vec! [];
-        let mut board = Board::new();
+        let mut board = Board::with_rules(size, rules);
         let mut pass_count = 0;
-        let size = board.size();
-
-        for moves in MOVE.captures_iter(src) {
-            let current_color = match &moves[1] {
-                "B" => Color::Black,
-                "W" => Color::White,
-                _   => unreachable!()
-            };
-            let x = moves[2].chars().nth(0)
-                .and_then(|x| LETTERS.binary_search(&x).ok())
-                .unwrap_or(size);
-            let y = moves[2].chars().nth(1)
-                .and_then(|y| LETTERS.binary_search(&y).ok())
-                .unwrap_or(size);
-            let policy = moves.get(3)
-                .map(|input| { PolicyEntry::Full(input.as_str().to_string()) })
-                .unwrap_or_else(|| {
-                    let index = size*y + x;
-
-                    PolicyEntry::Partial(::std::cmp::min(361, index))
-                });
-
-            if x >= size || y >= size {
-                entries.push((board.clone(), current_color, policy));
-                pass_count += 1;
-            } else if board.is_valid(current_color, x, y) {
-                entries.push((board.clone(), current_color, policy));
-                board.place(current_color, x, y);
-                pass_count = 0;
-            } else {
-                return None;  // invalid game
+
+        for (i, node) in game.main_line().enumerate() {
+            // place any setup stones, which are usually handicap stones, before
+            // any moves are played. If the game has a handicap but no setup
+            // stones then the handicap stones are at the fixed vertices, which
+            // are given with the rows counted from the bottom (as in GTP).
+            for property in node.properties.iter() {
+                let (color, vertices) = match *property {
+                    Property::AddBlack(ref vertices) => (Color::Black, vertices),
+                    Property::AddWhite(ref vertices) => (Color::White, vertices),
+                    Property::AddEmpty(_) => { return None; },  // not supported
+                    _ => { continue; }
+                };
+
+                for &(x, y) in vertices.iter() {
+                    if x >= size || y >= size || board.at(x, y).is_some() {
+                        return None;  // invalid game
+                    }
+
+                    board.setup(color, x, y);
+                }
+            }
+
+            if i == 0 && handicap >= 2 && board.zobrist_hash() == 0 {
+                if let Some(vertices) = go::fixed_handicap(size, handicap) {
+                    for (x, y) in vertices {
+                        let (x, y) = Sabaki::to_point(x, y, size);
+
+                        board.setup(Color::Black, x, y);
+                    }
+                } else {
+                    return None;  // invalid game
+                }
+            }
+
+            let policy = node.properties.iter()
+                .filter_map(|property| {
+                    match *property {
+                        Property::Policy(ref policy) => Some(policy.clone()),
+                        _ => None
+                    }
+                })
+                .next();
+
+            for property in node.properties.iter() {
+                let (current_color, vertex) = match *property {
+                    Property::Move(color, vertex) => (color, vertex),
+                    _ => { continue; }
+                };
+                let vertex = vertex.filter(|&(x, y)| x < size && y < size);
+                let policy = policy.clone()
+                    .map(|input| PolicyEntry::Full(input))
+                    .unwrap_or_else(|| {
+                        match vertex {
+                            None => PolicyEntry::Partial(361),
+                            Some((x, y)) => PolicyEntry::Partial(19*y + x)
+                        }
+                    });
+
+                match vertex {
+                    None => {
+                        entries.push((board.clone(), current_color, policy));
+                        pass_count += 1;
+                    },
+                    Some((x, y)) if board.is_valid(current_color, x, y) => {
+                        entries.push((board.clone(), current_color, policy));
+                        board.place(current_color, x, y);
+                        pass_count = 0;
+                    },
+                    _ => {
+                        return None;  // invalid game
+                    }
+                }
             }
         }
 
         // if the game was scored, then add two pass moves at the end of the game
         // since they are missing from a lot of SGF files and we want to engine
         // to learn that one should pass when the game has finished
-        if SCORED.is_match(src) && pass_count < 2 {
+        if is_scored && pass_count < 2 {
             let last_color = entries.last().map(|&(_, color, _)| color).unwrap_or(Color::Black);
 
             if pass_count == 1 && last_color == Color::Black {
@@ -251,6 +309,7 @@ impl Entry {
             entries: entries,
             original_size: original_size,
             winner: winner,
+            komi: komi,
             server: server
         })
     }
@@ -321,3 +380,32 @@ fn f16_to_bytes(array: &[f16]) -> Box<[u8]> {
 
     cursor.into_inner().into_boxed_slice()
 }
+
+#[cfg(test)]
+mod tests {
+    use dataset::entry::*;
+
+    /// Returns the board position before the first move of the given SGF
+    /// game, as extracted by `Entry::all`.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - the SGF game
+    ///
+    fn initial_board(src: &str) -> Board {
+        let games = sgf::parse(src).unwrap();
+        let entries = Entry::all(&games[0], &None).unwrap();
+
+        entries.entries[0].0 .0.clone()
+    }
+
+    #[test]
+    fn fixed_handicap() {
+        let handicap = initial_board("(;GM[1]FF[4]SZ[9]RE[W+R]HA[3];W[ee];B[dd])");
+        let setup = initial_board("(;GM[1]FF[4]SZ[9]RE[W+R]HA[3]AB[cg][gc][cc];W[ee];B[dd])");
+
+        assert!(handicap == setup);
+        assert_eq!(handicap.at(2, 6), Some(Color::Black));
+        assert_eq!(handicap.at(6, 6), None);
+    }
+}
@@ -21,11 +21,12 @@ use std::marker::PhantomData;
 use std::sync::mpsc::{sync_channel, SyncSender, Receiver};
 
 pub use self::entry::Entry;
+use go::sgf;
 use mcts::predict::PredictService;
 use util::config;
 
 /// Iterator over all positions within a single SGF collection, the SGF
-/// collection should contain exactly one full game tree per line.
+/// collection should contain one or more full game trees per line.
 pub struct Dataset<'a> {
     /// The channel where finish entries are delivered by the worker
     /// threads.
@@ -52,21 +53,25 @@ impl<'a> Dataset<'a> {
         let num_games = *config::NUM_GAMES;
         let (t_entry, r_entry) = sync_channel(num_games);
         let workers = (0..num_games).map(|_| {
-            let (t_line, r_line) = sync_channel(num_games);
+            let (t_line, r_line) = sync_channel::<String>(num_games);
             let t_entry = t_entry.clone();
             let server = server.map(|s| s.lock().clone_static());
             let worker = thread::spawn(move || {
                 for line in r_line.iter() {
-                    // parse the game, and then send the samples back to the
+                    // parse the games, and then send the samples back to the
                     // receivers
-                    if let Some(entries) = Entry::all(&line, &server) {
-                        let num_samples = ::std::cmp::max(1, match *config::NUM_SAMPLES {
-                            config::SamplingStrategy::Percent(pct) => (pct * (entries.original_len() as f32)) as usize,
-                            config::SamplingStrategy::Fixed(f) => f
-                        });
-
-                        for entry in entries.take(num_samples) {
-                            t_entry.send(entry).unwrap();
+                    let games = sgf::parse(&line).unwrap_or_else(|_| vec! []);
+
+                    for game in games.iter() {
+                        if let Some(entries) = Entry::all(game, &server) {
+                            let num_samples = ::std::cmp::max(1, match *config::NUM_SAMPLES {
+                                config::SamplingStrategy::Percent(pct) => (pct * (entries.original_len() as f32)) as usize,
+                                config::SamplingStrategy::Fixed(f) => f
+                            });
+
+                            for entry in entries.take(num_samples) {
+                                t_entry.send(entry).unwrap();
+                            }
                         }
                     }
                 }
@@ -12,6 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io;
+
+use util::binary;
+
 /// 
 pub struct CircularIterator<'a> {
     count: usize,
@@ -76,6 +80,57 @@ impl CircularBuf {
         self.position = N_MOD_SIX[self.position];
     }
 
+    /// Sets the given element to the given value in every buffer in this
+    /// stack.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `index` - the index of the element to set
+    /// * `value` - the value to set the element to
+    /// 
+    pub fn set_all(&mut self, index: usize, value: u8) {
+        for buf in self.buf.iter_mut() {
+            buf[index] = value;
+        }
+    }
+
+    /// Write every buffer in this stack to the given writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the writer to write to
+    ///
+    pub fn write_into<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        binary::write_u8(f, self.position as u8)?;
+
+        for buf in self.buf.iter() {
+            f.write_all(buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a stack that was previously written using `write_into`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the reader to read from
+    ///
+    pub fn read_from<R: io::Read>(f: &mut R) -> io::Result<CircularBuf> {
+        let mut out = CircularBuf::new();
+
+        out.position = binary::read_u8(f)? as usize;
+        if out.position >= 6 {
+            return Err(binary::invalid_data("history position out of range"));
+        }
+
+        for buf in out.buf.iter_mut() {
+            f.read_exact(buf)?;
+        }
+
+        Ok(out)
+    }
+
     /// Returns an iterator over all the buffers in the stack starting with the
     /// most recent one, and going backward in time.
     pub fn iter<'a>(&'a self) -> CircularIterator<'a> {
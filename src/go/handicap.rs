@@ -0,0 +1,137 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Returns the maximum number of fixed handicap stones that can be placed
+/// on a board of the given size.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+///
+pub fn max_fixed_handicap(size: usize) -> usize {
+    if size < 7 {
+        0
+    } else if size % 2 == 0 || size == 7 {
+        4
+    } else {
+        9
+    }
+}
+
+/// Returns the vertices `(x, y)` of the given number of fixed handicap
+/// stones, according to the placement in the GTP specification, or `None`
+/// if that number of stones can not be placed on a board of the given size.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+/// * `num_stones` - the number of handicap stones
+///
+pub fn fixed_handicap(size: usize, num_stones: usize) -> Option<Vec<(usize, usize)>> {
+    if num_stones < 2 || num_stones > max_fixed_handicap(size) {
+        return None;
+    }
+
+    let lo = if size >= 13 { 3 } else { 2 };
+    let hi = size - lo - 1;
+    let mid = size / 2;
+    let vertices = match num_stones {
+        2 => vec! [(lo, lo), (hi, hi)],
+        3 => vec! [(lo, lo), (hi, hi), (lo, hi)],
+        4 => vec! [(lo, lo), (hi, hi), (lo, hi), (hi, lo)],
+        5 => vec! [(lo, lo), (hi, hi), (lo, hi), (hi, lo), (mid, mid)],
+        6 => vec! [(lo, lo), (hi, hi), (lo, hi), (hi, lo), (lo, mid), (hi, mid)],
+        7 => vec! [(lo, lo), (hi, hi), (lo, hi), (hi, lo), (lo, mid), (hi, mid), (mid, mid)],
+        8 => vec! [(lo, lo), (hi, hi), (lo, hi), (hi, lo), (lo, mid), (hi, mid), (mid, lo), (mid, hi)],
+        _ => vec! [(lo, lo), (hi, hi), (lo, hi), (hi, lo), (lo, mid), (hi, mid), (mid, lo), (mid, hi), (mid, mid)]
+    };
+
+    Some(vertices)
+}
+
+/// Returns the vertices `(x, y)` of the given number of handicap stones,
+/// where the engine is free to choose the placement. This uses the fixed
+/// placement when possible, and otherwise spreads the remaining stones out
+/// as far from each other, and from the edge, as possible.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+/// * `num_stones` - the number of handicap stones, at most `size * size - 1`
+///
+pub fn free_handicap(size: usize, num_stones: usize) -> Vec<(usize, usize)> {
+    let num_fixed = ::std::cmp::min(num_stones, max_fixed_handicap(size));
+    let mut vertices = fixed_handicap(size, num_fixed).unwrap_or_else(|| vec! []);
+
+    while vertices.len() < num_stones {
+        let distance = |x: usize, y: usize| -> usize {
+            let to_edge = [x + 1, y + 1, size - x, size - y].iter().cloned().min().unwrap();
+            let to_stone = vertices.iter()
+                .map(|&(ox, oy)| {
+                    let dx = if x > ox { x - ox } else { ox - x };
+                    let dy = if y > oy { y - oy } else { oy - y };
+
+                    dx + dy
+                })
+                .min()
+                .unwrap_or(size);
+
+            ::std::cmp::min(2 * to_edge, to_stone)
+        };
+
+        let best = (0..(size * size))
+            .map(|i| (i % size, i / size))
+            .filter(|&(x, y)| !vertices.contains(&(x, y)))
+            .max_by_key(|&(x, y)| distance(x, y))
+            .unwrap();
+
+        vertices.push(best);
+    }
+
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use go::handicap::*;
+
+    #[test]
+    fn fixed_19() {
+        assert_eq!(fixed_handicap(19, 1), None);
+        assert_eq!(fixed_handicap(19, 2), Some(vec! [(3, 3), (15, 15)]));
+        assert_eq!(fixed_handicap(19, 9).map(|v| v.len()), Some(9));
+        assert_eq!(fixed_handicap(19, 10), None);
+    }
+
+    #[test]
+    fn fixed_small() {
+        assert_eq!(fixed_handicap(5, 2), None);
+        assert_eq!(fixed_handicap(7, 4), Some(vec! [(2, 2), (4, 4), (2, 4), (4, 2)]));
+        assert_eq!(fixed_handicap(7, 5), None);
+        assert_eq!(fixed_handicap(9, 5).map(|v| v[4]), Some((4, 4)));
+    }
+
+    #[test]
+    fn free() {
+        for &(size, max_stones) in &[(5, 24), (9, 80), (19, 32)] {
+            for num_stones in 2..(max_stones + 1) {
+                let vertices = free_handicap(size, num_stones);
+
+                assert_eq!(vertices.len(), num_stones);
+                assert!(vertices.iter().all(|&(x, y)| x < size && y < size));
+                assert!((1..vertices.len()).all(|i| !vertices[..i].contains(&vertices[i])));
+            }
+        }
+    }
+}
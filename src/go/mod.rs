@@ -15,16 +15,28 @@
 mod asm;
 mod circular_buf;
 mod codegen;
+mod handicap;
+mod ownership;
+mod persistent_set;
+mod rules;
 mod small_set;
 pub mod sgf;
 pub mod symmetry;
 mod zobrist;
 
+pub use self::handicap::*;
+pub use self::ownership::*;
+pub use self::rules::*;
+
 use std::fmt;
 use std::collections::VecDeque;
 use std::hash::{Hash, Hasher};
+use std::io;
+
+use util::binary;
 
 use self::circular_buf::CircularBuf;
+use self::persistent_set::PersistentSet;
 use self::small_set::SmallSet;
 
 #[repr(u8)]
@@ -122,6 +134,10 @@ pub struct Board {
     /// should in addition contain at least one extra padding element that
     /// contains `0xff`, this extra element is used to the out-of-bounds
     /// index to avoid extra branches.
+    ///
+    /// Boards smaller than `19x19` are stored in the top-left corner of
+    /// this array, with every vertex outside of the board also set to
+    /// `0xff` so that they are treated the same as the padding.
     vertices: [u8; 368],
 
     /// The index of a stone that is strongly connected to each vertex in
@@ -139,7 +155,21 @@ pub struct Board {
     zobrist_hash: u64,
 
     /// The zobrist hash of the most recent board positions.
-    zobrist_history: SmallSet
+    zobrist_history: SmallSet,
+
+    /// The zobrist hash of every board position in this game, according
+    /// to the super-ko variant of the rules. This is shared between clones
+    /// of this board.
+    superko_history: PersistentSet,
+
+    /// The width and height of this board.
+    size: usize,
+
+    /// The rules that this game is being played under.
+    rules: Rules,
+
+    /// The number of stones of each color that has been captured.
+    prisoners: [u16; 3]
 }
 
 impl Clone for Board {
@@ -150,34 +180,102 @@ impl Clone for Board {
             history: self.history.clone(),
             count: self.count,
             zobrist_hash: self.zobrist_hash,
-            zobrist_history: self.zobrist_history.clone()
+            zobrist_history: self.zobrist_history.clone(),
+            superko_history: self.superko_history.clone(),
+            size: self.size,
+            rules: self.rules,
+            prisoners: self.prisoners
         }
     }
 }
 
 impl Board {
-    /// Returns an empty board state.
+    /// Returns an empty `19x19` board state.
     pub fn new() -> Board {
+        Board::with_size(19)
+    }
+
+    /// Returns an empty board state with the given width and height, that
+    /// is played under the default rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board, between `5` and `19`
+    ///
+    pub fn with_size(size: usize) -> Board {
+        Board::with_rules(size, Rules::default())
+    }
+
+    /// Returns an empty board state with the given width and height, that
+    /// is played under the given rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board, between `5` and `19`
+    /// * `rules` - the rules of the game
+    ///
+    pub fn with_rules(size: usize, rules: Rules) -> Board {
+        assert!(size >= 5 && size <= 19, "unsupported board size {}", size);
+
         let mut board = Board {
-            vertices: [0; 368],
+            vertices: [0xff; 368],
             next_vertex: [0; 361],
             history: CircularBuf::new(),
             count: 0,
             zobrist_hash: 0,
-            zobrist_history: SmallSet::new()
+            zobrist_history: SmallSet::new(),
+            superko_history: PersistentSet::new(),
+            size: size,
+            rules: rules,
+            prisoners: [0; 3]
         };
 
-        for i in 361..368 {
-            board.vertices[i] = 0xff;
+        for y in 0..size {
+            for x in 0..size {
+                board.vertices[19 * y + x] = 0;
+            }
         }
 
+        // the empty board is also part of the history, with black to move
+        let history_hash = board.history_hash(0, Color::White);
+
+        board.superko_history.push(history_hash);
         board
     }
 
     /// Returns the width and height of this board.
     #[inline]
     pub fn size(&self) -> usize {
-        19
+        self.size
+    }
+
+    /// Returns the rules that this game is being played under.
+    #[inline]
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Returns the number of stones of the given color that has been
+    /// captured.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the captured stones
+    ///
+    #[inline]
+    pub fn prisoners(&self, color: Color) -> usize {
+        self.prisoners[color as usize] as usize
+    }
+
+    /// Returns true if the given HW index is on this board.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the HW index to check
+    ///
+    #[inline]
+    pub fn is_on_board(&self, index: usize) -> bool {
+        index < 361 && self.vertices[index] != 0xff
     }
 
     /// Returns the zobrist hash of this board.
@@ -186,12 +284,116 @@ impl Board {
         self.zobrist_hash
     }
 
+    /// Returns the zobrist hash of this board combined with the player that
+    /// made the most recent move, which distinguish between two identical
+    /// boards with a different player to move.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player that made the most recent move
+    ///
+    #[inline]
+    pub fn zobrist_hash_after(&self, color: Color) -> u64 {
+        self.zobrist_hash ^ zobrist::TURN[color as usize]
+    }
+
     /// Returns the number of moves that has been played on this board.
     #[inline]
     pub fn count(&self) -> u16 {
         self.count
     }
 
+    /// Write the entire state of this board, including the history that is
+    /// necessary to detect super-ko, to the given writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the writer to write to
+    ///
+    pub fn write_into<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        let rules = match self.rules {
+            Rules::Chinese => 0,
+            Rules::Japanese => 1,
+            Rules::TrompTaylor => 2,
+            Rules::NewZealand => 3
+        };
+
+        binary::write_u8(f, self.size as u8)?;
+        binary::write_u8(f, rules)?;
+        f.write_all(&self.vertices)?;
+        for &next in self.next_vertex.iter() {
+            binary::write_u16(f, next)?;
+        }
+
+        self.history.write_into(f)?;
+        binary::write_u16(f, self.count)?;
+        binary::write_u64(f, self.zobrist_hash)?;
+        self.zobrist_history.write_into(f)?;
+        self.superko_history.write_into(f)?;
+        for &prisoners in self.prisoners.iter() {
+            binary::write_u16(f, prisoners)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a board that was previously written using `write_into`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the reader to read from
+    ///
+    pub fn read_from<R: io::Read>(f: &mut R) -> io::Result<Board> {
+        let size = binary::read_u8(f)? as usize;
+        let rules = match binary::read_u8(f)? {
+            0 => Rules::Chinese,
+            1 => Rules::Japanese,
+            2 => Rules::TrompTaylor,
+            3 => Rules::NewZealand,
+            _ => { return Err(binary::invalid_data("unknown rules")) }
+        };
+
+        if size < 5 || size > 19 {
+            return Err(binary::invalid_data("unsupported board size"));
+        }
+
+        let mut vertices = [0; 368];
+        let mut next_vertex = [0; 361];
+
+        f.read_exact(&mut vertices)?;
+        for next in next_vertex.iter_mut() {
+            *next = binary::read_u16(f)?;
+
+            if *next >= 361 {
+                return Err(binary::invalid_data("vertex out of range"));
+            }
+        }
+
+        let history = CircularBuf::read_from(f)?;
+        let count = binary::read_u16(f)?;
+        let zobrist_hash = binary::read_u64(f)?;
+        let zobrist_history = SmallSet::read_from(f)?;
+        let superko_history = PersistentSet::read_from(f)?;
+        let mut prisoners = [0; 3];
+
+        for p in prisoners.iter_mut() {
+            *p = binary::read_u16(f)?;
+        }
+
+        Ok(Board {
+            vertices: vertices,
+            next_vertex: next_vertex,
+            history: history,
+            count: count,
+            zobrist_hash: zobrist_hash,
+            zobrist_history: zobrist_history,
+            superko_history: superko_history,
+            size: size,
+            rules: rules,
+            prisoners: prisoners
+        })
+    }
+
     /// Returns the color (if the vertex is not empty) of the stone at
     /// the given coordinates.
     ///
@@ -312,6 +514,7 @@ impl Board {
 
             self.zobrist_hash ^= zobrist::TABLE[c][current];
             self.vertices[current] = 0;
+            self.prisoners[c] += 1;
 
             current = self.next_vertex[current] as usize;
             if current == index {
@@ -406,8 +609,62 @@ impl Board {
         next_vertex[index] = other_prev;
     }
 
-    /// Returns whether the given move is valid according to the
-    /// Tromp-Taylor rules.
+    /// Returns whether playing the given move would leave the group that
+    /// it is part of without any liberties, after all captures has been
+    /// performed.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `index` - the HW index of the move
+    ///
+    fn _is_suicide(&self, color: Color, index: usize) -> bool {
+        let n = N!(self.vertices, index);
+        let e = E!(self.vertices, index);
+        let s = S!(self.vertices, index);
+        let w = W!(self.vertices, index);
+
+        // check for direct liberties
+        if n == 0 { return false; }
+        if e == 0 { return false; }
+        if s == 0 { return false; }
+        if w == 0 { return false; }
+
+        // check for the following two conditions simplied into one case:
+        //
+        // 1. If a neighbour is friendly then we are fine if it has at
+        //    least two liberties.
+        // 2. If a neighbour is unfriendly then we are fine if it has less
+        //    than two liberties (i.e. one).
+        let current = color as u8;
+
+        if n != 0xff && (n == current) == self.has_two_liberties(index + 19) { return false; }
+        if e != 0xff && (e == current) == self.has_two_liberties(index + 1) { return false; }
+        if s != 0xff && (s == current) == self.has_two_liberties(index - 19) { return false; }
+        if w != 0xff && (w == current) == self.has_two_liberties(index - 1) { return false; }
+
+        true  // move is suicide :'(
+    }
+
+    /// Returns true if the given vertex has at least one neighbour of the
+    /// given color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the neighbours to look for
+    /// * `index` - the HW index of the vertex
+    ///
+    fn has_neighbour(&self, color: Color, index: usize) -> bool {
+        let current = color as u8;
+
+        N!(self.vertices, index) == current
+            || E!(self.vertices, index) == current
+            || S!(self.vertices, index) == current
+            || W!(self.vertices, index) == current
+    }
+
+    /// Returns whether the given move is valid according to the rules of
+    /// this board, without considering super-ko.
     ///
     /// # Arguments
     ///
@@ -416,38 +673,34 @@ impl Board {
     ///
     pub fn _is_valid(&self, color: Color, index: usize) -> bool {
         self.vertices[index] == 0 && {
-            let n = N!(self.vertices, index);
-            let e = E!(self.vertices, index);
-            let s = S!(self.vertices, index);
-            let w = W!(self.vertices, index);
-
-            // check for direct liberties
-            if n == 0 { return true; }
-            if e == 0 { return true; }
-            if s == 0 { return true; }
-            if w == 0 { return true; }
-
-            // check for the following two conditions simplied into one case:
-            //
-            // 1. If a neighbour is friendly then we are fine if it has at
-            //    least two liberties.
-            // 2. If a neighbour is unfriendly then we are fine if it has less
-            //    than two liberties (i.e. one).
-            let current = color as u8;
-
-            if n != 0xff && (n == current) == self.has_two_liberties(index + 19) { return true; }
-            if e != 0xff && (e == current) == self.has_two_liberties(index + 1) { return true; }
-            if s != 0xff && (s == current) == self.has_two_liberties(index - 19) { return true; }
-            if w != 0xff && (w == current) == self.has_two_liberties(index - 1) { return true; }
-
-            false  // move is suicide :'(
+            !self._is_suicide(color, index) || (
+                // a suicide of a single stone is the same as passing, so we
+                // only allow suicide if it removes one of our groups
+                self.rules.allow_suicide() && self.has_neighbour(color, index)
+            )
+        }
+    }
+
+    /// Returns the zobrist hash to store in, or look for in, the history of
+    /// this board for the given position. Under situational super-ko the
+    /// player that made the most recent move is also part of the position.
+    ///
+    /// # Arguments
+    ///
+    /// * `zobrist_hash` - the zobrist hash of the board position
+    /// * `color` - the color of the player that made the most recent move
+    ///
+    fn history_hash(&self, zobrist_hash: u64, color: Color) -> u64 {
+        match self.rules.superko() {
+            Superko::Positional => zobrist_hash,
+            Superko::Situational => zobrist_hash ^ zobrist::TURN[color as usize]
         }
     }
 
     /// Returns whether playing the given rule violates the super-ko
-    /// rule. This functions assumes the given move is not suicide and
-    /// does not play on top of another stone, these pre-conditions can
-    /// be checked with the `_is_valid` function.
+    /// rule, considering every position since the start of the game. This
+    /// functions assumes the given move is valid otherwise, which can be
+    /// checked with the `_is_valid` function.
     /// 
     /// # Arguments
     /// 
@@ -455,6 +708,15 @@ impl Board {
     /// * `index` - the HW index of the move
     /// 
     pub fn _is_ko(&self, color: Color, index: usize) -> bool {
+        if self.rules.allow_suicide() && self._is_suicide(color, index) {
+            // suicide is rare enough that it is not worth the complexity to
+            // pretend play it, so just play it and look at the result.
+            let mut other = self.clone();
+            other.place(color, index % 19, index / 19);
+
+            return self.superko_history.contains(self.history_hash(other.zobrist_hash, color));
+        }
+
         let mut zobrist_pretend = self.zobrist_hash ^ zobrist::TABLE[color as usize][index];
         let opponent = color.opposite() as u8;
 
@@ -471,11 +733,11 @@ impl Board {
             zobrist_pretend ^= self.capture_if(opponent as usize, index - 1);
         }
 
-        self.zobrist_history.contains(zobrist_pretend)
+        self.superko_history.contains(self.history_hash(zobrist_pretend, color))
     }
 
-    /// Returns whether the given move is valid according to the
-    /// Tromp-Taylor rules.
+    /// Returns whether the given move is valid according to the rules of
+    /// this board.
     ///
     /// # Arguments
     ///
@@ -486,7 +748,7 @@ impl Board {
     pub fn is_valid(&self, color: Color, x: usize, y: usize) -> bool {
         let index = 19 * y + x;
 
-        self._is_valid(color, index) && !self._is_ko(color, index)
+        x < self.size && y < self.size && self._is_valid(color, index) && !self._is_ko(color, index)
     }
 
     /// Place the given stone on the board without checking if it is legal, and
@@ -521,7 +783,8 @@ impl Board {
 
     /// Place the given stone on the board without checking if it is legal, the
     /// board is then updated according to the Tromp-Taylor rules with the
-    /// except that ones own color is not cleared.
+    /// except that ones own color is only cleared if the rules of this board
+    /// allow suicide.
     ///
     /// # Arguments
     ///
@@ -547,6 +810,10 @@ impl Board {
         if S!(self.vertices, index) == opponent && !self.has_one_liberty(index - 19) { self.capture(index - 19); }
         if W!(self.vertices, index) == opponent && !self.has_one_liberty(index - 1) { self.capture(index - 1); }
 
+        // remove our own group if this move was suicide, this can only happen
+        // for rules that allow it
+        if self.rules.allow_suicide() && !self.has_one_liberty(index) { self.capture(index); }
+
         // add the current board state to the history *after* we have updated it because:
         //
         // 1. that way we do not need a special case to retrieve the current board when
@@ -554,7 +821,36 @@ impl Board {
         // 2. the circular stack starts with all buffers as zero, so there is no need to
         //    keep track of the initial board state.
         self.history.push(&self.vertices);
+        let history_hash = self.history_hash(self.zobrist_hash, color);
+
+        self.zobrist_history.push(self.zobrist_hash);
+        self.superko_history.push(history_hash);
+    }
+
+    /// Place the given setup stone on the board, such as a handicap stone. A
+    /// setup stone is not a move, so it does not capture any stones and it
+    /// is part of every historic board state that is used in the features.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the stone
+    /// * `x` - The column of the stone
+    /// * `y` - The row of the stone
+    ///
+    pub fn setup(&mut self, color: Color, x: usize, y: usize) {
+        let index = 19 * y + x;
+
+        assert!(x < self.size && y < self.size && self.vertices[index] == 0);
+
+        Board::place_no_capture(&mut self.vertices, &mut self.next_vertex, color, index);
+
+        self.zobrist_hash ^= zobrist::TABLE[color as usize][index];
+        self.history.set_all(index, color as u8);
+
+        let history_hash = self.history_hash(self.zobrist_hash, color);
+
         self.zobrist_history.push(self.zobrist_hash);
+        self.superko_history.push(history_hash);
     }
 
     /// Returns true if playing a stone at the given index successfully
@@ -813,8 +1109,8 @@ impl Board {
         }
     }
 
-    /// Returns whether the given move is valid according to the
-    /// Tromp-Taylor rules using the provided `memoize` table to
+    /// Returns whether the given move is valid according to the rules
+    /// of this board using the provided `memoize` table to
     /// determine the number of liberties.
     /// 
     /// This function also assume the given vertex is empty and does
@@ -853,7 +1149,8 @@ impl Board {
         if s != 0xff && (s == current) == (self.get_num_liberties(index - 19, memoize) >= 2) { return true; }
         if w != 0xff && (w == current) == (self.get_num_liberties(index - 1, memoize) >= 2) { return true; }
 
-        false  // move is suicide :'(
+        // move is suicide :'(
+        self.rules.allow_suicide() && self.has_neighbour(color, index)
     }
 
     /// Returns the number of liberties of the group connected to the given stone
@@ -988,7 +1285,7 @@ impl Board {
         let c_1: T = T::from(1.0);
 
         let mut features = vec! [c_0; 32 * 361];
-        let symmetry_table = symmetry.get_table();
+        let symmetry_table = symmetry.get_table(self.size);
         let is_black = if color == Color::Black { c_1 } else { c_0 };
         let current = color as u8;
 
@@ -996,6 +1293,10 @@ impl Board {
         let mut liberties = [0; 368];
 
         for index in 0..361 {
+            if !self.is_on_board(index) {
+                continue;
+            }
+
             let other = symmetry_table[index] as usize;
 
             features[O::index(0, other)] = c_1;
@@ -1022,9 +1323,14 @@ impl Board {
                     self.get_num_liberties_if(color, index, &mut liberties),
                     6
                 );
-                let l = 7 + num_liberties;
 
-                features[O::index(l, other)] = c_1;
+                // a suicide move has no liberties after the move, so it
+                // does not belong in any of the planes
+                if num_liberties > 0 {
+                    let l = 7 + num_liberties;
+
+                    features[O::index(l, other)] = c_1;
+                }
             }
         }
 
@@ -1033,7 +1339,7 @@ impl Board {
             for index in 0..361 {
                 let other = symmetry_table[index] as usize;
 
-                if vertices[index] == 0 {
+                if vertices[index] == 0 || vertices[index] == 0xff {
                     // pass
                 } else if vertices[index] == current {
                     let p = 14 + i;
@@ -1069,11 +1375,13 @@ impl Board {
     }
 
     /// Returns the score for each player `(black, white)` of the
-    /// current board state according to the Tromp-Taylor rules.
+    /// current board state according to the scoring method of the rules
+    /// of this board. Every stone on the board is assumed to be alive.
     /// 
     /// This method does not take any komi into account, you will
     /// need to add it yourself.
     pub fn get_score(&self) -> (usize, usize) {
+        let is_area = self.rules.scoring() == Scoring::Area;
         let mut black = 0;
         let mut white = 0;
 
@@ -1082,10 +1390,12 @@ impl Board {
             let white_distance = self.get_territory_distance(Color::White);
 
             for i in 0..361 {
-                if black_distance[i] == 0 as u8 {
-                    black += 1;  // black has stone at vertex
+                if !self.is_on_board(i) {
+                    // pass
+                } else if black_distance[i] == 0 as u8 {
+                    if is_area { black += 1; }  // black has stone at vertex
                 } else if white_distance[i] == 0 as u8 {
-                    white += 1;  // white has stone at vertex
+                    if is_area { white += 1; }  // white has stone at vertex
                 } else if white_distance[i] == 0xff {
                     black += 1;  // only reachable from black
                 } else if black_distance[i] == 0xff {
@@ -1094,6 +1404,11 @@ impl Board {
             }
         }
 
+        if !is_area {
+            black += self.prisoners(Color::White);
+            white += self.prisoners(Color::Black);
+        }
+
         (black, white)
     }
 }
@@ -1112,19 +1427,21 @@ impl fmt::Display for Board {
             'v', 'w', 'x', 'y', 'z'
         ];
 
+        let size = self.size;
+
         write!(f, "    ")?;
-        for i in 0..19 { write!(f, " {}", LETTERS[i])?; }
+        for i in 0..size { write!(f, " {}", LETTERS[i])?; }
         write!(f, "\n")?;
         write!(f, "   \u{256d}")?;
-        for _ in 0..19 { write!(f, "\u{2500}\u{2500}")?; }
+        for _ in 0..size { write!(f, "\u{2500}\u{2500}")?; }
         write!(f, "\u{2500}\u{256e}\n")?;
 
-        for y in 0..19 {
-            let y = 18 - y;
+        for y in 0..size {
+            let y = size - y - 1;
 
             write!(f, "{:2} \u{2502}", 1 + y)?;
 
-            for x in 0..19 {
+            for x in 0..size {
                 let index = 19 * y + x;
 
                 if self.vertices[index] == 0 {
@@ -1140,10 +1457,10 @@ impl fmt::Display for Board {
         }
 
         write!(f, "   \u{2570}")?;
-        for _ in 0..19 { write!(f, "\u{2500}\u{2500}")?; }
+        for _ in 0..size { write!(f, "\u{2500}\u{2500}")?; }
         write!(f, "\u{2500}\u{256f}\n")?;
         write!(f, "    ")?;
-        for i in 0..19 { write!(f, " {}", LETTERS[i])?; }
+        for i in 0..size { write!(f, " {}", LETTERS[i])?; }
         write!(f, "\n")?;
         write!(f, "    \u{25cf} Black    \u{25cb} White\n")?;
 
@@ -1156,6 +1473,8 @@ impl Hash for Board {
         // include the entire zobrist hash history, since we use six planes of
         // historic data in the features, and transposing them does not necessary
         // result in the same neural network output (mostly due to super-ko).
+        state.write_usize(self.size);
+
         for z in self.zobrist_history.iter() {
             state.write_u64(z);
         }
@@ -1168,7 +1487,7 @@ impl PartialEq for Board {
             .zip(other.zobrist_history.iter())
             .all(|(a, b)| a == b);
 
-        history && self.vertices.iter().zip(other.vertices.iter()).all(|(a, b)| a == b)
+        history && self.size == other.size && self.vertices.iter().zip(other.vertices.iter()).all(|(a, b)| a == b)
     }
 }
 
@@ -1274,6 +1593,128 @@ mod tests {
         assert!(!board.is_valid(Color::Black, 0, 0));
     }
 
+    /// Test that the basic ko is detected using situational super-ko, which
+    /// requires the moves to alternate between the players.
+    #[test]
+    fn ko_situational() {
+        let mut board = Board::with_rules(19, Rules::NewZealand);
+
+        board.place(Color::Black, 0, 0);
+        board.place(Color::White, 1, 0);
+        board.place(Color::Black, 1, 1);
+        board.place(Color::White, 5, 5);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::White, 0, 1);
+
+        assert!(!board.is_valid(Color::Black, 0, 0));
+    }
+
+    /// Test that suicide of more than one stone is only allowed by the rules
+    /// that allow it, and that it removes the suicided stones.
+    #[test]
+    fn suicide_multiple() {
+        for &rules in &[Rules::Chinese, Rules::Japanese, Rules::TrompTaylor, Rules::NewZealand] {
+            let mut board = Board::with_rules(19, rules);
+
+            board.place(Color::Black, 0, 0);
+            board.place(Color::White, 1, 0);
+            board.place(Color::White, 1, 1);
+            board.place(Color::White, 0, 2);
+
+            assert_eq!(board.is_valid(Color::Black, 0, 1), rules.allow_suicide());
+
+            if rules.allow_suicide() {
+                board.place(Color::Black, 0, 1);
+
+                assert_eq!(board.at(0, 0), None);
+                assert_eq!(board.at(0, 1), None);
+                assert_eq!(board.prisoners(Color::Black), 2);
+            }
+        }
+    }
+
+    /// Test that suicide of a single stone is never allowed.
+    #[test]
+    fn suicide_single() {
+        for &rules in &[Rules::Chinese, Rules::Japanese, Rules::TrompTaylor, Rules::NewZealand] {
+            let mut board = Board::with_rules(19, rules);
+
+            board.place(Color::White, 1, 0);
+            board.place(Color::White, 0, 1);
+
+            assert!(!board.is_valid(Color::Black, 0, 0));
+        }
+    }
+
+    #[test]
+    fn score_territory() {
+        let mut board = Board::with_rules(19, Rules::Japanese);
+        board.place(Color::White, 1, 0);
+        board.place(Color::White, 0, 1);
+        board.place(Color::White, 1, 1);
+        board.place(Color::Black, 2, 0);
+        board.place(Color::Black, 2, 1);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::Black, 1, 2);
+
+        assert_eq!(board.get_score(), (353, 1));
+    }
+
+    #[test]
+    fn score_prisoners() {
+        let mut board = Board::with_rules(19, Rules::Japanese);
+
+        board.place(Color::Black,  9,  9);
+        board.place(Color::White,  8,  9);
+        board.place(Color::White, 10,  9);
+        board.place(Color::White,  9,  8);
+        board.place(Color::White,  9, 10);
+
+        assert_eq!(board.prisoners(Color::Black), 1);
+        assert_eq!(board.get_score(), (0, 358));
+    }
+
+    /// Test that a cycle of ten moves between five simultaneous kos is
+    /// detected, which requires the full history of the game.
+    #[test]
+    fn superko_long_cycle() {
+        let kos = [(0, 0, 1), (5, 0, 1), (10, 0, 1), (15, 0, 1), (0, 18, -1)];
+        let mut board = Board::new();
+
+        for (i, &(x, y, dy)) in kos.iter().enumerate() {
+            let y1 = (y as isize + dy) as usize;
+
+            board.place(Color::Black, x, y);
+            board.place(Color::Black, x + 1, y1);
+            board.place(Color::White, x + 2, y1);
+            board.place(Color::White, x + 3, y);
+
+            if i % 2 == 0 {
+                board.place(Color::White, x + 1, y);
+            } else {
+                board.place(Color::Black, x + 2, y);
+            }
+        }
+
+        // flip every ko twice, except the last ko which is only flipped
+        // once
+        let moves = [
+            (Color::Black, 0), (Color::White, 1), (Color::Black, 2), (Color::White, 3), (Color::Black, 4),
+            (Color::White, 0), (Color::Black, 1), (Color::White, 2), (Color::Black, 3)
+        ];
+
+        for &(color, i) in moves.iter() {
+            let (x, y, _) = kos[i];
+            let x = if color == Color::Black { x + 2 } else { x + 1 };
+
+            assert!(board.is_valid(color, x, y));
+            board.place(color, x, y);
+        }
+
+        assert!(board._is_valid(Color::White, 19 * 18 + 1));
+        assert!(!board.is_valid(Color::White, 1, 18));
+    }
+
     #[test]
     fn score_black() {
         let mut board = Board::new();
@@ -1307,6 +1748,78 @@ mod tests {
         assert_eq!(board.get_score(), (357, 4));
     }
 
+    /// Test that the edge of a small board is treated as the edge, and not
+    /// as an empty vertex.
+    #[test]
+    fn capture_small_corner() {
+        let mut board = Board::with_size(9);
+
+        board.place(Color::Black, 8, 8);
+        board.place(Color::White, 7, 8);
+        board.place(Color::White, 8, 7);
+
+        assert_eq!(board.at(8, 8), None);
+        assert!(!board.is_valid(Color::Black, 8, 8));
+        assert!(!board.is_valid(Color::Black, 9, 0));
+        assert!(!board.is_valid(Color::Black, 0, 9));
+    }
+
+    #[test]
+    fn score_small() {
+        let mut board = Board::with_size(9);
+        board.place(Color::Black, 4, 4);
+
+        assert_eq!(board.get_score(), (81, 0));
+
+        let mut board = Board::with_size(13);
+        board.place(Color::White, 1, 0);
+        board.place(Color::White, 0, 1);
+        board.place(Color::White, 1, 1);
+        board.place(Color::Black, 2, 0);
+        board.place(Color::Black, 2, 1);
+        board.place(Color::Black, 0, 2);
+        board.place(Color::Black, 1, 2);
+
+        assert!(board.is_scoreable());
+        assert_eq!(board.get_score(), (165, 4));
+    }
+
+    #[test]
+    fn features_small() {
+        let board = Board::with_size(9);
+        let features = board.get_features::<f32, CHW>(Color::Black, symmetry::Transform::Rot90);
+        let ones = (0..361).filter(|&i| features[CHW::index(0, i)] == 1.0).count();
+
+        assert_eq!(ones, 81);
+        assert!((0..361).all(|i| features[CHW::index(0, i)] == 0.0 || (i % 19 < 9 && i / 19 < 9)));
+    }
+
+    #[test]
+    fn setup_stones() {
+        let mut board = Board::new();
+
+        for (x, y) in fixed_handicap(19, 4).unwrap() {
+            board.setup(Color::Black, x, y);
+        }
+
+        assert_eq!(board.count(), 0);
+        assert_eq!(board.at(3, 3), Some(Color::Black));
+        assert!(!board.is_valid(Color::White, 3, 3));
+
+        // the stones should be visible in every historic plane, as our stones
+        // for black and as the opponent stones for white
+        let features = board.get_features::<f32, CHW>(Color::White, symmetry::Transform::Identity);
+
+        for p in 26..32 {
+            assert_eq!(features[CHW::index(p, 19 * 3 + 3)], 1.0);
+            assert_eq!(features[CHW::index(p, 19 * 15 + 15)], 1.0);
+        }
+
+        for p in 14..20 {
+            assert_eq!(features[CHW::index(p, 19 * 3 + 3)], 0.0);
+        }
+    }
+
     #[test]
     fn ladder_corner_capture() {
         // test the following (as 19x19 board), and check
@@ -1394,4 +1907,45 @@ mod tests {
             }
         }
     }
+
+    /// Test that two move orders that reach the same position have the same
+    /// hash, unless a different player made the most recent move.
+    #[test]
+    fn zobrist_hash_after() {
+        let mut board_1 = Board::new();
+        let mut board_2 = Board::new();
+
+        board_1.place(Color::Black, 3, 3);
+        board_1.place(Color::White, 15, 15);
+        board_1.place(Color::Black, 15, 3);
+        board_2.place(Color::Black, 15, 3);
+        board_2.place(Color::White, 15, 15);
+        board_2.place(Color::Black, 3, 3);
+
+        assert_eq!(board_1.zobrist_hash_after(Color::Black), board_2.zobrist_hash_after(Color::Black));
+        assert!(board_1.zobrist_hash_after(Color::Black) != board_1.zobrist_hash_after(Color::White));
+    }
+
+    /// Test that a board is identical to itself after it has been written
+    /// and read back, including its super-ko history.
+    #[test]
+    fn write_read() {
+        let mut board = Board::with_rules(9, Rules::Japanese);
+        let mut buf = vec! [];
+
+        board.place(Color::Black, 2, 2);
+        board.place(Color::White, 6, 6);
+        board.place(Color::Black, 2, 6);
+        board.write_into(&mut buf).unwrap();
+
+        let other = Board::read_from(&mut &buf[..]).unwrap();
+
+        assert!(other == board);
+        assert_eq!(other.size(), 9);
+        assert_eq!(other.rules(), Rules::Japanese);
+        assert_eq!(other.count(), board.count());
+        assert_eq!(other.zobrist_hash(), board.zobrist_hash());
+        assert_eq!(other.at(2, 6), Some(Color::Black));
+        assert!(Board::read_from(&mut &buf[..10]).is_err());
+    }
 }
@@ -0,0 +1,538 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::Rng;
+
+use go::{Board, Color};
+
+/// The life status of a group at the end of the game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    /// The group will remain on the board until the end of the game.
+    Alive,
+
+    /// The group will be captured before the end of the game, and should
+    /// be removed before the game is scored.
+    Dead,
+
+    /// The group is alive, but shares some liberties with the opponent
+    /// that neither player can fill.
+    Seki
+}
+
+/// Returns the HW index of the vertex at the given offset from the given
+/// vertex, or `None` if that vertex is outside of the board.
+///
+/// # Arguments
+///
+/// * `board` - the board to look at
+/// * `index` - the HW index of the vertex
+/// * `dx` - the offset of the column
+/// * `dy` - the offset of the row
+///
+fn offset(board: &Board, index: usize, dx: isize, dy: isize) -> Option<usize> {
+    let x = (index % 19) as isize + dx;
+    let y = (index / 19) as isize + dy;
+    let size = board.size() as isize;
+
+    if x >= 0 && x < size && y >= 0 && y < size {
+        Some(19 * y as usize + x as usize)
+    } else {
+        None
+    }
+}
+
+impl Board {
+    /// Returns true if the given vertex is an eye of the given color. An eye
+    /// is an empty vertex whose neighbours are all of the given color, and
+    /// whose diagonals are not controlled by the opponent.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player who may own the eye
+    /// * `index` - the HW index of the vertex
+    ///
+    fn is_eye(&self, color: Color, index: usize) -> bool {
+        let current = color as u8;
+        let opponent = color.opposite() as u8;
+
+        if self.vertices[index] != 0 {
+            return false;
+        }
+
+        for &(dx, dy) in &[(0, 1), (1, 0), (0, -1), (-1, 0)] {
+            if let Some(other) = offset(self, index, dx, dy) {
+                if self.vertices[other] != current {
+                    return false;
+                }
+            }
+        }
+
+        // a vertex on the edge of the board is a false eye if the opponent
+        // controls any of its diagonals, otherwise it takes two
+        let mut num_opponent = 0;
+        let mut num_edge = 0;
+
+        for &(dx, dy) in &[(1, 1), (1, -1), (-1, -1), (-1, 1)] {
+            match offset(self, index, dx, dy) {
+                Some(other) => if self.vertices[other] == opponent { num_opponent += 1 },
+                None => { num_edge += 1 }
+            }
+        }
+
+        if num_edge > 0 {
+            num_opponent == 0
+        } else {
+            num_opponent <= 1
+        }
+    }
+
+    /// Returns true if the given move is worth playing during a playout. This
+    /// excludes moves that are not valid, moves that fills ones own eyes,
+    /// and moves that puts one of ones own groups in atari.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `x` - the column of the move
+    /// * `y` - the row of the move
+    ///
+    pub fn is_playout_move(&self, color: Color, x: usize, y: usize) -> bool {
+        let index = 19 * y + x;
+
+        self.is_valid(color, x, y) && !self.is_eye(color, index) && {
+            // a single stone in self-atari is usually a throw-in, which is
+            // necessary to capture some groups, so only forbid it if the
+            // move joins with another group.
+            !self.has_neighbour(color, index) || {
+                let mut memoize = [0; 368];
+
+                self.get_num_liberties_if(color, index, &mut memoize) >= 2
+            }
+        }
+    }
+
+    /// Remove the group that contains the given vertex from the board, and
+    /// count its stones as prisoners. This is used to remove dead stones
+    /// before scoring a finished game.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - the column of a stone in the group
+    /// * `y` - the row of a stone in the group
+    ///
+    pub fn remove_group(&mut self, x: usize, y: usize) {
+        let index = 19 * y + x;
+
+        if x < self.size && y < self.size && self.vertices[index] != 0 {
+            self.capture(index);
+        }
+    }
+
+    /// Returns the score of this board position, after every group that is
+    /// dead according to the given status has been removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - the life status of the group at every vertex
+    ///
+    pub fn get_final_score(&self, status: &[Option<Status>]) -> (usize, usize) {
+        let mut board = self.clone();
+
+        for index in 0..361 {
+            if status[index] == Some(Status::Dead) {
+                board.remove_group(index % 19, index / 19);
+            }
+        }
+
+        board.get_score()
+    }
+}
+
+/// Returns the board at the end of a playout from the given board position,
+/// where the move of each player is decided by the given function. The
+/// playout ends when both players pass, or when it has gone on for so long
+/// that it is unlikely to ever end.
+///
+/// # Arguments
+///
+/// * `starting_point` - the board position to start the playout from
+/// * `starting_color` - the color of the player to make the first move
+/// * `pick` - returns the HW index of the move to play for the given board
+///   and color, or `None` to pass
+///
+pub fn playout<F>(starting_point: &Board, starting_color: Color, mut pick: F) -> Board
+    where F: FnMut(&Board, Color) -> Option<usize>
+{
+    let max_count = 3 * starting_point.size() * starting_point.size();
+    let mut board = starting_point.clone();
+    let mut current = starting_color;
+    let mut pass_count = 0;
+    let mut count = 0;
+
+    while pass_count < 2 && count < max_count {
+        if let Some(index) = pick(&board, current) {
+            board.place(current, index % 19, index / 19);
+            pass_count = 0;
+        } else {
+            pass_count += 1;
+        }
+
+        current = current.opposite();
+        count += 1;
+    }
+
+    board
+}
+
+/// Returns a uniformly random move that is worth playing during a playout
+/// for the given board and color, or `None` if no such move exists.
+///
+/// # Arguments
+///
+/// * `board` - the board position
+/// * `color` - the color of the move
+/// * `rng` - the random number generator to pick the move with
+///
+fn random_move<R: Rng>(board: &Board, color: Color, rng: &mut R) -> Option<usize> {
+    let size = board.size();
+    let mut candidates = (0..(size * size))
+        .map(|i| 19 * (i / size) + (i % size))
+        .collect::<Vec<usize>>();
+
+    rng.shuffle(&mut candidates);
+    candidates.into_iter().find(|&index| {
+        board.is_playout_move(color, index % 19, index / 19)
+    })
+}
+
+/// The number of times each vertex has been owned by each player at the
+/// end of a number of playouts.
+pub struct Ownership {
+    /// The number of playouts where black owned each vertex.
+    black: [u32; 361],
+
+    /// The number of playouts where white owned each vertex.
+    white: [u32; 361],
+
+    /// The total number of playouts.
+    count: u32
+}
+
+impl Ownership {
+    /// Returns an ownership with no playouts.
+    pub fn new() -> Ownership {
+        Ownership {
+            black: [0; 361],
+            white: [0; 361],
+            count: 0
+        }
+    }
+
+    /// Adds the area owned by each player in the given finished board
+    /// position to the statistics. A vertex is owned by a player if they
+    /// have a stone there, or if it is only reachable by their stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board position at the end of a playout
+    ///
+    pub fn add(&mut self, board: &Board) {
+        let black_distance = board.get_territory_distance(Color::Black);
+        let white_distance = board.get_territory_distance(Color::White);
+
+        for i in 0..361 {
+            if !board.is_on_board(i) {
+                // pass
+            } else if black_distance[i] == 0 {
+                self.black[i] += 1;
+            } else if white_distance[i] == 0 {
+                self.white[i] += 1;
+            } else if white_distance[i] == 0xff && black_distance[i] != 0xff {
+                self.black[i] += 1;
+            } else if black_distance[i] == 0xff && white_distance[i] != 0xff {
+                self.white[i] += 1;
+            }
+        }
+
+        self.count += 1;
+    }
+
+    /// Adds the playouts of the given ownership statistics to these
+    /// statistics.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the statistics to add
+    ///
+    pub fn merge(&mut self, other: &Ownership) {
+        for i in 0..361 {
+            self.black[i] += other.black[i];
+            self.white[i] += other.white[i];
+        }
+
+        self.count += other.count;
+    }
+
+    /// Returns the average ownership of the given vertex, where `+1` means
+    /// that black always owned the vertex and `-1` that white always did.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the HW index of the vertex
+    ///
+    pub fn get(&self, index: usize) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.black[index] as f32 - self.white[index] as f32) / self.count as f32
+        }
+    }
+
+    /// Returns the fraction of playouts where neither player owned the
+    /// given vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the HW index of the vertex
+    ///
+    fn get_neutral(&self, index: usize) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            let owned = self.black[index] + self.white[index];
+
+            (self.count - owned) as f32 / self.count as f32
+        }
+    }
+
+    /// Returns the life status of the group at every vertex of the given
+    /// board, or `None` for empty vertices. A group is dead if it is owned
+    /// by the opponent more often than by its own player, and in seki if it
+    /// is alive but has liberties that usually remain neutral.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board position that the statistics was gathered for
+    ///
+    pub fn get_status(&self, board: &Board) -> Vec<Option<Status>> {
+        let mut status = vec! [None; 361];
+
+        for index in 0..361 {
+            if !board.is_on_board(index) || board.vertices[index] == 0 || status[index].is_some() {
+                continue;
+            }
+
+            let sign = if board.vertices[index] == Color::Black as u8 { 1.0 } else { -1.0 };
+            let mut group = vec! [];
+            let mut current = index;
+
+            loop {
+                group.push(current);
+
+                current = board.next_vertex[current] as usize;
+                if current == index {
+                    break
+                }
+            }
+
+            let total: f32 = group.iter().map(|&i| sign * self.get(i)).sum();
+            let group_status = if total < 0.0 {
+                Status::Dead
+            } else {
+                let is_seki = group.iter().any(|&i| {
+                    [(0, 1), (1, 0), (0, -1), (-1, 0)].iter().any(|&(dx, dy)| {
+                        match offset(board, i, dx, dy) {
+                            Some(other) => board.vertices[other] == 0 && self.get_neutral(other) > 0.5,
+                            None => false
+                        }
+                    })
+                });
+
+                if is_seki { Status::Seki } else { Status::Alive }
+            };
+
+            for &i in &group {
+                status[i] = Some(group_status);
+            }
+        }
+
+        status
+    }
+}
+
+/// Returns the ownership statistics of the given board position, gathered
+/// from uniformly random playouts. This is a fallback for when no neural
+/// network is available to guide the playouts.
+///
+/// # Arguments
+///
+/// * `starting_point` - the board position to estimate the ownership of
+/// * `starting_color` - the color of the player to make the next move
+/// * `num_playouts` - the number of playouts to perform
+/// * `rng` - the random number generator to pick the moves with
+///
+pub fn random_ownership<R: Rng>(starting_point: &Board, starting_color: Color, num_playouts: usize, rng: &mut R) -> Ownership {
+    let mut ownership = Ownership::new();
+
+    for _ in 0..num_playouts {
+        let board = playout(starting_point, starting_color, |board, color| random_move(board, color, rng));
+
+        ownership.add(&board);
+    }
+
+    ownership
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::XorShiftRng;
+
+    use go::ownership::*;
+    use go::*;
+
+    /// Returns a board where black owns the left part of the board, white
+    /// owns the right part of the board, and there is a single dead white
+    /// stone inside of black's area. Both players have two eyes in the
+    /// lower corners.
+    fn dead_stone_board() -> Board {
+        let mut board = Board::with_size(9);
+
+        for y in 0..9 {
+            board.place(Color::Black, 3, y);
+            board.place(Color::White, 4, y);
+        }
+
+        for &(x, y) in &[(1, 0), (0, 1), (1, 1), (2, 1)] {
+            board.place(Color::Black, x, y);
+        }
+
+        for &(x, y) in &[(5, 0), (7, 0), (5, 1), (6, 1), (7, 1), (8, 1)] {
+            board.place(Color::White, x, y);
+        }
+
+        board.place(Color::White, 1, 4);
+        board
+    }
+
+    #[test]
+    fn eye() {
+        let mut board = Board::with_size(9);
+
+        board.place(Color::Black, 1, 0);
+        board.place(Color::Black, 0, 1);
+        board.place(Color::Black, 2, 1);
+        board.place(Color::Black, 1, 2);
+
+        assert!(board.is_eye(Color::Black, 19 * 1 + 1));
+        assert!(!board.is_eye(Color::White, 19 * 1 + 1));
+        assert!(board.is_eye(Color::Black, 0));
+
+        board.place(Color::White, 0, 2);
+
+        assert!(board.is_eye(Color::Black, 19 * 1 + 1));
+        assert!(!board.is_playout_move(Color::Black, 1, 1));
+
+        board.place(Color::White, 2, 2);
+
+        assert!(!board.is_eye(Color::Black, 19 * 1 + 1));
+    }
+
+    #[test]
+    fn dead_stone() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let board = dead_stone_board();
+        let status = random_ownership(&board, Color::Black, 32, &mut rng).get_status(&board);
+
+        assert_eq!(status[19 * 4 + 1], Some(Status::Dead));
+        assert_eq!(status[3], Some(Status::Alive));
+        assert_eq!(status[4], Some(Status::Alive));
+        assert_eq!(status[0], None);
+    }
+
+    #[test]
+    fn remove_group() {
+        let mut board = dead_stone_board();
+
+        board.remove_group(1, 4);
+
+        assert_eq!(board.at(1, 4), None);
+        assert_eq!(board.prisoners(Color::White), 1);
+        assert_eq!(board.get_score(), (36, 45));
+    }
+
+    #[test]
+    fn final_score() {
+        let board = dead_stone_board();
+        let mut status = vec! [None; 361];
+
+        status[19 * 4 + 1] = Some(Status::Dead);
+
+        assert_eq!(board.get_final_score(&status), (36, 45));
+        assert_eq!(board.at(1, 4), Some(Color::White));
+    }
+
+    #[test]
+    fn merge() {
+        let board = dead_stone_board();
+        let mut ownership = Ownership::new();
+        let mut other = Ownership::new();
+
+        ownership.add(&board);
+        other.add(&board);
+        other.add(&board);
+        ownership.merge(&other);
+
+        assert_eq!(ownership.count, 3);
+        assert_eq!(ownership.get(3), 1.0);
+        assert_eq!(ownership.get(4), -1.0);
+    }
+
+    #[test]
+    fn seki() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let mut board = Board::with_size(9);
+
+        // a small black group at b1 and c1 and a white group around it, that
+        // share the two liberties at a1 and d1, inside of a living black
+        // group. The rest of the board belongs to a living white group.
+        for &(x, y) in &[(1, 0), (2, 0), (5, 0), (7, 0), (5, 1), (6, 1), (7, 1), (8, 1)] {
+            board.place(Color::Black, x, y);
+        }
+
+        for x in 0..9 {
+            board.place(Color::Black, x, 2);
+        }
+
+        for &(x, y) in &[(0, 1), (1, 1), (2, 1), (3, 1), (4, 1), (4, 0)] {
+            board.place(Color::White, x, y);
+        }
+
+        for x in 0..9 {
+            board.place(Color::White, x, 3);
+            board.place(Color::White, x, 5);
+
+            if x % 2 == 1 {
+                board.place(Color::White, x, 4);
+            }
+        }
+
+        let status = random_ownership(&board, Color::Black, 32, &mut rng).get_status(&board);
+
+        assert_eq!(status[1], Some(Status::Seki));
+        assert_eq!(status[19 * 1 + 0], Some(Status::Seki));
+        assert_eq!(status[19 * 2 + 0], Some(Status::Alive));
+        assert_eq!(status[19 * 3 + 0], Some(Status::Alive));
+    }
+}
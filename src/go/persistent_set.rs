@@ -0,0 +1,228 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io;
+use std::sync::Arc;
+
+use util::binary;
+
+/// The number of 64-bit words in the filter of a `PersistentSet`.
+const FILTER_SIZE: usize = 32;
+
+/// A single value in a `PersistentSet`, with a link to the value that was
+/// added before it.
+struct Link {
+    value: u64,
+    next: Option<Arc<Link>>
+}
+
+/// An append-only set of 64-bit values that keeps every value that has
+/// ever been added to it. Cloning the set takes constant time since the
+/// values are stored in a linked list that is shared between all clones.
+///
+/// A small bit filter is kept next to the list so that most lookups for
+/// values that are not in the set never has to walk the list.
+pub struct PersistentSet {
+    filter: [u64; FILTER_SIZE],
+    head: Option<Arc<Link>>
+}
+
+impl Clone for PersistentSet {
+    fn clone(&self) -> PersistentSet {
+        PersistentSet {
+            filter: self.filter,
+            head: self.head.clone()
+        }
+    }
+}
+
+impl Drop for PersistentSet {
+    fn drop(&mut self) {
+        // drop the list iteratively, instead of recursively, to avoid
+        // overflowing the stack for very long games.
+        let mut next = self.head.take();
+
+        while let Some(link) = next {
+            match Arc::try_unwrap(link) {
+                Ok(mut link) => { next = link.next.take(); },
+                Err(_) => break
+            }
+        }
+    }
+}
+
+impl PersistentSet {
+    /// Returns an empty set.
+    pub fn new() -> PersistentSet {
+        PersistentSet {
+            filter: [0; FILTER_SIZE],
+            head: None
+        }
+    }
+
+    /// Returns the word and the bit in the filter for the given value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to get the filter position for
+    ///
+    #[inline]
+    fn filter_position(value: u64) -> (usize, u64) {
+        let bit = (value >> 32) as usize % (64 * FILTER_SIZE);
+
+        (bit / 64, 1 << (bit % 64))
+    }
+
+    /// Adds the given value to this set, without affecting any clones of
+    /// this set.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to add to the set
+    ///
+    pub fn push(&mut self, value: u64) {
+        let (word, bit) = PersistentSet::filter_position(value);
+
+        self.filter[word] |= bit;
+        self.head = Some(Arc::new(Link {
+            value: value,
+            next: self.head.take()
+        }));
+    }
+
+    /// Returns true if this set contains the given value.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the value to look for
+    ///
+    pub fn contains(&self, other: u64) -> bool {
+        let (word, bit) = PersistentSet::filter_position(other);
+
+        if self.filter[word] & bit == 0 {
+            return false;
+        }
+
+        let mut current = &self.head;
+
+        while let Some(ref link) = *current {
+            if link.value == other {
+                return true;
+            }
+
+            current = &link.next;
+        }
+
+        false
+    }
+
+    /// Write every value in this set, oldest first, to the given writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the writer to write to
+    ///
+    pub fn write_into<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        let mut values = vec! [];
+        let mut current = &self.head;
+
+        while let Some(ref link) = *current {
+            values.push(link.value);
+            current = &link.next;
+        }
+
+        binary::write_u32(f, values.len() as u32)?;
+        for &value in values.iter().rev() {
+            binary::write_u64(f, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a set that was previously written using `write_into`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the reader to read from
+    ///
+    pub fn read_from<R: io::Read>(f: &mut R) -> io::Result<PersistentSet> {
+        let mut out = PersistentSet::new();
+        let count = binary::read_u32(f)?;
+
+        for _ in 0..count {
+            out.push(binary::read_u64(f)?);
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::persistent_set::*;
+
+    #[test]
+    fn check() {
+        let mut s = PersistentSet::new();
+
+        for i in 0..1000u64 {
+            s.push(i.wrapping_mul(0x9e3779b97f4a7c15));
+        }
+
+        for i in 0..1000u64 {
+            assert!(s.contains(i.wrapping_mul(0x9e3779b97f4a7c15)));
+        }
+
+        assert!(!s.contains(1000u64.wrapping_mul(0x9e3779b97f4a7c15)));
+    }
+
+    #[test]
+    fn clone() {
+        let mut s = PersistentSet::new();
+        s.push(1);
+
+        let mut t = s.clone();
+        t.push(2);
+        s.push(3);
+
+        assert!(s.contains(1) && !s.contains(2) && s.contains(3));
+        assert!(t.contains(1) && t.contains(2) && !t.contains(3));
+    }
+
+    #[test]
+    fn drop_long() {
+        let mut s = PersistentSet::new();
+
+        for i in 0..1000000 {
+            s.push(i);
+        }
+
+        assert!(s.contains(0));
+    }
+
+    #[test]
+    fn write_read() {
+        let mut s = PersistentSet::new();
+        let mut buf = vec! [];
+
+        s.push(1);
+        s.push(2);
+        s.write_into(&mut buf).unwrap();
+
+        let t = PersistentSet::read_from(&mut &buf[..]).unwrap();
+
+        assert!(t.contains(1) && t.contains(2) && !t.contains(3));
+        assert_eq!(t.head.as_ref().map(|link| link.value), Some(2));
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The variants of the super-ko rule that determines which positions are
+/// considered to be repeated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Superko {
+    /// A move may not recreate any earlier board position.
+    Positional,
+
+    /// A move may not recreate any earlier board position with the same
+    /// player to move.
+    Situational
+}
+
+/// The methods used to score a finished game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scoring {
+    /// Each player gets one point for every stone, and every empty vertex,
+    /// that they control.
+    Area,
+
+    /// Each player gets one point for every empty vertex that they control,
+    /// and for every stone they have captured.
+    Territory
+}
+
+/// The rule sets that are supported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Rules {
+    Chinese,
+    Japanese,
+    TrompTaylor,
+    NewZealand
+}
+
+impl Rules {
+    /// Returns true if these rules allow a move that captures ones own
+    /// stones. A suicide of a single stone is never allowed since it would
+    /// be equivalent to a pass.
+    pub fn allow_suicide(&self) -> bool {
+        match *self {
+            Rules::Chinese => false,
+            Rules::Japanese => false,
+            Rules::TrompTaylor => true,
+            Rules::NewZealand => true
+        }
+    }
+
+    /// Returns the super-ko variant used by these rules.
+    pub fn superko(&self) -> Superko {
+        match *self {
+            Rules::Chinese => Superko::Positional,
+            Rules::Japanese => Superko::Positional,
+            Rules::TrompTaylor => Superko::Positional,
+            Rules::NewZealand => Superko::Situational
+        }
+    }
+
+    /// Returns the scoring method used by these rules.
+    pub fn scoring(&self) -> Scoring {
+        match *self {
+            Rules::Chinese => Scoring::Area,
+            Rules::Japanese => Scoring::Territory,
+            Rules::TrompTaylor => Scoring::Area,
+            Rules::NewZealand => Scoring::Area
+        }
+    }
+
+    /// Returns the default komi of these rules.
+    pub fn komi(&self) -> f32 {
+        match *self {
+            Rules::Chinese => 7.5,
+            Rules::Japanese => 6.5,
+            Rules::TrompTaylor => 7.5,
+            Rules::NewZealand => 7.0
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules::Chinese
+    }
+}
+
+impl FromStr for Rules {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Rules, Self::Err> {
+        let s = s.trim().to_lowercase();
+
+        if s == "chinese" || s == "cn" {
+            Ok(Rules::Chinese)
+        } else if s == "japanese" || s == "jp" {
+            Ok(Rules::Japanese)
+        } else if s == "tromp-taylor" || s == "tromp_taylor" || s == "tt" {
+            Ok(Rules::TrompTaylor)
+        } else if s == "new_zealand" || s == "new-zealand" || s == "nz" {
+            Ok(Rules::NewZealand)
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl fmt::Display for Rules {
+    /// Write the name of these rules as used by the `RU` property in SGF
+    /// files.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the formatter to write the name to
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Rules::Chinese => write!(f, "Chinese"),
+            Rules::Japanese => write!(f, "Japanese"),
+            Rules::TrompTaylor => write!(f, "Tromp-Taylor"),
+            Rules::NewZealand => write!(f, "NZ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::rules::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("chinese".parse::<Rules>(), Ok(Rules::Chinese));
+        assert_eq!("Japanese".parse::<Rules>(), Ok(Rules::Japanese));
+        assert_eq!("tromp-taylor".parse::<Rules>(), Ok(Rules::TrompTaylor));
+        assert_eq!("new_zealand".parse::<Rules>(), Ok(Rules::NewZealand));
+        assert_eq!("NZ".parse::<Rules>(), Ok(Rules::NewZealand));
+        assert_eq!("ing".parse::<Rules>(), Err(()));
+    }
+
+    #[test]
+    fn display() {
+        for &rules in &[Rules::Chinese, Rules::Japanese, Rules::TrompTaylor, Rules::NewZealand] {
+            assert_eq!(format!("{}", rules).parse::<Rules>(), Ok(rules));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod parser;
+mod writer;
+
+pub use self::parser::*;
+pub use self::writer::*;
+
+pub trait SgfCoordinate {
+    /// Returns the SGF point `(x, y)` of the given vertex on a board with the
+    /// given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - the column of the vertex
+    /// * `y` - the row of the vertex
+    /// * `size` - the width and height of the board
+    ///
+    fn to_point(x: usize, y: usize, size: usize) -> (usize, usize);
+
+    /// Returns the SGF coordinate of the given vertex on a board with the
+    /// given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - the column of the vertex
+    /// * `y` - the row of the vertex
+    /// * `size` - the width and height of the board
+    ///
+    fn to_sgf(x: usize, y: usize, size: usize) -> String {
+        format_point(Self::to_point(x, y, size))
+    }
+}
+
+pub struct CGoban;
+
+impl SgfCoordinate for CGoban {
+    fn to_point(x: usize, y: usize, _size: usize) -> (usize, usize) {
+        (x, y)
+    }
+}
+
+pub struct Sabaki;
+
+impl SgfCoordinate for Sabaki {
+    fn to_point(x: usize, y: usize, size: usize) -> (usize, usize) {
+        (x, size - y - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::sgf::*;
+
+    #[test]
+    fn cgoban() {
+        assert_eq!(CGoban::to_sgf(0, 0, 19), "aa");
+        assert_eq!(CGoban::to_sgf(3, 2, 9), "dc");
+    }
+
+    #[test]
+    fn sabaki() {
+        assert_eq!(Sabaki::to_sgf(0, 0, 19), "as");
+        assert_eq!(Sabaki::to_sgf(3, 2, 9), "dg");
+    }
+}
@@ -0,0 +1,584 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use go::Color;
+
+/// An error that occurred while parsing an SGF file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SgfError {
+    /// The file ended in the middle of a game tree.
+    UnexpectedEnd,
+
+    /// The given character was found at the given byte offset, where it
+    /// was not expected.
+    UnexpectedChar(usize, char),
+
+    /// The value of the given property could not be parsed.
+    InvalidValue(String, String)
+}
+
+impl fmt::Display for SgfError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SgfError::UnexpectedEnd => write!(fmt, "unexpected end of file"),
+            SgfError::UnexpectedChar(offset, ch) => write!(fmt, "unexpected character '{}' at offset {}", ch, offset),
+            SgfError::InvalidValue(ref ident, ref value) => write!(fmt, "invalid value {}[{}]", ident, value)
+        }
+    }
+}
+
+/// How a game was won, as recorded in the `RE` property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WinBy {
+    Score(f32),
+    Resign,
+    Time,
+    Forfeit,
+    Unknown
+}
+
+/// The result of a game, as recorded in the `RE` property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Win(Color, WinBy),
+    Draw,
+    Void,
+    Unknown
+}
+
+impl Outcome {
+    /// Returns the outcome described by the given value of an `RE`
+    /// property. Values that does not follow the specification are
+    /// interpreted as leniently as possible, since they are common.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the `RE` property
+    ///
+    fn parse(value: &str) -> Outcome {
+        let value = value.trim();
+        let winner = match value.chars().nth(0) {
+            Some('B') | Some('b') => Color::Black,
+            Some('W') | Some('w') => Color::White,
+            _ => {
+                return match value {
+                    "0" | "Draw" | "Jigo" => Outcome::Draw,
+                    "Void" => Outcome::Void,
+                    _ => Outcome::Unknown
+                };
+            }
+        };
+        let by = value[1..].trim_left_matches('+').trim();
+        let by = match by {
+            "R" | "Resign" => WinBy::Resign,
+            "T" | "Time" => WinBy::Time,
+            "F" | "Forfeit" => WinBy::Forfeit,
+            _ => by.parse::<f32>().map(|score| WinBy::Score(score)).unwrap_or(WinBy::Unknown)
+        };
+
+        Outcome::Win(winner, by)
+    }
+}
+
+/// A single property of a node, with its values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    /// `SZ` - the width and height of the board.
+    Size(usize),
+
+    /// `KM` - the komi.
+    Komi(f32),
+
+    /// `HA` - the number of handicap stones.
+    Handicap(usize),
+
+    /// `RE` - the result of the game.
+    Result(Outcome),
+
+    /// `RU` - the name of the rules.
+    Rules(String),
+
+    /// `PB` - the name of the black player.
+    PlayerBlack(String),
+
+    /// `PW` - the name of the white player.
+    PlayerWhite(String),
+
+    /// `AP` - the name and version of the program that created the file.
+    Application(String),
+
+    /// `DT` - the date when the game was played.
+    Date(String),
+
+    /// `GC` - background information about the game.
+    GameComment(String),
+
+    /// `B` or `W` - a move `(x, y)` by the given player, or `None` if the
+    /// player passed.
+    Move(Color, Option<(usize, usize)>),
+
+    /// `AB` - black stones to place on the board.
+    AddBlack(Vec<(usize, usize)>),
+
+    /// `AW` - white stones to place on the board.
+    AddWhite(Vec<(usize, usize)>),
+
+    /// `AE` - vertices to clear on the board.
+    AddEmpty(Vec<(usize, usize)>),
+
+    /// `PL` - the player whose turn it is to play.
+    ToPlay(Color),
+
+    /// `C` - a comment.
+    Comment(String),
+
+    /// `P` - the policy of the search, encoded with `b85`.
+    Policy(String),
+
+    /// `V` - the value of the search, from blacks perspective.
+    Value(f32),
+
+    /// `NN` - the name of the network that played the move.
+    Network(String),
+
+    /// Any other property, with its raw values.
+    Unknown(String, Vec<String>)
+}
+
+/// A single node in a game tree, which usually contains a move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub properties: Vec<Property>
+}
+
+/// A sequence of nodes, followed by zero or more variations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameTree {
+    pub nodes: Vec<Node>,
+    pub variations: Vec<GameTree>
+}
+
+impl GameTree {
+    /// Returns the root node of this game tree.
+    pub fn root(&self) -> &Node {
+        &self.nodes[0]
+    }
+
+    /// Returns an iterator over the nodes of the main line of this game
+    /// tree, which follows the first variation at every branch.
+    pub fn main_line<'a>(&'a self) -> MainLine<'a> {
+        MainLine { current: Some(self), index: 0 }
+    }
+}
+
+/// Iterator over the nodes of the main line of a game tree.
+pub struct MainLine<'a> {
+    current: Option<&'a GameTree>,
+    index: usize
+}
+
+impl<'a> Iterator for MainLine<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            let current = self.current?;
+
+            if self.index < current.nodes.len() {
+                self.index += 1;
+
+                return Some(&current.nodes[self.index - 1]);
+            } else {
+                self.current = current.variations.first();
+                self.index = 0;
+            }
+        }
+    }
+}
+
+/// Returns all game trees in the given SGF collection.
+///
+/// # Arguments
+///
+/// * `src` - the SGF collection
+///
+pub fn parse(src: &str) -> Result<Vec<GameTree>, SgfError> {
+    let mut parser = Parser {
+        chars: src.char_indices().peekable(),
+        size: 19
+    };
+    let mut collection = vec! [];
+
+    loop {
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None => break,
+            Some('(') => {
+                parser.size = 19;
+                collection.push(parser.game_tree()?);
+            },
+            Some(_) => { return Err(parser.unexpected()); }
+        }
+    }
+
+    Ok(collection)
+}
+
+/// Recursive descent parser of SGF collections.
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+
+    /// The size of the board in the game tree that is currently being
+    /// parsed, which is needed to tell whether `tt` is a pass.
+    size: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, ch)| ch)
+    }
+
+    fn unexpected(&mut self) -> SgfError {
+        match self.chars.next() {
+            Some((offset, ch)) => SgfError::UnexpectedChar(offset, ch),
+            None => SgfError::UnexpectedEnd
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |ch| ch.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SgfError> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(expected) {
+            self.chars.next();
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// `GameTree = "(" Sequence { GameTree } ")"`
+    fn game_tree(&mut self) -> Result<GameTree, SgfError> {
+        let mut nodes = vec! [];
+        let mut variations = vec! [];
+
+        self.expect('(')?;
+        self.skip_whitespace();
+
+        while self.peek() == Some(';') {
+            nodes.push(self.node()?);
+            self.skip_whitespace();
+        }
+
+        if nodes.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        while self.peek() == Some('(') {
+            variations.push(self.game_tree()?);
+            self.skip_whitespace();
+        }
+
+        self.expect(')')?;
+
+        Ok(GameTree { nodes: nodes, variations: variations })
+    }
+
+    /// `Node = ";" { Property }`
+    fn node(&mut self) -> Result<Node, SgfError> {
+        let mut properties = vec! [];
+
+        self.expect(';')?;
+        self.skip_whitespace();
+
+        while self.peek().map_or(false, |ch| ch.is_ascii_uppercase()) {
+            properties.push(self.property()?);
+            self.skip_whitespace();
+        }
+
+        Ok(Node { properties: properties })
+    }
+
+    /// `Property = PropIdent PropValue { PropValue }`
+    fn property(&mut self) -> Result<Property, SgfError> {
+        let mut ident = String::new();
+        let mut values = vec! [];
+
+        // lower case letters are allowed (and ignored) in the identifiers
+        // of older versions of the format, e.g. `AddBlack`
+        while let Some(ch) = self.peek() {
+            if ch.is_ascii_uppercase() {
+                ident.push(ch);
+            } else if !ch.is_ascii_lowercase() {
+                break
+            }
+
+            self.chars.next();
+        }
+
+        self.skip_whitespace();
+
+        while self.peek() == Some('[') {
+            values.push(self.value()?);
+            self.skip_whitespace();
+        }
+
+        if values.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        self.to_property(ident, values)
+    }
+
+    /// `PropValue = "[" CValueType "]"`, where any escaped characters are
+    /// unescaped, and escaped line breaks are removed.
+    fn value(&mut self) -> Result<String, SgfError> {
+        let mut value = String::new();
+
+        self.expect('[')?;
+
+        loop {
+            match self.chars.next() {
+                None => { return Err(SgfError::UnexpectedEnd); },
+                Some((_, ']')) => break,
+                Some((_, '\\')) => {
+                    match self.chars.next() {
+                        None => { return Err(SgfError::UnexpectedEnd); },
+                        Some((_, '\n')) => {},
+                        Some((_, '\r')) => {
+                            if self.peek() == Some('\n') {
+                                self.chars.next();
+                            }
+                        },
+                        Some((_, ch)) => value.push(ch)
+                    }
+                },
+                Some((_, ch)) => value.push(ch)
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Returns the typed property with the given identifier and values.
+    fn to_property(&mut self, ident: String, mut values: Vec<String>) -> Result<Property, SgfError> {
+        let invalid = |ident: &str, value: &str| SgfError::InvalidValue(ident.to_string(), value.to_string());
+        let property = match ident.as_str() {
+            "SZ" => {
+                // non-square boards (`SZ[19:13]`) are not supported
+                let size = values[0].trim().parse::<usize>()
+                    .map_err(|_| invalid(&ident, &values[0]))?;
+
+                if size == 0 || size > 26 {
+                    return Err(invalid(&ident, &values[0]));
+                }
+
+                self.size = size;
+                Property::Size(size)
+            },
+            "KM" => {
+                Property::Komi(values[0].trim().parse::<f32>().map_err(|_| invalid(&ident, &values[0]))?)
+            },
+            "HA" => {
+                Property::Handicap(values[0].trim().parse::<usize>().map_err(|_| invalid(&ident, &values[0]))?)
+            },
+            "V" => {
+                Property::Value(values[0].trim().parse::<f32>().map_err(|_| invalid(&ident, &values[0]))?)
+            },
+            "RE" => Property::Result(Outcome::parse(&values[0])),
+            "RU" => Property::Rules(values.swap_remove(0)),
+            "PB" => Property::PlayerBlack(values.swap_remove(0)),
+            "PW" => Property::PlayerWhite(values.swap_remove(0)),
+            "AP" => Property::Application(values.swap_remove(0)),
+            "DT" => Property::Date(values.swap_remove(0)),
+            "GC" => Property::GameComment(values.swap_remove(0)),
+            "C" => Property::Comment(values.swap_remove(0)),
+            "P" => Property::Policy(values.swap_remove(0)),
+            "NN" => Property::Network(values.swap_remove(0)),
+            "PL" => {
+                match values[0].trim() {
+                    "B" | "b" => Property::ToPlay(Color::Black),
+                    "W" | "w" => Property::ToPlay(Color::White),
+                    _ => { return Err(invalid(&ident, &values[0])); }
+                }
+            },
+            "B" | "W" => {
+                let color = if ident == "B" { Color::Black } else { Color::White };
+                let vertex = values[0].trim();
+
+                if vertex.is_empty() || (vertex == "tt" && self.size <= 19) {
+                    Property::Move(color, None)
+                } else {
+                    Property::Move(color, Some(parse_point(vertex).ok_or_else(|| invalid(&ident, vertex))?))
+                }
+            },
+            "AB" | "AW" | "AE" => {
+                let mut vertices = vec! [];
+
+                for value in values.iter() {
+                    vertices.extend(parse_point_list(value).ok_or_else(|| invalid(&ident, value))?);
+                }
+
+                match ident.as_str() {
+                    "AB" => Property::AddBlack(vertices),
+                    "AW" => Property::AddWhite(vertices),
+                    _ => Property::AddEmpty(vertices)
+                }
+            },
+            _ => Property::Unknown(ident.clone(), values)
+        };
+
+        Ok(property)
+    }
+}
+
+/// Returns the vertex `(x, y)` of the given SGF point, e.g. `dc`.
+///
+/// # Arguments
+///
+/// * `value` - the SGF point
+///
+fn parse_point(value: &str) -> Option<(usize, usize)> {
+    let to_coordinate = |ch: char| {
+        if ch.is_ascii_lowercase() {
+            Some(ch as usize - 'a' as usize)
+        } else if ch.is_ascii_uppercase() {
+            Some(ch as usize - 'A' as usize + 26)
+        } else {
+            None
+        }
+    };
+    let mut chars = value.chars();
+
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(x), Some(y), None) => Some((to_coordinate(x)?, to_coordinate(y)?)),
+        _ => None
+    }
+}
+
+/// Returns all vertices in the given SGF point, or compressed rectangle of
+/// points, e.g. `aa:cc`.
+///
+/// # Arguments
+///
+/// * `value` - the SGF point, or rectangle of points
+///
+fn parse_point_list(value: &str) -> Option<Vec<(usize, usize)>> {
+    let mut corners = value.trim().splitn(2, ':');
+    let (x0, y0) = parse_point(corners.next()?)?;
+    let (x1, y1) = if let Some(other) = corners.next() {
+        parse_point(other)?
+    } else {
+        (x0, y0)
+    };
+    let (x0, x1) = (::std::cmp::min(x0, x1), ::std::cmp::max(x0, x1));
+    let (y0, y1) = (::std::cmp::min(y0, y1), ::std::cmp::max(y0, y1));
+
+    Some((y0..(y1 + 1)).flat_map(|y| (x0..(x1 + 1)).map(move |x| (x, y))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use go::sgf::parser::*;
+    use go::Color;
+
+    #[test]
+    fn simple() {
+        let games = parse("(;GM[1]FF[4]SZ[9]KM[6.5]RE[W+Resign];B[ee];W[])").unwrap();
+
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].nodes.len(), 3);
+        assert_eq!(games[0].root().properties, vec! [
+            Property::Unknown("GM".to_string(), vec! ["1".to_string()]),
+            Property::Unknown("FF".to_string(), vec! ["4".to_string()]),
+            Property::Size(9),
+            Property::Komi(6.5),
+            Property::Result(Outcome::Win(Color::White, WinBy::Resign))
+        ]);
+        assert_eq!(games[0].nodes[1].properties, vec! [Property::Move(Color::Black, Some((4, 4)))]);
+        assert_eq!(games[0].nodes[2].properties, vec! [Property::Move(Color::White, None)]);
+    }
+
+    #[test]
+    fn variations() {
+        let games = parse("(;SZ[19];B[aa](;W[bb];B[cc])(;W[dd]))").unwrap();
+        let main_line = games[0].main_line()
+            .map(|node| node.properties[0].clone())
+            .collect::<Vec<Property>>();
+
+        assert_eq!(games[0].variations.len(), 2);
+        assert_eq!(main_line, vec! [
+            Property::Size(19),
+            Property::Move(Color::Black, Some((0, 0))),
+            Property::Move(Color::White, Some((1, 1))),
+            Property::Move(Color::Black, Some((2, 2)))
+        ]);
+    }
+
+    #[test]
+    fn collection() {
+        let games = parse("(;SZ[9];B[tt])\n(;SZ[25];B[tt])").unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].nodes[1].properties, vec! [Property::Move(Color::Black, None)]);
+        assert_eq!(games[1].nodes[1].properties, vec! [Property::Move(Color::Black, Some((19, 19)))]);
+    }
+
+    #[test]
+    fn escaped_text() {
+        let games = parse("(;C[a \\] b \\\\ c\\\nd]\n;\nB\n[aa]\n)").unwrap();
+
+        assert_eq!(games[0].root().properties, vec! [Property::Comment("a ] b \\ cd".to_string())]);
+        assert_eq!(games[0].nodes[1].properties, vec! [Property::Move(Color::Black, Some((0, 0)))]);
+    }
+
+    #[test]
+    fn setup() {
+        let games = parse("(;AB[aa][bb:cc]AddWhite[dd]AE[ee])").unwrap();
+
+        assert_eq!(games[0].root().properties, vec! [
+            Property::AddBlack(vec! [(0, 0), (1, 1), (2, 1), (1, 2), (2, 2)]),
+            Property::AddWhite(vec! [(3, 3)]),
+            Property::AddEmpty(vec! [(4, 4)])
+        ]);
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(Outcome::parse("B+3.5"), Outcome::Win(Color::Black, WinBy::Score(3.5)));
+        assert_eq!(Outcome::parse("W+T"), Outcome::Win(Color::White, WinBy::Time));
+        assert_eq!(Outcome::parse("W+"), Outcome::Win(Color::White, WinBy::Unknown));
+        assert_eq!(Outcome::parse("0"), Outcome::Draw);
+        assert_eq!(Outcome::parse("?"), Outcome::Unknown);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("(;B[aa]"), Err(SgfError::UnexpectedEnd));
+        assert_eq!(parse("(;B[aa"), Err(SgfError::UnexpectedEnd));
+        assert_eq!(parse("()"), Err(SgfError::UnexpectedChar(1, ')')));
+        assert_eq!(parse("(;KM[seven])"), Err(SgfError::InvalidValue("KM".to_string(), "seven".to_string())));
+        assert_eq!(parse("x(;B[aa])"), Err(SgfError::UnexpectedChar(0, 'x')));
+    }
+}
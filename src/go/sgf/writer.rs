@@ -0,0 +1,216 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use go::Color;
+use go::sgf::{GameTree, Node, Outcome, Property, WinBy};
+
+/// Returns the given value with every character that has a special meaning
+/// inside of a property value escaped.
+///
+/// # Arguments
+///
+/// * `value` - the value to escape
+///
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        if ch == ']' || ch == '\\' {
+            out.push('\\');
+        }
+
+        out.push(ch);
+    }
+
+    out
+}
+
+/// Returns the SGF representation of the given point `(x, y)`, e.g. `dc`.
+///
+/// # Arguments
+///
+/// * `point` - the point to format
+///
+pub fn format_point(point: (usize, usize)) -> String {
+    const LETTERS: [char; 52] = [
+        'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm',
+        'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+        'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M',
+        'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z'
+    ];
+
+    format!("{}{}", LETTERS[point.0], LETTERS[point.1])
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Outcome::Win(winner, ref by) => {
+                let winner = if winner == Color::Black { "B" } else { "W" };
+
+                match *by {
+                    WinBy::Score(score) => write!(fmt, "{}+{}", winner, score),
+                    WinBy::Resign => write!(fmt, "{}+R", winner),
+                    WinBy::Time => write!(fmt, "{}+T", winner),
+                    WinBy::Forfeit => write!(fmt, "{}+F", winner),
+                    WinBy::Unknown => write!(fmt, "{}+", winner)
+                }
+            },
+            Outcome::Draw => write!(fmt, "0"),
+            Outcome::Void => write!(fmt, "Void"),
+            Outcome::Unknown => write!(fmt, "?")
+        }
+    }
+}
+
+impl Property {
+    /// Returns the identifier of this property, e.g. `SZ`.
+    pub fn ident(&self) -> &str {
+        match *self {
+            Property::Size(_) => "SZ",
+            Property::Komi(_) => "KM",
+            Property::Handicap(_) => "HA",
+            Property::Result(_) => "RE",
+            Property::Rules(_) => "RU",
+            Property::PlayerBlack(_) => "PB",
+            Property::PlayerWhite(_) => "PW",
+            Property::Application(_) => "AP",
+            Property::Date(_) => "DT",
+            Property::GameComment(_) => "GC",
+            Property::Move(Color::Black, _) => "B",
+            Property::Move(Color::White, _) => "W",
+            Property::AddBlack(_) => "AB",
+            Property::AddWhite(_) => "AW",
+            Property::AddEmpty(_) => "AE",
+            Property::ToPlay(_) => "PL",
+            Property::Comment(_) => "C",
+            Property::Policy(_) => "P",
+            Property::Value(_) => "V",
+            Property::Network(_) => "NN",
+            Property::Unknown(ref ident, _) => ident
+        }
+    }
+
+    /// Returns the values of this property, before they have been escaped.
+    pub fn values(&self) -> Vec<String> {
+        match *self {
+            Property::Size(size) => vec! [size.to_string()],
+            Property::Komi(komi) => vec! [komi.to_string()],
+            Property::Handicap(handicap) => vec! [handicap.to_string()],
+            Property::Result(ref outcome) => vec! [outcome.to_string()],
+            Property::Rules(ref text) |
+            Property::PlayerBlack(ref text) |
+            Property::PlayerWhite(ref text) |
+            Property::Application(ref text) |
+            Property::Date(ref text) |
+            Property::GameComment(ref text) |
+            Property::Comment(ref text) |
+            Property::Policy(ref text) |
+            Property::Network(ref text) => vec! [text.clone()],
+            Property::Move(_, None) => vec! [String::new()],
+            Property::Move(_, Some(point)) => vec! [format_point(point)],
+            Property::AddBlack(ref points) |
+            Property::AddWhite(ref points) |
+            Property::AddEmpty(ref points) => {
+                points.iter().map(|&point| format_point(point)).collect()
+            },
+            Property::ToPlay(color) => vec! [if color == Color::Black { "B" } else { "W" }.to_string()],
+            Property::Value(value) => vec! [value.to_string()],
+            Property::Unknown(_, ref values) => values.clone()
+        }
+    }
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.ident())?;
+
+        for value in self.values() {
+            write!(fmt, "[{}]", escape(&value))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, ";")?;
+
+        // a property without any values is not valid, so skip them (this
+        // can happend for empty lists of setup stones)
+        for property in self.properties.iter() {
+            if !property.values().is_empty() {
+                write!(fmt, "{}", property)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for GameTree {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "(")?;
+
+        for node in self.nodes.iter() {
+            write!(fmt, "{}", node)?;
+        }
+
+        for variation in self.variations.iter() {
+            write!(fmt, "{}", variation)?;
+        }
+
+        write!(fmt, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::sgf::*;
+    use go::Color;
+
+    #[test]
+    fn escaped_text() {
+        let property = Property::Comment("a ] b \\ c".to_string());
+
+        assert_eq!(format!("{}", property), "C[a \\] b \\\\ c]");
+    }
+
+    #[test]
+    fn points() {
+        assert_eq!(format_point((0, 0)), "aa");
+        assert_eq!(format_point((3, 2)), "dc");
+        assert_eq!(format_point((19, 26)), "tA");
+        assert_eq!(format!("{}", Property::Move(Color::White, None)), "W[]");
+        assert_eq!(format!("{}", Property::AddBlack(vec! [(0, 0), (1, 1)])), "AB[aa][bb]");
+    }
+
+    #[test]
+    fn outcome() {
+        assert_eq!(Outcome::Win(Color::Black, WinBy::Score(3.5)).to_string(), "B+3.5");
+        assert_eq!(Outcome::Win(Color::White, WinBy::Resign).to_string(), "W+R");
+        assert_eq!(Outcome::Draw.to_string(), "0");
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = "(;GM[1]FF[4]SZ[9]KM[6.5]RU[Chinese]PB[a \\] b]RE[B+R]AB[aa][bb];W[cc]C[\\\\](;B[]V[0.5])(;B[dd]P[0a!]NN[a.json:0123abcd]))";
+        let games = parse(src).unwrap();
+
+        assert_eq!(format!("{}", games[0]), src);
+    }
+}
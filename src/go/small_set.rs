@@ -12,6 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io;
+
+use util::binary;
+
 /// A LRA set that only keeps the eight most recently added values.
 #[derive(Clone)]
 pub struct SmallSet {
@@ -41,14 +45,40 @@ impl SmallSet {
         }
     }
 
-    /// Returns true if this set contains the given value.
-    /// 
+    /// Write the values in this set, and the position of the oldest value,
+    /// to the given writer.
+    ///
     /// # Arguments
-    /// 
-    /// * `other` - the value to look for
-    /// 
-    pub fn contains(&self, other: u64) -> bool {
-        (0..8).any(|x| self.buf[x] == other)
+    ///
+    /// * `f` - the writer to write to
+    ///
+    pub fn write_into<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        for &value in self.buf.iter() {
+            binary::write_u64(f, value)?;
+        }
+
+        binary::write_u8(f, self.count as u8)
+    }
+
+    /// Returns a set that was previously written using `write_into`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the reader to read from
+    ///
+    pub fn read_from<R: io::Read>(f: &mut R) -> io::Result<SmallSet> {
+        let mut set = SmallSet::new();
+
+        for value in set.buf.iter_mut() {
+            *value = binary::read_u64(f)?;
+        }
+
+        set.count = binary::read_u8(f)? as usize;
+        if set.count >= 8 {
+            return Err(binary::invalid_data("small set position out of range"));
+        }
+
+        Ok(set)
     }
 
     /// Returns an iterator over all elements in this set.
@@ -93,9 +123,9 @@ mod tests {
         s.push(2);
         s.push(3);
 
-        assert!(s.contains(1));
-        assert!(s.contains(2));
-        assert!(s.contains(3));
-        assert!(!s.contains(4));
+        assert!(s.iter().any(|x| x == 1));
+        assert!(s.iter().any(|x| x == 2));
+        assert!(s.iter().any(|x| x == 3));
+        assert!(!s.iter().any(|x| x == 4));
     }
 }
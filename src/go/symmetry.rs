@@ -17,18 +17,38 @@ use std::mem;
 
 use go::Board;
 
-fn get_transformation<F, G>(ax: F, ay: G) -> Box<[u16]>
+/// Returns the given transformation for a board of the given size that has
+/// been embedded into the top-left corner of a `19x19` board. Any vertex that
+/// is outside of the embedded board is mapped onto itself.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the embedded board
+/// * `ax` - the transformation of the (doubled) x coordinate
+/// * `ay` - the transformation of the (doubled) y coordinate
+///
+fn get_transformation<F, G>(size: usize, ax: F, ay: G) -> Box<[u16]>
     where F: Fn(i32, i32) -> i32, G: Fn(i32, i32) -> i32
 {
+    // the coordinates are doubled before they are centered around the origin
+    // so that even sized boards, whose center lies between two vertices, can
+    // be transformed using integer arithmetic.
+    let n = size as i32;
+
     (0..361)
         .map(|i| {
             let x = (i % 19) as i32;
             let y = (i / 19) as i32;
-            let tx = ax(x - 9, y - 9) + 9;
-            let ty = ay(x - 9, y - 9) + 9;
 
-            assert!(tx >= 0 && tx < 19, "tx {} -> {}", x, tx);
-            assert!(ty >= 0 && ty < 19, "ty {} -> {}", y, ty);
+            if x >= n || y >= n {
+                return i as u16;
+            }
+
+            let tx = (ax(2 * x - (n - 1), 2 * y - (n - 1)) + (n - 1)) / 2;
+            let ty = (ay(2 * x - (n - 1), 2 * y - (n - 1)) + (n - 1)) / 2;
+
+            assert!(tx >= 0 && tx < n, "tx {} -> {}", x, tx);
+            assert!(ty >= 0 && ty < n, "ty {} -> {}", y, ty);
 
             (19 * ty + tx) as u16
         })
@@ -36,30 +56,44 @@ fn get_transformation<F, G>(ax: F, ay: G) -> Box<[u16]>
         .into_boxed_slice()
 }
 
-lazy_static! {
-    /// Identity transformation.
-    static ref _IDENTITY: Box<[u16]> = get_transformation(|x,_| x, |_,y| y);
+/// All transformations for a board of the given size, in the same order as
+/// the variants of `Transform`.
+///
+/// # Arguments
+///
+/// * `size` - the width and height of the board
+///
+fn get_transformations(size: usize) -> Vec<Box<[u16]>> {
+    vec! [
+        // Identity transformation.
+        get_transformation(size, |x,_| x, |_,y| y),
+
+        // Flip the matrix across the horizontal axis.
+        get_transformation(size, |x,_| -x, |_,y| y),
 
-    /// Flip the matrix across the horizontal axis.
-    static ref _FLIP_LR: Box<[u16]> = get_transformation(|x,_| -x, |_,y| y);
+        // Flip the matrix across the vertical axis.
+        get_transformation(size, |x,_| x, |_,y| -y),
 
-    /// Flip the matrix across the vertical axis.
-    static ref _FLIP_UD: Box<[u16]> = get_transformation(|x,_| x, |_,y| -y);
+        // Flip the matrix across the main-diagonal.
+        get_transformation(size, |_,y| y, |x,_| x),
 
-    /// Flip the matrix across the main-diagonal.
-    static ref _TRANSPOSE_MAIN: Box<[u16]> = get_transformation(|_,y| y, |x,_| x);
+        // Flip the matrix across the anti-diagonal.
+        get_transformation(size, |_,y| -y, |x,_| -x),
 
-    /// Flip the matrix across the anti-diagonal.
-    static ref _TRANSPOSE_ANTI: Box<[u16]> = get_transformation(|_,y| -y, |x,_| -x);
+        // Rotate the matrix 90 degrees clock-wise.
+        get_transformation(size, |_,y| y, |x,_| -x),
 
-    /// Rotate the matrix 90 degrees clock-wise.
-    static ref _ROT_90: Box<[u16]> = get_transformation(|_,y| y, |x,_| -x);
+        // Rotate the matrix 180 degrees clock-wise.
+        get_transformation(size, |x,_| -x, |_,y| -y),
 
-    /// Rotate the matrix 180 degrees clock-wise.
-    static ref _ROT_180: Box<[u16]> = get_transformation(|x,_| -x, |_,y| -y);
+        // Rotate the matrix 270 degrees clock-wise.
+        get_transformation(size, |_,y| -y, |x,_| x)
+    ]
+}
 
-    /// Rotate the matrix 270 degrees clock-wise.
-    static ref _ROT_270: Box<[u16]> = get_transformation(|_,y| -y, |x,_| x);
+lazy_static! {
+    /// The transformation tables for every board size between `0` and `19`.
+    static ref TABLES: Vec<Vec<Box<[u16]>>> = (0..20).map(|size| get_transformations(size)).collect();
 }
 
 /// Available transformations that are part of the go boards symmetry group.
@@ -89,31 +123,37 @@ impl Transform {
         }
     }
 
-    pub fn apply(&self, index: usize) -> usize {
-        let dest = match *self {
-            Transform::Identity => _IDENTITY[index],
-            Transform::FlipLR => _FLIP_LR[index],
-            Transform::FlipUD => _FLIP_UD[index],
-            Transform::Transpose => _TRANSPOSE_MAIN[index],
-            Transform::TransposeAnti => _TRANSPOSE_ANTI[index],
-            Transform::Rot90 => _ROT_90[index],
-            Transform::Rot180 => _ROT_180[index],
-            Transform::Rot270 => _ROT_270[index],
-        };
-
-        dest as usize
+    /// Returns the index that the given vertex is moved to by this
+    /// transformation on a board of the given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    /// * `index` - the HW index of the vertex to transform
+    ///
+    pub fn apply(&self, size: usize, index: usize) -> usize {
+        self.get_table(size)[index] as usize
     }
 
-    pub fn get_table(&self) -> &'static [u16] {
+    /// Returns the lookup table of this transformation on a board of the
+    /// given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    ///
+    pub fn get_table(&self, size: usize) -> &'static [u16] {
+        let tables = &TABLES[size];
+
         match *self {
-            Transform::Identity => &_IDENTITY,
-            Transform::FlipLR => &_FLIP_LR,
-            Transform::FlipUD => &_FLIP_UD,
-            Transform::Transpose => &_TRANSPOSE_MAIN,
-            Transform::TransposeAnti => &_TRANSPOSE_ANTI,
-            Transform::Rot90 => &_ROT_90,
-            Transform::Rot180 => &_ROT_180,
-            Transform::Rot270 => &_ROT_270
+            Transform::Identity => &tables[0],
+            Transform::FlipLR => &tables[1],
+            Transform::FlipUD => &tables[2],
+            Transform::Transpose => &tables[3],
+            Transform::TransposeAnti => &tables[4],
+            Transform::Rot90 => &tables[5],
+            Transform::Rot180 => &tables[6],
+            Transform::Rot270 => &tables[7]
         }
     }
 }
@@ -135,9 +175,10 @@ fn reorder<T: Copy>(src: &[T], dst: &mut [T], mapping: &[u16]) {
 /// # Arguments
 /// 
 /// * `values` -
+/// * `size` - the width and height of the board
 /// * `transform` - 
 /// 
-pub fn apply<T: Copy>(values: &mut [T], transform: Transform) {
+pub fn apply<T: Copy>(values: &mut [T], size: usize, transform: Transform) {
     thread_local! {
         static WORKSPACE: RefCell<[i32; 361]> = RefCell::new([0; 361]);
     }
@@ -147,7 +188,7 @@ pub fn apply<T: Copy>(values: &mut [T], transform: Transform) {
     WORKSPACE.with(|workspace| { unsafe {
         let mut workspace = workspace.borrow_mut();
         let workspace = &mut *(&mut *workspace as *mut [i32] as *mut [T]);
-        let lookup: &[u16] = transform.get_table();
+        let lookup: &[u16] = transform.get_table(size);
         let n = values.len() / 361;
 
         for i in 0..n {
@@ -173,7 +214,7 @@ pub fn apply<T: Copy>(values: &mut [T], transform: Transform) {
 /// * `transform` -
 /// 
 pub fn is_symmetric(board: &Board, transform: Transform) -> bool {
-    let lookup: &[u16] = transform.get_table();
+    let lookup: &[u16] = transform.get_table(board.size());
 
     (0..361).all(|i| {
         let j = lookup[i] as usize;
@@ -201,7 +242,7 @@ mod tests {
     #[test]
     pub fn identity() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::Identity);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::Identity);
 
         test_uniq(&seq);
     }
@@ -209,7 +250,7 @@ mod tests {
     #[test]
     pub fn flip_lr() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::FlipLR);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::FlipLR);
 
         test_uniq(&seq);
     }
@@ -217,7 +258,7 @@ mod tests {
     #[test]
     pub fn flip_ud() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::FlipUD);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::FlipUD);
 
         test_uniq(&seq);
     }
@@ -225,7 +266,7 @@ mod tests {
     #[test]
     pub fn transpose() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::Transpose);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::Transpose);
 
         test_uniq(&seq);
     }
@@ -233,7 +274,7 @@ mod tests {
     #[test]
     pub fn transpose_anti() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::TransposeAnti);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::TransposeAnti);
 
         test_uniq(&seq);
     }
@@ -241,7 +282,7 @@ mod tests {
     #[test]
     pub fn rot90() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::Rot90);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::Rot90);
 
         test_uniq(&seq);
     }
@@ -249,7 +290,7 @@ mod tests {
     #[test]
     pub fn rot180() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::Rot180);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::Rot180);
 
         test_uniq(&seq);
     }
@@ -257,8 +298,40 @@ mod tests {
     #[test]
     pub fn rot270() {
         let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
-        symmetry::apply(&mut seq, symmetry::Transform::Rot270);
+        symmetry::apply(&mut seq, 19, symmetry::Transform::Rot270);
 
         test_uniq(&seq);
     }
+
+    #[test]
+    pub fn small_boards() {
+        for &size in &[5, 8, 9, 13] {
+            for &t in &[
+                symmetry::Transform::FlipLR,
+                symmetry::Transform::FlipUD,
+                symmetry::Transform::Transpose,
+                symmetry::Transform::TransposeAnti,
+                symmetry::Transform::Rot90,
+                symmetry::Transform::Rot180,
+                symmetry::Transform::Rot270
+            ] {
+                let mut seq = (0..361).map(|i| i as f32).collect::<Vec<f32>>();
+                symmetry::apply(&mut seq, size, t);
+
+                test_uniq(&seq);
+
+                for i in 0..361 {
+                    let (x, y) = (i % 19, i / 19);
+                    let j = t.apply(size, i);
+
+                    if x >= size || y >= size {
+                        assert_eq!(j, i);
+                    } else {
+                        assert!(j % 19 < size && j / 19 < size);
+                        assert_eq!(t.inverse().apply(size, j), i);
+                    }
+                }
+            }
+        }
+    }
 }
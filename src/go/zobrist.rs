@@ -12,6 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+/// Random 64-bit integers for the player to move, indexed by the color of
+/// the player that made the most recent move. This is used to distinguish
+/// between positions with a different player to move for situational
+/// super-ko.
+pub const TURN: [u64; 3] = [0, 0x4da4f9fc3c6da5d7, 0xb8a1abcd1a6916c7];
+
 /// Automatically generated zobrist hash table of random 64-bit integers generated
 /// by a Python random function (MT).
 pub const TABLE: [[u64; 361]; 3] = [[
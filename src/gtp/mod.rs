@@ -13,27 +13,45 @@
 // limitations under the License.
 
 use ordered_float::*;
+use rand::{SeedableRng, XorShiftRng};
 use regex::Regex;
-use std::io::BufRead;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::time::{Duration, Instant};
 
 use go::sgf::*;
-use go::{Board, Color};
+use go::{self, Board, Color, Rules, Status};
 use mcts::predict::{self, PredictService};
 use mcts;
-use nn::Network;
+use nn::{LoadError, Network};
+use util::config;
 
+mod time_control;
 mod vertex;
 
-use gtp::vertex::*;
+use gtp::time_control::*;
+pub use gtp::vertex::Vertex;
 
 /// List containing all implemented commands, this is used to implement
 /// the `list_commands` and `known_command` commands.
-const KNOWN_COMMANDS: [&'static str; 17] = [
+const KNOWN_COMMANDS: [&'static str; 31] = [
     "protocol_verion", "name", "version", "boardsize", "clear_board", "komi", "play",
     "list_commands", "known_command", "showboard", "genmove", "reg_genmove", "undo",
-    "quit", "final_score", "heatmap", "sabaki-genmovelog"
+    "quit", "final_score", "final_status_list", "fixed_handicap", "place_free_handicap",
+    "set_free_handicap", "time_settings", "time_left", "heatmap", "sabaki-genmovelog",
+    "kgs-rules", "kgs-time_settings", "lz-analyze", "loadsgf",
+    "printsgf", "savetree", "loadtree", "loadweights"
 ];
 
+/// The default interval (in centiseconds) between each line of output from
+/// the `lz-analyze` command.
+const DEFAULT_ANALYZE_INTERVAL: usize = 100;
+
+/// The number of playouts to use when estimating the status of each stone
+/// at the end of the game.
+const NUM_STATUS_PLAYOUTS: usize = 64;
+
 #[derive(Debug, PartialEq)]
 enum Command {
     Pass,  // do nothing
@@ -42,15 +60,28 @@ enum Command {
     Version,  // report the version number of the program
     BoardSize(usize),  // set the board size to NxN
     ClearBoard,  // clear the board
+    FixedHandicap(usize),  // place the given number of handicap stones at fixed vertices
+    PlaceFreeHandicap(usize),  // place the given number of handicap stones at any vertices
+    SetFreeHandicap(Vec<Vertex>),  // place handicap stones at the given vertices
     Heatmap(Color),  // sabaki heatmap for the given color
     Komi(f32),  // set the komi
+    KgsRules(Rules),  // set the rules
+    TimeSettings(TimeSettings),  // set the time settings
+    TimeLeft(Color, TimeLeft),  // set the remaining time for the given color
     Play(Color, Vertex),  // play a stone of the given color at the given vertex
     ListCommands,  // list all available commands
     KnownCommand(String),  // tell whether a command is known
     ShowBoard,  // write the position to stdout
+    LoadSgf(String, Option<usize>),  // replace the game with the one in the given SGF file, up to the given move number
+    PrintSgf(Option<String>),  // write the game as an SGF file to stdout, or the given file
+    SaveTree(String),  // write the current search tree to the given file
+    LoadTree(String),  // replace the game and the search tree with the ones in the given file
+    LoadWeights(String),  // replace the network with the weights in the given file
     GenMove(Color),  // generate and play the supposedly best move for either color
     GenMoveLog,  // output all variations considered by the most recent search
+    LzAnalyze(Option<Color>, usize),  // search until the next command, and periodically output the candidate moves
     FinalScore,  // write the score to stdout
+    FinalStatusList(Status),  // write the stones with the given status to stdout
     RegGenMove(Color),  // generate the supposedly best move for either color
     Undo,  // undo one move
     Quit  // quit
@@ -77,23 +108,65 @@ macro_rules! error {
 lazy_static! {
     static ref ID_PREFIX: Regex = Regex::new(r"^([0-9]+)(?: +(.*)$|$)").unwrap();
     static ref BOARD_SIZE: Regex = Regex::new(r"^boardsize +([0-9]+)").unwrap();
+    static ref FIXED_HANDICAP: Regex = Regex::new(r"^fixed_handicap +([0-9]+)").unwrap();
+    static ref PLACE_FREE_HANDICAP: Regex = Regex::new(r"^place_free_handicap +([0-9]+)").unwrap();
+    static ref SET_FREE_HANDICAP: Regex = Regex::new(r"^set_free_handicap +(.+)").unwrap();
     static ref HEATMAP: Regex = Regex::new(r"^heatmap +([bw])").unwrap();
-    static ref KOMI: Regex = Regex::new(r"^komi +([0-9\.]+)").unwrap();
+    static ref KOMI: Regex = Regex::new(r"^komi +(-?[0-9\.]+)").unwrap();
+    static ref KGS_RULES: Regex = Regex::new(r"^kgs-rules +([^ ]+)").unwrap();
+    static ref TIME_SETTINGS: Regex = Regex::new(r"^time_settings +([0-9]+) +([0-9]+) +([0-9]+)").unwrap();
+    static ref KGS_TIME_SETTINGS: Regex = Regex::new(r"^kgs-time_settings +([a-z]+)((?: +[0-9]+)*)").unwrap();
+    static ref TIME_LEFT: Regex = Regex::new(r"^time_left +([bw]|black|white) +([0-9]+) +([0-9]+)").unwrap();
     static ref PLAY: Regex = Regex::new(r"^play +([bBwW]) +([a-z][0-9]+|pass)").unwrap();
     static ref KNOWN_COMMAND: Regex = Regex::new(r"^known_command +([^ ]+)").unwrap();
+    static ref FINAL_STATUS_LIST: Regex = Regex::new(r"^final_status_list +(alive|dead|seki)").unwrap();
     static ref GENMOVE: Regex = Regex::new(r"^genmove +([bw])").unwrap();
     static ref REG_GENMOVE: Regex = Regex::new(r"^reg_genmove +([bBwW])").unwrap();
+    static ref LOADSGF: Regex = Regex::new(r"^(?i:loadsgf) +([^ ]+)(?: +([0-9]+))? *$").unwrap();
+    static ref PRINTSGF: Regex = Regex::new(r"^(?i:printsgf)(?: +([^ ]+))? *$").unwrap();
+    static ref SAVETREE: Regex = Regex::new(r"^(?i:savetree) +([^ ]+) *$").unwrap();
+    static ref LOADTREE: Regex = Regex::new(r"^(?i:loadtree) +([^ ]+) *$").unwrap();
+    static ref LOADWEIGHTS: Regex = Regex::new(r"^(?i:loadweights) +([^ ]+) *$").unwrap();
+    static ref LZ_ANALYZE: Regex = Regex::new(r"^lz-analyze(?: +([bBwW]|black|white))?(?: +(?:interval +)?([0-9]+))? *$").unwrap();
 }
 
 struct Gtp {
     service: Option<PredictService>,
+    use_network: bool,
     search_tree: Option<mcts::tree::Node<mcts::tree::DefaultValue>>,
+    ponder: Option<mcts::Ponder<mcts::tree::DefaultValue>>,
+    is_analyzing: bool,
     last_log: String,
     history: Vec<Board>,
-    komi: f32
+    first_to_move: Option<Color>,
+    evaluations: HashMap<(usize, usize), (String, String)>,
+    final_status: Option<(u64, Vec<Option<Status>>)>,
+    komi: f32,
+    time_settings: TimeSettings,
+    time_left: [TimeLeft; 3]
 }
 
 impl Gtp {
+    /// Returns a GTP client with an empty board, using the rules and komi
+    /// from the command-line, and no time limit.
+    fn new() -> Gtp {
+        Gtp {
+            service: None,
+            use_network: true,
+            search_tree: None,
+            ponder: None,
+            is_analyzing: false,
+            last_log: "{}".to_string(),
+            history: vec! [Board::with_rules(19, *config::RULES)],
+            first_to_move: None,
+            evaluations: HashMap::new(),
+            final_status: None,
+            komi: config::KOMI.sample(),
+            time_settings: TimeSettings::None,
+            time_left: [TimeSettings::None.time_left(); 3]
+        }
+    }
+
     /// Parse the GTP command in the given string and returns our internal
     /// representation of the given command.
     /// 
@@ -103,6 +176,9 @@ impl Gtp {
     /// * `line` -
     /// 
     fn parse_command(id: Option<usize>, line: &str) -> Option<(Option<usize>, Command)> {
+        // the commands that take a path are matched against the original
+        // line, so that the case of the path is preserved
+        let original = line;
         let line = &line.to_lowercase();
 
         if line == "protocol_version" {
@@ -122,6 +198,35 @@ impl Gtp {
             }
         } else if line == "clear_board" {
             Some((id, Command::ClearBoard))
+        } else if let Some(caps) = FIXED_HANDICAP.captures(line) {
+            let num_stones = caps[1].parse::<usize>();
+
+            if let Ok(num_stones) = num_stones {
+                Some((id, Command::FixedHandicap(num_stones)))
+            } else {
+                error!(id, "syntax error");
+                Some((None, Command::Pass))
+            }
+        } else if let Some(caps) = PLACE_FREE_HANDICAP.captures(line) {
+            let num_stones = caps[1].parse::<usize>();
+
+            if let Ok(num_stones) = num_stones {
+                Some((id, Command::PlaceFreeHandicap(num_stones)))
+            } else {
+                error!(id, "syntax error");
+                Some((None, Command::Pass))
+            }
+        } else if let Some(caps) = SET_FREE_HANDICAP.captures(line) {
+            let vertices = caps[1].split_whitespace()
+                .map(|vertex| vertex.parse::<Vertex>())
+                .collect::<Result<Vec<Vertex>, ()>>();
+
+            if let Ok(vertices) = vertices {
+                Some((id, Command::SetFreeHandicap(vertices)))
+            } else {
+                error!(id, "syntax error");
+                Some((None, Command::Pass))
+            }
         } else if let Some(caps) = HEATMAP.captures(line) {
             let color = caps[1].parse::<Color>();
 
@@ -140,6 +245,55 @@ impl Gtp {
                 error!(id, "syntax error");
                 Some((None, Command::Pass))
             }
+        } else if let Some(caps) = KGS_RULES.captures(line) {
+            let rules = caps[1].parse::<Rules>();
+
+            if let Ok(rules) = rules {
+                Some((id, Command::KgsRules(rules)))
+            } else {
+                error!(id, "unknown rules");
+                Some((None, Command::Pass))
+            }
+        } else if let Some(caps) = TIME_SETTINGS.captures(line) {
+            let main_time = caps[1].parse::<f32>();
+            let byo_yomi_time = caps[2].parse::<f32>();
+            let byo_yomi_stones = caps[3].parse::<usize>();
+
+            if let (Ok(main_time), Ok(byo_yomi_time), Ok(byo_yomi_stones)) = (main_time, byo_yomi_time, byo_yomi_stones) {
+                Some((id, Command::TimeSettings(TimeSettings::from_gtp(main_time, byo_yomi_time, byo_yomi_stones))))
+            } else {
+                error!(id, "syntax error");
+                Some((None, Command::Pass))
+            }
+        } else if let Some(caps) = KGS_TIME_SETTINGS.captures(line) {
+            let args = caps[2].split_whitespace()
+                .filter_map(|arg| arg.parse::<usize>().ok())
+                .collect::<Vec<usize>>();
+            let settings = match (&caps[1], args.len()) {
+                ("none", 0) => Some(TimeSettings::None),
+                ("absolute", 1) => Some(TimeSettings::Absolute(args[0] as f32)),
+                ("byoyomi", 3) => Some(TimeSettings::ByoYomi(args[0] as f32, args[1] as f32, args[2])),
+                ("canadian", 3) => Some(TimeSettings::from_gtp(args[0] as f32, args[1] as f32, args[2])),
+                _ => None
+            };
+
+            if let Some(settings) = settings {
+                Some((id, Command::TimeSettings(settings)))
+            } else {
+                error!(id, "syntax error");
+                Some((None, Command::Pass))
+            }
+        } else if let Some(caps) = TIME_LEFT.captures(line) {
+            let color = caps[1].parse::<Color>();
+            let time = caps[2].parse::<f32>();
+            let stones = caps[3].parse::<usize>();
+
+            if let (Ok(color), Ok(time), Ok(stones)) = (color, time, stones) {
+                Some((id, Command::TimeLeft(color, TimeLeft { time: time, stones: stones })))
+            } else {
+                error!(id, "syntax error");
+                Some((None, Command::Pass))
+            }
         } else if let Some(caps) = PLAY.captures(line) {
             let color = caps[1].parse::<Color>();
             let vertex = caps[2].parse::<Vertex>();
@@ -163,6 +317,29 @@ impl Gtp {
             Some((id, Command::KnownCommand(command.to_string())))
         } else if line == "showboard" {
             Some((id, Command::ShowBoard))
+        } else if let Some(caps) = LOADSGF.captures(original) {
+            let path = caps[1].to_string();
+            let move_number = caps.get(2).map(|move_number| move_number.as_str().parse::<usize>());
+
+            match move_number {
+                Some(Err(_)) => {
+                    error!(id, "syntax error");
+                    Some((None, Command::Pass))
+                },
+                move_number => {
+                    Some((id, Command::LoadSgf(path, move_number.map(|move_number| move_number.unwrap()))))
+                }
+            }
+        } else if let Some(caps) = PRINTSGF.captures(original) {
+            let path = caps.get(1).map(|path| path.as_str().to_string());
+
+            Some((id, Command::PrintSgf(path)))
+        } else if let Some(caps) = SAVETREE.captures(original) {
+            Some((id, Command::SaveTree(caps[1].to_string())))
+        } else if let Some(caps) = LOADTREE.captures(original) {
+            Some((id, Command::LoadTree(caps[1].to_string())))
+        } else if let Some(caps) = LOADWEIGHTS.captures(original) {
+            Some((id, Command::LoadWeights(caps[1].to_string())))
         } else if let Some(caps) = GENMOVE.captures(line) {
             let color = caps[1].parse::<Color>();
 
@@ -174,8 +351,30 @@ impl Gtp {
             }
         } else if line == "sabaki-genmovelog" {
             Some((id, Command::GenMoveLog))
+        } else if let Some(caps) = LZ_ANALYZE.captures(line) {
+            let color = caps.get(1).map(|color| color.as_str().parse::<Color>());
+            let interval = caps.get(2)
+                .map_or(Ok(DEFAULT_ANALYZE_INTERVAL), |interval| interval.as_str().parse::<usize>());
+
+            match (color, interval) {
+                (Some(Err(_)), _) | (_, Err(_)) => {
+                    error!(id, "syntax error");
+                    Some((None, Command::Pass))
+                },
+                (color, Ok(interval)) => {
+                    Some((id, Command::LzAnalyze(color.map(|color| color.unwrap()), interval)))
+                }
+            }
         } else if line == "final_score" {
             Some((id, Command::FinalScore))
+        } else if let Some(caps) = FINAL_STATUS_LIST.captures(line) {
+            let status = match &caps[1] {
+                "alive" => Status::Alive,
+                "dead" => Status::Dead,
+                _ => Status::Seki
+            };
+
+            Some((id, Command::FinalStatusList(status)))
         } else if let Some(caps) = REG_GENMOVE.captures(line) {
             let color = caps[1].parse::<Color>();
 
@@ -225,13 +424,15 @@ impl Gtp {
     }
 
     /// Create the `PredictService` if it does not exist, and then returns the
-    /// current service.
+    /// current service. No service is created if `use_network` is false.
     fn open_service(&mut self) -> &Option<PredictService> {
-        if self.service.is_none() {
+        if self.service.is_none() && self.use_network {
             match Network::new() {
-                None => {},
-                Some(network) => {
+                Ok(network) => {
                     self.service = Some(predict::service(network));
+                },
+                Err(reason) => {
+                    eprintln!("Could not load network weights -- {}", reason);
                 }
             }
         }
@@ -263,18 +464,30 @@ impl Gtp {
                 }
             });
 
-            let (value, index, tree) = mcts::predict::<mcts::tree::DefaultValue>(
+            let time_limit = self.time_settings.get_budget(&self.time_left[color as usize], &board);
+            let budget = mcts::Budget::rollouts(*config::NUM_ROLLOUT)
+                .with_time_limit(time_limit)
+                .with_early_stop(true);
+            let start_time = Instant::now();
+            let (value, index, tree) = mcts::predict::<mcts::tree::DefaultValue, _>(
                 &service.lock(),
                 None,
+                budget,
                 search_tree,
                 &board,
-                color
+                color,
+                self.komi
             );
 
             eprintln!("{}", mcts::tree::to_pretty(&tree));
 
-            self.last_log = format!("{}", mcts::tree::to_sgf::<Sabaki, _>(&tree, &board, false));
+            self.last_log = format!("{}", mcts::tree::to_sgf::<Sabaki, _>(&tree, &board, self.komi, false));
+            self.evaluations.insert(
+                (self.history.len(), index),
+                (format!("Win: {:.1}%, Nodes: {}", 100.0 * value, tree.size()), service.get_evaluator().name())
+            );
             self.search_tree = Some(tree);
+            self.time_left[color as usize].subtract(start_time.elapsed());
 
             if value < 0.025 {  // 2.5% chance of winning
                 success!(id, "resign");
@@ -298,6 +511,86 @@ impl Gtp {
         }
     }
 
+    /// Start expanding the current search tree in the background, if pondering
+    /// is enabled and there is a search tree to expand.
+    fn start_ponder(&mut self) {
+        if !*config::PONDER {
+            return;
+        }
+
+        if let Some(ref service) = self.service {
+            if let Some(tree) = self.search_tree.take() {
+                let board = self.history.last().unwrap();
+
+                self.ponder = Some(mcts::Ponder::start(&service.lock(), tree, board, self.komi));
+            }
+        }
+    }
+
+    /// Stop expanding the current search tree in the background, if we are
+    /// pondering, and restore the search tree so that it can be re-used.
+    fn stop_ponder(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            self.search_tree = Some(ponder.stop());
+        }
+
+        if self.is_analyzing {
+            // terminate the response of the `lz-analyze` command
+            println!();
+
+            self.is_analyzing = false;
+        }
+    }
+
+    /// Search the current board position in the background, and output the
+    /// candidate moves of the search tree every `interval` centiseconds
+    /// until the next command is received.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the identifier of the command
+    /// * `color` - the color to analyze the board position for
+    /// * `interval` - the time (in centiseconds) between each line of output
+    ///
+    fn analyze(&mut self, id: Option<usize>, color: Color, interval: usize) {
+        self.open_service();
+
+        if let Some(ref service) = self.service {
+            let board = self.history.last().unwrap();
+            let search_tree = self.search_tree.take().and_then(|tree| {
+                if tree.color != color {
+                    mcts::tree::Node::forward(tree, 361)  // pass
+                } else {
+                    Some(tree)
+                }
+            });
+
+            match id {
+                None => println!("="),
+                Some(id) => println!("={}", id)
+            }
+
+            self.is_analyzing = true;
+            self.ponder = Some(mcts::Ponder::analyze(
+                &service.lock(),
+                search_tree,
+                board,
+                color,
+                self.komi,
+                Duration::from_millis(10 * ::std::cmp::max(interval, 1) as u64),
+                |root| {
+                    let stdout = io::stdout();
+                    let mut stdout = stdout.lock();
+
+                    writeln!(stdout, "{}", mcts::tree::to_analyze(root)).unwrap();
+                    stdout.flush().unwrap();
+                }
+            ));
+        } else {
+            error!(id, "unable to load network weights");
+        }
+    }
+
     /// Output all variations that were considered in the most recent search
     /// tree.
     /// 
@@ -314,8 +607,9 @@ impl Gtp {
     /// # Arguments
     /// 
     /// * `softmax` -
+    /// * `size` - the width and height of the board
     /// 
-    fn to_heatmap(softmax: &[f32]) -> String {
+    fn to_heatmap(softmax: &[f32], size: usize) -> String {
         // format the flat softmax policy as a nested list (in JSON), where
         // each list correspond to one row on the board. The elements in the
         // inner list is the heat of each vertex, discretized to an integer
@@ -325,9 +619,9 @@ impl Gtp {
             .max_by_key(|&&v| OrderedFloat(v))
             .unwrap();
 
-        for (index, _heat) in softmax.iter().take(361).enumerate() {
-            let y = index / 19;
-            let x = index % 19;
+        for index in 0..(size * size) {
+            let y = index / size;
+            let x = index % size;
 
             if x == 0 {
                 if y > 0 {
@@ -344,7 +638,7 @@ impl Gtp {
             // the GTP coordinates go from the bottom-left to the top-right, but
             // Sabaki heatmap coordinates go from the top-left to the
             // bottom-right (...) so inverse the y-axis.
-            let other = softmax[19 * (18 - y) + x];
+            let other = softmax[19 * (size - y - 1) + x];
 
             json += &format!("{}", (9.0 * other / max_heat).ceil());
         }
@@ -372,18 +666,22 @@ impl Gtp {
                     Some(tree)
                 }
             });
-            let (_value, _index, tree) = mcts::predict::<mcts::tree::DefaultValue>(
+            // the heatmap shows the visit distribution of every move, so do
+            // not stop early once the best move has been settled
+            let (_value, _index, tree) = mcts::predict::<mcts::tree::DefaultValue, _>(
                 &service.lock(),
                 None,
+                mcts::Budget::rollouts(*config::NUM_ROLLOUT),
                 search_tree,
                 &board,
-                color
+                color,
+                self.komi
             );
 
             eprintln!("{}", mcts::tree::to_pretty(&tree));
 
             // output the heatmap in Sabaki format
-            let json = Gtp::to_heatmap(&tree.softmax());
+            let json = Gtp::to_heatmap(&tree.softmax(), board.size());
 
             success!(id, &format!("#sabaki{}", json));
         } else {
@@ -391,7 +689,360 @@ impl Gtp {
         }
     }
 
+    /// Place black handicap stones at the given vertices on an empty board,
+    /// replacing the current game. Returns false if the board is not empty,
+    /// or if the vertices are not valid handicap stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - the vertices to place the handicap stones at
+    ///
+    fn set_handicap(&mut self, vertices: &[Vertex]) -> bool {
+        let size = self.history[0].size();
+        let rules = self.history[0].rules();
+
+        if self.history.last().unwrap().zobrist_hash() != 0 {
+            return false;
+        }
+
+        if vertices.len() < 2 || vertices.len() >= size * size {
+            return false;
+        }
+
+        let mut board = Board::with_rules(size, rules);
+
+        for vertex in vertices {
+            if vertex.x >= size || vertex.y >= size || board.at(vertex.x, vertex.y).is_some() {
+                return false;
+            }
+
+            board.setup(Color::Black, vertex.x, vertex.y);
+        }
+
+        self.reset(board);
+        true
+    }
+
+    /// Replace the current game with a new game that starts from the given
+    /// board position.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the initial board position of the new game
+    ///
+    fn reset(&mut self, board: Board) {
+        self.history = vec! [board];
+        self.first_to_move = None;
+        self.evaluations.clear();
+        self.search_tree = None;
+    }
+
+    /// Replace the current game with the main line of the first game in the
+    /// given SGF file, stopping before the given move number is played.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the SGF file
+    /// * `move_number` - the number of the first move not to play, or `None`
+    ///   to play all moves
+    ///
+    fn load_sgf(&mut self, path: &str, move_number: Option<usize>) -> Result<(), &'static str> {
+        let mut src = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut src))
+            .map_err(|_| "cannot read file")?;
+
+        let games = go::sgf::parse(&src).map_err(|_| "cannot parse file")?;
+        let game = games.first().ok_or("cannot parse file")?;
+        let mut size = 19;
+        let mut rules = self.history[0].rules();
+        let mut komi = None;
+
+        for property in game.root().properties.iter() {
+            match *property {
+                Property::Size(other) => { size = other; },
+                Property::Rules(ref other) => { rules = other.parse::<Rules>().unwrap_or(rules); },
+                Property::Komi(other) => { komi = Some(other); },
+                _ => {}
+            }
+        }
+
+        if size < 5 || size > 19 {
+            return Err("unacceptable size");
+        }
+
+        let mut history = vec! [Board::with_rules(size, rules)];
+        let mut count = 1;
+
+        'nodes: for node in game.main_line() {
+            for property in node.properties.iter() {
+                let board = history.last_mut().unwrap();
+                let (color, points) = match *property {
+                    Property::AddBlack(ref points) => (Color::Black, points),
+                    Property::AddWhite(ref points) => (Color::White, points),
+                    _ => { continue; }
+                };
+
+                for &(x, y) in points.iter() {
+                    if x >= size || y >= size {
+                        return Err("bad setup stones");
+                    }
+
+                    let (x, y) = Sabaki::to_point(x, y, size);
+
+                    if board.at(x, y).is_some() {
+                        return Err("bad setup stones");
+                    }
+
+                    board.setup(color, x, y);
+                }
+            }
+
+            for property in node.properties.iter() {
+                let (color, point) = match *property {
+                    Property::Move(color, point) => (color, point),
+                    _ => { continue; }
+                };
+
+                if move_number.map_or(false, |move_number| count >= move_number) {
+                    break 'nodes;
+                }
+
+                let mut board = history.last().unwrap().clone();
+
+                if let Some((x, y)) = point.filter(|&(x, y)| x < size && y < size) {
+                    let (x, y) = Sabaki::to_point(x, y, size);
+
+                    if !board.is_valid(color, x, y) {
+                        return Err("illegal move");
+                    }
+
+                    board.place(color, x, y);
+                }
+
+                history.push(board);
+                count += 1;
+            }
+        }
+
+        self.history = history;
+        self.first_to_move = None;
+        self.evaluations.clear();
+        self.search_tree = None;
+        self.komi = komi.unwrap_or_else(|| rules.komi());
+        Ok(())
+    }
+
+    /// Write the current search tree, together with the current board
+    /// position and komi, to the given file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the file
+    ///
+    fn save_tree(&self, path: &str) -> Result<(), &'static str> {
+        let tree = self.search_tree.as_ref().ok_or("no search tree")?;
+        let board = self.history.last().unwrap();
+
+        File::create(path)
+            .and_then(|file| mcts::save_tree(&mut BufWriter::new(file), tree, board, self.komi))
+            .map_err(|_| "cannot write file")
+    }
+
+    /// Replace the current game with a new game that starts from the board
+    /// position in the given file, and continue searching from the search
+    /// tree in the same file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the file
+    ///
+    fn load_tree(&mut self, path: &str) -> Result<(), &'static str> {
+        let file = File::open(path).map_err(|_| "cannot read file")?;
+        let (tree, board, komi) = mcts::load_tree(&mut BufReader::new(file))
+            .map_err(|_| "cannot parse file")?;
+
+        self.reset(board);
+        self.first_to_move = Some(tree.color);
+        self.search_tree = Some(tree);
+        self.komi = komi;
+        Ok(())
+    }
+
+    /// Replace the network with the one stored in the given file. Any
+    /// background search is stopped first, and the search tree is discarded
+    /// since it was built using the previous network.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the network weights
+    ///
+    fn load_weights(&mut self, path: &str) -> Result<(), LoadError> {
+        let network = Network::from_path(path)?;
+
+        // the previous service stops answering requests once it is dropped,
+        // so every search that is using it must have terminated before then
+        self.stop_ponder();
+        self.service = Some(predict::service(network));
+        self.search_tree = None;
+        self.final_status = None;
+        Ok(())
+    }
+
+    /// Returns the color of the player whose turn it is to play in the current
+    /// board position, assuming that the players has alternated and that white
+    /// plays first in handicap games. If the game was loaded from a search
+    /// tree then the player to move in that tree plays first.
+    fn to_move(&self) -> Color {
+        let first = self.first_to_move.unwrap_or_else(|| {
+            if self.history[0].zobrist_hash() != 0 { Color::White } else { Color::Black }
+        });
+
+        if self.history.len() % 2 == 1 { first } else { first.opposite() }
+    }
+
+    /// Returns the current game as an SGF game tree, where the moves are
+    /// recovered from the differences between each board position in the
+    /// history. Any moves generated by the engine are annotated with its
+    /// evaluation.
+    fn to_game_tree(&self) -> GameTree {
+        let initial = &self.history[0];
+        let size = initial.size();
+        let vertices = (0..size).flat_map(|y| (0..size).map(move |x| (x, y)));
+        let stones = |color: Color| {
+            vertices.clone()
+                .filter(|&(x, y)| initial.at(x, y) == Some(color))
+                .map(|(x, y)| Sabaki::to_point(x, y, size))
+                .collect::<Vec<(usize, usize)>>()
+        };
+        let handicap = stones(Color::Black).len();
+        let mut root = Node {
+            properties: vec! [
+                Property::Unknown("GM".to_string(), vec! ["1".to_string()]),
+                Property::Unknown("FF".to_string(), vec! ["4".to_string()]),
+                Property::Application(format!("{}:{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+                Property::Size(size),
+                Property::Rules(initial.rules().to_string()),
+                Property::Komi(self.komi)
+            ]
+        };
+
+        if handicap > 0 {
+            root.properties.push(Property::Handicap(handicap));
+        }
+
+        root.properties.push(Property::AddBlack(stones(Color::Black)));
+        root.properties.push(Property::AddWhite(stones(Color::White)));
+
+        let mut nodes = vec! [root];
+        let mut color = if initial.zobrist_hash() != 0 { Color::White } else { Color::Black };
+
+        for i in 1..self.history.len() {
+            let (prev, next) = (&self.history[i - 1], &self.history[i]);
+            let played = vertices.clone()
+                .filter(|&(x, y)| prev.at(x, y).is_none() && next.at(x, y).is_some())
+                .next();
+
+            let mut node = if let Some((x, y)) = played {
+                color = next.at(x, y).unwrap();
+
+                Node { properties: vec! [Property::Move(color, Some(Sabaki::to_point(x, y, size)))] }
+            } else {
+                Node { properties: vec! [Property::Move(color, None)] }
+            };
+
+            // add the evaluation of the engine, if it generated this move
+            let index = played.map_or(361, |(x, y)| 19 * y + x);
+
+            if let Some(&(ref evaluation, ref network)) = self.evaluations.get(&(i, index)) {
+                node.properties.push(Property::Comment(evaluation.clone()));
+                node.properties.push(Property::Network(network.clone()));
+            }
+
+            nodes.push(node);
+
+            color = color.opposite();
+        }
+
+        GameTree { nodes: nodes, variations: vec! [] }
+    }
+
+    /// Returns the estimated status of every stone in the current board
+    /// position. The estimate is cached so that repeated calls for the same
+    /// position gives the same answer.
+    fn get_final_status(&mut self) -> Vec<Option<Status>> {
+        let board = self.history.last().unwrap().clone();
+        let color = self.to_move();
+        let hash = board.zobrist_hash() ^ self.history.len() as u64;
+
+        if let Some((other, ref status)) = self.final_status {
+            if other == hash {
+                return status.clone();
+            }
+        }
+
+        // use the neural network to guide the playouts if it is available,
+        // otherwise fallback to uniformly random playouts
+        self.open_service();
+
+        let ownership = if let Some(ref service) = self.service {
+            mcts::ownership(&service.lock(), &board, color, NUM_STATUS_PLAYOUTS)
+        } else {
+            // seed the playouts with the position so that the same position
+            // always gets the same status
+            let mut rng = XorShiftRng::from_seed([hash as u32, (hash >> 32) as u32, 0x9e3779b9, 0x7f4a7c15]);
+
+            go::random_ownership(&board, color, NUM_STATUS_PLAYOUTS, &mut rng)
+        };
+        let status = ownership.get_status(&board);
+
+        self.final_status = Some((hash, status.clone()));
+        status
+    }
+
+    /// Returns the score of the current board position after all dead stones
+    /// have been removed, formatted as specified by the `final_score` command.
+    fn final_score(&mut self) -> String {
+        let status = self.get_final_status();
+        let (black, white) = self.history.last().unwrap().get_final_score(&status);
+        let black = black as f32;
+        let white = white as f32 + self.komi;
+
+        if black > white {
+            format!("B+{:.1}", black - white)
+        } else if white > black {
+            format!("W+{:.1}", white - black)
+        } else {
+            "0".to_string()
+        }
+    }
+
+    /// Place the given handicap stones, chosen by the engine, and output
+    /// their vertices.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the identifier of the command
+    /// * `vertices` - the vertices of the handicap stones
+    ///
+    fn process_handicap(&mut self, id: Option<usize>, vertices: Vec<Vertex>) {
+        if self.set_handicap(&vertices) {
+            let vertices = vertices.iter()
+                .map(|vertex| format!("{}", vertex))
+                .collect::<Vec<String>>();
+
+            success!(id, &vertices.join(" "));
+        } else {
+            error!(id, "board not empty");
+        }
+    }
+
     fn process(&mut self, id: Option<usize>, cmd: Command) {
+        if cmd != Command::Pass {
+            self.stop_ponder();
+        }
+
         match cmd {
             Command::Quit => {}
             Command::Pass => {},
@@ -399,22 +1050,84 @@ impl Gtp {
             Command::Name => { success!(id, env!("CARGO_PKG_NAME")); },
             Command::Version => { success!(id, env!("CARGO_PKG_VERSION")); },
             Command::BoardSize(size) => {
-                if size != 19 {
+                if size < 5 || size > 19 {
                     error!(id, "unacceptable size");
                 } else {
-                    self.history = vec! [Board::new()];
+                    let rules = self.history[0].rules();
+
+                    self.reset(Board::with_rules(size, rules));
                     success!(id, "");
                 }
             },
             Command::ClearBoard => {
-                self.history = vec! [Board::new()];
-                self.search_tree = None;
+                let size = self.history[0].size();
+                let rules = self.history[0].rules();
+
+                self.reset(Board::with_rules(size, rules));
                 success!(id, "");
             },
+            Command::FixedHandicap(num_stones) => {
+                let size = self.history[0].size();
+                let vertices = go::fixed_handicap(size, num_stones)
+                    .map(|vertices| {
+                        vertices.into_iter()
+                            .map(|(x, y)| Vertex { x: x, y: y })
+                            .collect::<Vec<Vertex>>()
+                    });
+
+                if let Some(vertices) = vertices {
+                    self.process_handicap(id, vertices);
+                } else {
+                    error!(id, "invalid number of stones");
+                }
+            },
+            Command::PlaceFreeHandicap(num_stones) => {
+                let size = self.history[0].size();
+
+                if num_stones < 2 || num_stones >= size * size {
+                    error!(id, "invalid number of stones");
+                } else {
+                    let vertices = go::free_handicap(size, num_stones).into_iter()
+                        .map(|(x, y)| Vertex { x: x, y: y })
+                        .collect::<Vec<Vertex>>();
+
+                    self.process_handicap(id, vertices);
+                }
+            },
+            Command::SetFreeHandicap(vertices) => {
+                if self.history.last().unwrap().zobrist_hash() != 0 {
+                    error!(id, "board not empty");
+                } else if self.set_handicap(&vertices) {
+                    success!(id, "");
+                } else {
+                    error!(id, "bad vertex list");
+                }
+            },
             Command::Komi(komi) => {
+                if self.komi != komi {
+                    // the values in the search tree depends on the komi
+                    self.search_tree = None;
+                }
+
                 self.komi = komi;
                 success!(id, "");
             },
+            Command::KgsRules(rules) => {
+                let size = self.history[0].size();
+
+                self.reset(Board::with_rules(size, rules));
+                self.komi = rules.komi();
+                success!(id, "");
+            },
+            Command::TimeSettings(settings) => {
+                self.time_settings = settings;
+                self.time_left = [settings.time_left(); 3];
+                success!(id, "");
+            },
+            Command::TimeLeft(color, time_left) => {
+                self.time_left[color as usize] = time_left;
+                success!(id, "");
+            },
             Command::Heatmap(color) => {
                 self.heatmap(id, color);
             },
@@ -481,6 +1194,46 @@ impl Gtp {
 
                 success!(id, &format!("\n{}", board));
             },
+            Command::LoadSgf(path, move_number) => {
+                match self.load_sgf(&path, move_number) {
+                    Ok(()) => { success!(id, ""); },
+                    Err(reason) => { error!(id, reason); }
+                }
+            },
+            Command::SaveTree(path) => {
+                match self.save_tree(&path) {
+                    Ok(()) => { success!(id, ""); },
+                    Err(reason) => { error!(id, reason); }
+                }
+            },
+            Command::LoadTree(path) => {
+                match self.load_tree(&path) {
+                    Ok(()) => { success!(id, ""); },
+                    Err(reason) => { error!(id, reason); }
+                }
+            },
+            Command::LoadWeights(path) => {
+                match self.load_weights(&path) {
+                    Ok(()) => { success!(id, ""); },
+                    Err(reason) => { error!(id, &format!("cannot load weights -- {}", reason)); }
+                }
+            },
+            Command::PrintSgf(path) => {
+                let game_tree = format!("{}", self.to_game_tree());
+
+                if let Some(path) = path {
+                    let result = File::create(&path)
+                        .and_then(|mut file| writeln!(file, "{}", game_tree));
+
+                    if result.is_ok() {
+                        success!(id, "");
+                    } else {
+                        error!(id, "cannot write file");
+                    }
+                } else {
+                    success!(id, &game_tree);
+                }
+            },
             Command::GenMove(color) => {
                 let vertex = self.generate_move(id, color);
 
@@ -497,24 +1250,30 @@ impl Gtp {
                         mcts::tree::Node::forward(tree, 361)
                     });
                 }
+
+                self.start_ponder();
             },
             Command::GenMoveLog => {
                 self.generate_move_log(id);
             },
+            Command::LzAnalyze(color, interval) => {
+                let color = color.unwrap_or_else(|| self.to_move());
+
+                self.analyze(id, color, interval);
+            },
             Command::FinalScore => {
-                let board = self.history.last().unwrap();
-                let (black, white) = board.get_score();
-                let black = black as f32;
-                let white = white as f32 + self.komi;
-
-                if black == white {
-                    success!(id, "0");
-                } else if black > white {
-                    success!(id, &format!("B+{:.1}", black - white));
-                } else if white > black {
-                    success!(id, &format!("W+{:.1}", white - black));
-                }
-            }
+                let score = self.final_score();
+
+                success!(id, &score);
+            },
+            Command::FinalStatusList(status) => {
+                let vertices = self.get_final_status().iter().enumerate()
+                    .filter(|&(_, &other)| other == Some(status))
+                    .map(|(index, _)| format!("{}", Vertex { x: index % 19, y: index / 19 }))
+                    .collect::<Vec<String>>();
+
+                success!(id, &vertices.join(" "));
+            },
             Command::RegGenMove(color) => {
                 self.search_tree = None;
                 self.generate_move(id, color);
@@ -538,13 +1297,7 @@ impl Gtp {
 pub fn run() {
     let stdin = ::std::io::stdin();
     let stdin_lock = stdin.lock();
-    let mut gtp = Gtp {
-        service: None,
-        search_tree: None,
-        last_log: "{}".to_string(),
-        history: vec! [Board::new()],
-        komi: 7.5
-    };
+    let mut gtp = Gtp::new();
 
     for line in stdin_lock.lines() {
         if let Ok(line) = line {
@@ -591,6 +1344,70 @@ mod tests {
         assert_eq!(Gtp::parse_line("boardsize 13"), Some((None, Command::BoardSize(13))));
     }
 
+    #[test]
+    fn boardsize_small() {
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::BoardSize(9));
+        assert_eq!(gtp.history.last().unwrap().size(), 9);
+
+        gtp.process(None, Command::Play(Color::Black, Vertex { x: 8, y: 8 }));
+        gtp.process(None, Command::Play(Color::White, Vertex { x: 9, y: 0 }));
+        assert_eq!(gtp.history.len(), 2);
+
+        gtp.process(None, Command::ClearBoard);
+        assert_eq!(gtp.history.last().unwrap().size(), 9);
+
+        gtp.process(None, Command::BoardSize(25));
+        assert_eq!(gtp.history.last().unwrap().size(), 9);
+    }
+
+    #[test]
+    fn fixed_handicap() {
+        assert_eq!(Gtp::parse_line("1 fixed_handicap 2"), Some((Some(1), Command::FixedHandicap(2))));
+        assert_eq!(Gtp::parse_line("fixed_handicap 9"), Some((None, Command::FixedHandicap(9))));
+    }
+
+    #[test]
+    fn place_free_handicap() {
+        assert_eq!(Gtp::parse_line("1 place_free_handicap 2"), Some((Some(1), Command::PlaceFreeHandicap(2))));
+        assert_eq!(Gtp::parse_line("place_free_handicap 12"), Some((None, Command::PlaceFreeHandicap(12))));
+    }
+
+    #[test]
+    fn set_free_handicap() {
+        assert_eq!(
+            Gtp::parse_line("1 set_free_handicap d4 q16"),
+            Some((Some(1), Command::SetFreeHandicap(vec! [Vertex { x: 3, y: 3 }, Vertex { x: 15, y: 15 }])))
+        );
+        assert_eq!(Gtp::parse_line("set_free_handicap d4 i4"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn handicap_process() {
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::FixedHandicap(4));
+        assert_eq!(gtp.history.len(), 1);
+        assert_eq!(gtp.history[0].at(3, 3), Some(Color::Black));
+        assert_eq!(gtp.history[0].at(15, 3), Some(Color::Black));
+
+        // the board is no longer empty
+        assert!(!gtp.set_handicap(&[Vertex { x: 0, y: 0 }, Vertex { x: 1, y: 1 }]));
+
+        gtp.process(None, Command::ClearBoard);
+        assert!(!gtp.set_handicap(&[Vertex { x: 0, y: 0 }, Vertex { x: 0, y: 0 }]));
+        assert!(gtp.set_handicap(&[Vertex { x: 0, y: 0 }, Vertex { x: 1, y: 1 }]));
+        assert_eq!(gtp.history[0].at(1, 1), Some(Color::Black));
+    }
+
+    #[test]
+    fn vertex_from_index() {
+        assert_eq!(format!("{}", Vertex::from_index(19 * 3 + 8)), "j4");
+        assert_eq!(format!("{}", Vertex::from_index(361)), "pass");
+        assert_eq!(Vertex::from_index(19 * 18 + 2), Vertex { x: 2, y: 18 });
+    }
+
     #[test]
     fn clear_board() {
         assert_eq!(Gtp::parse_line("1 clear_board"), Some((Some(1), Command::ClearBoard)));
@@ -601,6 +1418,50 @@ mod tests {
     fn komi() {
         assert_eq!(Gtp::parse_line("1 komi 0.5"), Some((Some(1), Command::Komi(0.5))));
         assert_eq!(Gtp::parse_line("komi 10"), Some((None, Command::Komi(10.0))));
+        assert_eq!(Gtp::parse_line("komi -6.5"), Some((None, Command::Komi(-6.5))));
+    }
+
+    #[test]
+    fn kgs_rules() {
+        assert_eq!(Gtp::parse_line("1 kgs-rules japanese"), Some((Some(1), Command::KgsRules(Rules::Japanese))));
+        assert_eq!(Gtp::parse_line("kgs-rules new_zealand"), Some((None, Command::KgsRules(Rules::NewZealand))));
+        assert_eq!(Gtp::parse_line("kgs-rules ing"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn kgs_rules_process() {
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::BoardSize(13));
+        gtp.process(None, Command::KgsRules(Rules::Japanese));
+        assert_eq!(gtp.history.last().unwrap().rules(), Rules::Japanese);
+        assert_eq!(gtp.history.last().unwrap().size(), 13);
+        assert_eq!(gtp.komi, 6.5);
+
+        gtp.process(None, Command::ClearBoard);
+        assert_eq!(gtp.history.last().unwrap().rules(), Rules::Japanese);
+    }
+
+    #[test]
+    fn time_settings() {
+        assert_eq!(Gtp::parse_line("1 time_settings 300 30 5"), Some((Some(1), Command::TimeSettings(TimeSettings::Canadian(300.0, 30.0, 5)))));
+        assert_eq!(Gtp::parse_line("time_settings 300 0 0"), Some((None, Command::TimeSettings(TimeSettings::Absolute(300.0)))));
+        assert_eq!(Gtp::parse_line("time_settings 0 1 0"), Some((None, Command::TimeSettings(TimeSettings::None))));
+    }
+
+    #[test]
+    fn kgs_time_settings() {
+        assert_eq!(Gtp::parse_line("1 kgs-time_settings none"), Some((Some(1), Command::TimeSettings(TimeSettings::None))));
+        assert_eq!(Gtp::parse_line("kgs-time_settings absolute 600"), Some((None, Command::TimeSettings(TimeSettings::Absolute(600.0)))));
+        assert_eq!(Gtp::parse_line("kgs-time_settings byoyomi 600 30 5"), Some((None, Command::TimeSettings(TimeSettings::ByoYomi(600.0, 30.0, 5)))));
+        assert_eq!(Gtp::parse_line("kgs-time_settings canadian 600 300 25"), Some((None, Command::TimeSettings(TimeSettings::Canadian(600.0, 300.0, 25)))));
+        assert_eq!(Gtp::parse_line("kgs-time_settings byoyomi 600"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn time_left() {
+        assert_eq!(Gtp::parse_line("1 time_left b 120 0"), Some((Some(1), Command::TimeLeft(Color::Black, TimeLeft { time: 120.0, stones: 0 }))));
+        assert_eq!(Gtp::parse_line("time_left white 25 3"), Some((None, Command::TimeLeft(Color::White, TimeLeft { time: 25.0, stones: 3 }))));
     }
 
     #[test]
@@ -621,6 +1482,193 @@ mod tests {
         assert_eq!(Gtp::parse_line("known_command genmove"), Some((None, Command::KnownCommand("genmove".to_string()))));
     }
 
+    #[test]
+    fn lz_analyze() {
+        assert_eq!(Gtp::parse_line("1 lz-analyze"), Some((Some(1), Command::LzAnalyze(None, 100))));
+        assert_eq!(Gtp::parse_line("lz-analyze 50"), Some((None, Command::LzAnalyze(None, 50))));
+        assert_eq!(Gtp::parse_line("lz-analyze b 50"), Some((None, Command::LzAnalyze(Some(Color::Black), 50))));
+        assert_eq!(Gtp::parse_line("lz-analyze white interval 25"), Some((None, Command::LzAnalyze(Some(Color::White), 25))));
+        assert_eq!(Gtp::parse_line("lz-analyze x 25"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn to_move() {
+        let mut gtp = Gtp::new();
+
+        assert_eq!(gtp.to_move(), Color::Black);
+        gtp.process(None, Command::Play(Color::Black, Vertex { x: 3, y: 3 }));
+        assert_eq!(gtp.to_move(), Color::White);
+
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::FixedHandicap(2));
+        assert_eq!(gtp.to_move(), Color::White);
+    }
+
+    #[test]
+    fn printsgf() {
+        assert_eq!(Gtp::parse_line("1 printsgf"), Some((Some(1), Command::PrintSgf(None))));
+        assert_eq!(Gtp::parse_line("printsgf game.sgf"), Some((None, Command::PrintSgf(Some("game.sgf".to_string())))));
+    }
+
+    #[test]
+    fn loadsgf() {
+        assert_eq!(Gtp::parse_line("1 loadsgf game.sgf"), Some((Some(1), Command::LoadSgf("game.sgf".to_string(), None))));
+        assert_eq!(Gtp::parse_line("loadsgf game.sgf 12"), Some((None, Command::LoadSgf("game.sgf".to_string(), Some(12)))));
+        assert_eq!(Gtp::parse_line("loadsgf"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn load_sgf() {
+        let path = ::std::env::temp_dir().join("dream_go_load_sgf.sgf");
+        let path = path.to_str().unwrap();
+
+        File::create(path).unwrap()
+            .write_all(b"(;GM[1]FF[4]SZ[9]RU[Chinese]KM[0.5]HA[2]AB[cg][gc];W[ee];B[];W[df]C[Win: 50.0%])")
+            .unwrap();
+
+        let mut gtp = Gtp::new();
+
+        assert_eq!(gtp.load_sgf(path, Some(3)), Ok(()));
+        assert_eq!(gtp.history.len(), 3);
+        assert_eq!(gtp.komi, 0.5);
+        assert_eq!(gtp.history[0].size(), 9);
+        assert_eq!(gtp.history[0].at(2, 2), Some(Color::Black));
+        assert_eq!(gtp.history[0].at(6, 6), Some(Color::Black));
+        assert_eq!(gtp.history[2].at(4, 4), Some(Color::White));
+
+        assert_eq!(gtp.load_sgf(path, None), Ok(()));
+        assert_eq!(gtp.history.len(), 4);
+        assert!(format!("{}", gtp.to_game_tree()).ends_with(";W[ee];B[];W[df])"));
+        assert_eq!(gtp.load_sgf("/does/not/exist.sgf", None), Err("cannot read file"));
+        assert_eq!(gtp.history.len(), 4);
+    }
+
+    #[test]
+    fn load_sgf_mixed_case() {
+        let path = ::std::env::temp_dir().join("Dream_Go_Load_SGF.sgf");
+        let path = path.to_str().unwrap();
+
+        File::create(path).unwrap()
+            .write_all(b"(;GM[1]FF[4]SZ[9];B[ee];W[cc])")
+            .unwrap();
+
+        let mut gtp = Gtp::new();
+
+        match Gtp::parse_line(&format!("LoadSGF {} 2", path)) {
+            Some((None, Command::LoadSgf(ref other, Some(2)))) if other == path => {
+                assert_eq!(gtp.load_sgf(other, Some(2)), Ok(()));
+                assert_eq!(gtp.history.len(), 2);
+            },
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn savetree_loadtree() {
+        assert_eq!(Gtp::parse_line("1 savetree game.tree"), Some((Some(1), Command::SaveTree("game.tree".to_string()))));
+        assert_eq!(Gtp::parse_line("loadtree game.tree"), Some((None, Command::LoadTree("game.tree".to_string()))));
+        assert_eq!(Gtp::parse_line("loadtree"), Some((None, Command::Pass)));
+        assert_eq!(Gtp::parse_line("savetree Games/A.tree"), Some((None, Command::SaveTree("Games/A.tree".to_string()))));
+        assert_eq!(Gtp::parse_line("LOADTREE Games/A.tree"), Some((None, Command::LoadTree("Games/A.tree".to_string()))));
+    }
+
+    #[test]
+    fn loadweights() {
+        assert_eq!(Gtp::parse_line("2 loadweights a.json"), Some((Some(2), Command::LoadWeights("a.json".to_string()))));
+        assert_eq!(Gtp::parse_line("loadweights"), Some((None, Command::Pass)));
+        assert_eq!(Gtp::parse_line("loadweights Models/B.json"), Some((None, Command::LoadWeights("Models/B.json".to_string()))));
+        assert_eq!(Gtp::parse_line("printsgf Games/C.sgf"), Some((None, Command::PrintSgf(Some("Games/C.sgf".to_string())))));
+
+        let mut gtp = Gtp::new();
+
+        match gtp.load_weights("/does/not/exist.json") {
+            Err(LoadError::NotFound(_)) => {},
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn save_load_tree() {
+        let path = ::std::env::temp_dir().join("dream_go_save_load_tree.bin");
+        let path = path.to_str().unwrap();
+        let mut gtp = Gtp::new();
+
+        assert_eq!(gtp.save_tree(path), Err("no search tree"));
+
+        gtp.process(None, Command::BoardSize(9));
+        gtp.process(None, Command::Komi(5.5));
+        gtp.process(None, Command::Play(Color::Black, Vertex { x: 4, y: 4 }));
+        gtp.search_tree = Some(mcts::tree::Node::new(Color::White, vec! [0.0; 362].into_boxed_slice()));
+
+        assert_eq!(gtp.save_tree(path), Ok(()));
+
+        let mut other = Gtp::new();
+
+        assert_eq!(other.load_tree(path), Ok(()));
+        assert_eq!(other.history.len(), 1);
+        assert!(other.history[0] == gtp.history[1]);
+        assert_eq!(other.komi, 5.5);
+        assert_eq!(other.search_tree.as_ref().map(|tree| tree.color), Some(Color::White));
+        assert_eq!(other.load_tree("/does/not/exist.bin"), Err("cannot read file"));
+    }
+
+    #[test]
+    fn load_tree_to_move() {
+        let path = ::std::env::temp_dir().join("dream_go_load_tree_to_move.bin");
+        let path = path.to_str().unwrap();
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::BoardSize(9));
+        gtp.process(None, Command::Play(Color::Black, Vertex { x: 4, y: 4 }));
+        gtp.process(None, Command::Play(Color::White, Vertex { x: 2, y: 2 }));
+        gtp.search_tree = Some(mcts::tree::Node::new(Color::Black, vec! [0.0; 362].into_boxed_slice()));
+
+        assert_eq!(gtp.save_tree(path), Ok(()));
+
+        let mut other = Gtp::new();
+
+        other.process(None, Command::LoadTree(path.to_string()));
+        assert_eq!(other.history.len(), 1);
+        assert_eq!(other.to_move(), Color::Black);
+
+        other.process(None, Command::Play(Color::Black, Vertex { x: 6, y: 6 }));
+        assert_eq!(other.to_move(), Color::White);
+
+        other.process(None, Command::ClearBoard);
+        assert_eq!(other.to_move(), Color::Black);
+    }
+
+    #[test]
+    fn to_game_tree_evaluations() {
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::BoardSize(9));
+        gtp.process(None, Command::Play(Color::Black, Vertex { x: 4, y: 4 }));
+        gtp.evaluations.insert((1, 19 * 4 + 4), ("Win: 52.0%, Nodes: 100".to_string(), "a.json:0123abcd".to_string()));
+
+        let sgf = format!("{}", gtp.to_game_tree());
+
+        assert!(sgf.ends_with(";B[ee]C[Win: 52.0%, Nodes: 100]NN[a.json:0123abcd])"), "{}", sgf);
+    }
+
+    #[test]
+    fn to_game_tree() {
+        let mut gtp = Gtp::new();
+
+        gtp.process(None, Command::BoardSize(9));
+        gtp.process(None, Command::Komi(0.5));
+        gtp.process(None, Command::FixedHandicap(2));
+        gtp.process(None, Command::Play(Color::White, Vertex { x: 4, y: 4 }));
+        gtp.process(None, Command::Play(Color::Black, Vertex { x: 20, y: 20 }));
+        gtp.process(None, Command::Play(Color::White, Vertex { x: 3, y: 3 }));
+
+        let sgf = format!("{}", gtp.to_game_tree());
+
+        assert!(sgf.starts_with("(;GM[1]FF[4]AP["), "{}", sgf);
+        assert!(sgf.ends_with("SZ[9]RU[Chinese]KM[0.5]HA[2]AB[cg][gc];W[ee];B[];W[df])"), "{}", sgf);
+    }
+
     #[test]
     fn showboard() {
         assert_eq!(Gtp::parse_line("1 showboard"), Some((Some(1), Command::ShowBoard)));
@@ -639,6 +1687,41 @@ mod tests {
         assert_eq!(Gtp::parse_line("final_score"), Some((None, Command::FinalScore)));
     }
 
+    #[test]
+    fn final_score_dead_stones() {
+        let mut board = Board::with_size(9);
+
+        for y in 0..9 {
+            board.place(Color::Black, 3, y);
+            board.place(Color::White, 4, y);
+        }
+
+        for &(x, y) in &[(1, 0), (0, 1), (1, 1), (2, 1)] {
+            board.place(Color::Black, x, y);
+        }
+
+        for &(x, y) in &[(5, 0), (7, 0), (5, 1), (6, 1), (7, 1), (8, 1)] {
+            board.place(Color::White, x, y);
+        }
+
+        board.place(Color::White, 1, 4);
+
+        let mut gtp = Gtp::new();
+        gtp.use_network = false;
+        gtp.history = vec! [board];
+        gtp.komi = 0.5;
+
+        assert_eq!(gtp.final_score(), "W+9.5");
+        assert_eq!(gtp.get_final_status()[19 * 4 + 1], Some(Status::Dead));
+    }
+
+    #[test]
+    fn final_status_list() {
+        assert_eq!(Gtp::parse_line("1 final_status_list dead"), Some((Some(1), Command::FinalStatusList(Status::Dead))));
+        assert_eq!(Gtp::parse_line("final_status_list alive"), Some((None, Command::FinalStatusList(Status::Alive))));
+        assert_eq!(Gtp::parse_line("final_status_list seki"), Some((None, Command::FinalStatusList(Status::Seki))));
+    }
+
     #[test]
     fn reg_genmove() {
         assert_eq!(Gtp::parse_line("1 reg_genmove b"), Some((Some(1), Command::RegGenMove(Color::Black))));
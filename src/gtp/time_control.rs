@@ -0,0 +1,214 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use go::Board;
+
+/// The amount of time (in seconds) to keep in reserve for each move to
+/// account for network lag, and the time it takes to stop the search.
+const LAG_BUFFER: f32 = 0.5;
+
+/// The minimum amount of time (in seconds) to search for any move.
+const MIN_TIME: f32 = 0.1;
+
+/// The time settings of a game, all times are given in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeSettings {
+    /// There is no time limit.
+    None,
+
+    /// Each player has the given main time for the entire game.
+    Absolute(f32),
+
+    /// Each player has the given main time, followed by the given number
+    /// of periods of the given length. A period is only used up if a move
+    /// takes longer than the length of the period.
+    ByoYomi(f32, f32, usize),
+
+    /// Each player has the given main time, followed by periods of the
+    /// given length in which they must play the given number of stones.
+    Canadian(f32, f32, usize)
+}
+
+impl TimeSettings {
+    /// Returns the time settings described by the arguments to the GTP
+    /// `time_settings` command.
+    ///
+    /// # Arguments
+    ///
+    /// * `main_time` - the main time
+    /// * `byo_yomi_time` - the length of each byo-yomi period
+    /// * `byo_yomi_stones` - the number of stones to play in each period
+    ///
+    pub fn from_gtp(main_time: f32, byo_yomi_time: f32, byo_yomi_stones: usize) -> TimeSettings {
+        if byo_yomi_time > 0.0 && byo_yomi_stones == 0 {
+            TimeSettings::None
+        } else if byo_yomi_time == 0.0 {
+            TimeSettings::Absolute(main_time)
+        } else {
+            TimeSettings::Canadian(main_time, byo_yomi_time, byo_yomi_stones)
+        }
+    }
+
+    /// Returns the time that each player has at the start of the game.
+    pub fn time_left(&self) -> TimeLeft {
+        match *self {
+            TimeSettings::None => TimeLeft { time: 0.0, stones: 0 },
+            TimeSettings::Absolute(main_time) => TimeLeft { time: main_time, stones: 0 },
+            TimeSettings::ByoYomi(main_time, _, _) => TimeLeft { time: main_time, stones: 0 },
+            TimeSettings::Canadian(main_time, _, _) => TimeLeft { time: main_time, stones: 0 }
+        }
+    }
+
+    /// Returns how long to search for the next move in the given board
+    /// position, or `None` if there is no time limit. The remaining main
+    /// time is divided over the number of moves that are expected to remain
+    /// in the game, which depends on how many empty vertices that remains.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_left` - the remaining time of the player to move
+    /// * `board` - the current board position
+    ///
+    pub fn get_budget(&self, time_left: &TimeLeft, board: &Board) -> Option<Duration> {
+        let size = board.size();
+        let num_empty = (0..size).flat_map(|y| (0..size).map(move |x| (x, y)))
+            .filter(|&(x, y)| board.at(x, y).is_none())
+            .count();
+        let num_moves = ::std::cmp::max(num_empty / 4, 8) as f32;
+        let in_main_time = time_left.stones == 0;
+
+        let seconds = match *self {
+            TimeSettings::None => { return None; },
+            TimeSettings::Absolute(_) => {
+                time_left.time / num_moves
+            },
+            TimeSettings::ByoYomi(_, period_time, periods) => {
+                if in_main_time && periods > 0 {
+                    time_left.time / num_moves + period_time
+                } else if in_main_time {
+                    time_left.time / num_moves
+                } else {
+                    time_left.time
+                }
+            },
+            TimeSettings::Canadian(_, byo_yomi_time, byo_yomi_stones) => {
+                if in_main_time {
+                    time_left.time / num_moves + byo_yomi_time / byo_yomi_stones as f32
+                } else {
+                    time_left.time / time_left.stones as f32
+                }
+            }
+        };
+
+        let seconds = (seconds - LAG_BUFFER).max(MIN_TIME);
+
+        Some(Duration::from_millis((1000.0 * seconds) as u64))
+    }
+}
+
+/// The remaining time of a player, as reported by the GTP `time_left`
+/// command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeLeft {
+    /// The remaining time (in seconds) of the main time, or of the current
+    /// byo-yomi period.
+    pub time: f32,
+
+    /// The number of stones left to play in the current period, or `0` if
+    /// the player is still in their main time.
+    pub stones: usize
+}
+
+impl TimeLeft {
+    /// Subtract the time spent on a move from the remaining main time. This
+    /// keeps the clock roughly correct if the controller does not send
+    /// `time_left` before each move.
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed` - the time spent on the move
+    ///
+    pub fn subtract(&mut self, elapsed: Duration) {
+        if self.stones == 0 {
+            let elapsed = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9;
+
+            self.time = (self.time - elapsed).max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::*;
+    use gtp::time_control::*;
+
+    #[test]
+    fn from_gtp() {
+        assert_eq!(TimeSettings::from_gtp(300.0, 0.0, 0), TimeSettings::Absolute(300.0));
+        assert_eq!(TimeSettings::from_gtp(300.0, 30.0, 0), TimeSettings::None);
+        assert_eq!(TimeSettings::from_gtp(300.0, 30.0, 5), TimeSettings::Canadian(300.0, 30.0, 5));
+    }
+
+    #[test]
+    fn no_time_limit() {
+        let board = Board::new();
+
+        assert_eq!(TimeSettings::None.get_budget(&TimeLeft { time: 0.0, stones: 0 }, &board), None);
+    }
+
+    #[test]
+    fn absolute() {
+        let board = Board::new();
+        let settings = TimeSettings::Absolute(900.0);
+        let opening = settings.get_budget(&settings.time_left(), &board).unwrap();
+        let hurry = settings.get_budget(&TimeLeft { time: 10.0, stones: 0 }, &board).unwrap();
+
+        assert!(opening > Duration::from_secs(5) && opening < Duration::from_secs(15));
+        assert!(hurry < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn byo_yomi() {
+        let board = Board::new();
+        let settings = TimeSettings::ByoYomi(0.0, 30.0, 3);
+        let budget = settings.get_budget(&TimeLeft { time: 30.0, stones: 3 }, &board).unwrap();
+
+        assert!(budget < Duration::from_secs(30));
+        assert!(budget > Duration::from_secs(20));
+    }
+
+    #[test]
+    fn canadian() {
+        let board = Board::new();
+        let settings = TimeSettings::Canadian(0.0, 300.0, 25);
+        let budget = settings.get_budget(&TimeLeft { time: 60.0, stones: 10 }, &board).unwrap();
+
+        assert!(budget < Duration::from_secs(6));
+        assert!(budget > Duration::from_secs(5));
+    }
+
+    #[test]
+    fn subtract() {
+        let mut time_left = TimeLeft { time: 10.0, stones: 0 };
+        time_left.subtract(Duration::from_millis(2500));
+
+        assert_eq!(time_left.time, 7.5);
+
+        time_left.subtract(Duration::from_secs(60));
+
+        assert_eq!(time_left.time, 0.0);
+    }
+}
@@ -33,6 +33,21 @@ pub struct Vertex {
 }
 
 impl Vertex {
+    /// Returns the vertex of the given HW index, where `361` is a passing
+    /// move.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the HW index of the vertex
+    ///
+    pub fn from_index(index: usize) -> Vertex {
+        if index >= 361 {
+            Vertex { x: 19, y: 19 }
+        } else {
+            Vertex { x: index % 19, y: index / 19 }
+        }
+    }
+
     /// Returns if this is a passing move.
     pub fn is_pass(&self) -> bool {
         self.x >= 19 || self.y >= 19
@@ -65,6 +80,10 @@ impl str::FromStr for Vertex {
 
 impl fmt::Display for Vertex {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}{}", LETTERS[self.x], self.y + 1)
+        if self.is_pass() {
+            write!(f, "pass")
+        } else {
+            write!(f, "{}{}", LETTERS[self.x], self.y + 1)
+        }
     }
 }
@@ -23,6 +23,8 @@ extern crate regex;
 #[cfg(test)] extern crate test;
 extern crate time;
 
+pub mod analysis;
+pub mod arena;
 pub mod dataset;
 pub mod go;
 pub mod gtp;
@@ -15,20 +15,111 @@
 extern crate dream_go;
 extern crate time;
 
-use dream_go::{dataset, gtp, nn, mcts};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use dream_go::{analysis, arena, dataset, gtp, nn, mcts};
+use dream_go::mcts::predict::ReloadableService;
 use dream_go::util::config::{self, Procedure};
 
-/// Returns the network weights, panics if it failed to load the weights.
+/// Returns the network weights given by `--weights`, or stored at the default
+/// paths, exits if it failed to load the weights.
 fn load_network() -> nn::Network {
     match nn::Network::new() {
-        Some(network) => network,
-        None => {
-            println!("Could not load network weights!");
+        Ok(network) => network,
+        Err(reason) => {
+            println!("Could not load network weights -- {}", reason);
             ::std::process::exit(1);
         }
     }
 }
 
+/// Periodically check whether the network weights used by the given service
+/// have changed on disk, and if so replace the network. Weights that fail to
+/// load are reported, and then retried at the next check.
+///
+/// # Arguments
+///
+/// * `server` - the service whose network to replace
+/// * `interval` - the time between each check
+///
+fn watch_network(server: Arc<ReloadableService<nn::Network>>, interval: Duration) {
+    let modified = |path: &Path| -> Option<SystemTime> {
+        fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    };
+
+    thread::spawn(move || {
+        let path = server.current().get_evaluator().path().to_path_buf();
+        let mut last_modified = modified(&path);
+
+        loop {
+            thread::sleep(interval);
+
+            let now = modified(&path);
+
+            if now.is_some() && now != last_modified {
+                match nn::Network::from_path(&path) {
+                    Ok(network) => {
+                        eprintln!("Loaded network weights {}", network.name());
+
+                        server.replace(network);
+                        last_modified = now;
+                    },
+                    Err(reason) => {
+                        eprintln!("Could not reload network weights -- {}", reason);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Returns the players described by the given profiles, exits if the profiles
+/// are invalid or if the network weights of a player could not be loaded.
+///
+/// # Arguments
+///
+/// * `profiles` - the profiles of the two players
+///
+fn load_players(profiles: &[String]) -> [arena::Player<nn::Network>; 2] {
+    if profiles.len() != 2 {
+        println!("Usage: ./dream-go --match <n> <profile> <profile>");
+        ::std::process::exit(1);
+    }
+
+    let load = |name: &String| {
+        let profile = match name.parse::<arena::Profile>() {
+            Ok(profile) => profile,
+            Err(_) => {
+                println!("Invalid player profile -- {}", name);
+                ::std::process::exit(1);
+            }
+        };
+
+        let network = match profile.weights {
+            Some(ref path) => match nn::Network::from_path(path) {
+                Ok(network) => network,
+                Err(reason) => {
+                    println!("Could not load network weights from {} -- {}", path, reason);
+                    ::std::process::exit(1);
+                }
+            },
+            None => load_network()
+        };
+
+        arena::Player {
+            name: name.clone(),
+            server: mcts::predict::service(network),
+            profile: profile
+        }
+    };
+
+    [load(&profiles[0]), load(&profiles[1])]
+}
+
 /// Main function.
 fn main() {
     let remaining = config::get_args();
@@ -41,14 +132,25 @@ fn main() {
             println!("  --ex-it               When combined with --dataset perform search on any partial policies");
             println!("  --self-play <n>       Extract a dataset from self-play containing n examples");
             println!("  --policy-play <n>     Extract a dataset from self-play using only the policy network");
+            println!("  --analyze <files...>  Annotate the given SGF files, into <file>.analyzed.sgf");
+            println!("  --match <n> <a> <b>   Play n games between the profiles a and b, and test if b is stronger");
             println!("  --gtp                 Run GTP client (default)");
+            println!("  --ponder              Keep searching during the opponents turn in the GTP client");
             println!("");
             println!("Advanced options:");
             println!("  --num-rollout <n>     The number of rollouts to add to the search tree for every move");
             println!("  --num-games <n>       The number of games to play or extract in parallel");
             println!("  --num-threads <n>     The number of search threads to use in total");
+            println!("  --memory-limit <n>    The maximum amount of memory (in MiB) of each search tree");
             println!("  --num-samples <n>     The number of games to extract from each game record");
             println!("  --batch-size <n>      The number parallel rollouts to perform on the GPU");
+            println!("  --batch-latency <n>   The maximum time (in milliseconds, 1-1000) to wait for a batch to fill up");
+            println!("  --weights <path>      The network weights to use, instead of searching the default paths");
+            println!("  --reload-weights <n>  Check every n seconds whether the network weights have changed during self-play");
+            println!("  --cpu                 Run the neural network on the CPU instead of the GPU");
+            println!("  --transpositions      Share the statistics of transposed positions during search");
+            println!("  --rules <name>        The rules to play under (chinese, japanese, tromp-taylor, new_zealand)");
+            println!("  --komi <k,...>        The komi to play with, or a list of komi to pick from for each game");
         },
 
         Procedure::Extract(ex_it) => {
@@ -70,7 +172,11 @@ fn main() {
         },
 
         Procedure::SelfPlay(n) => {
-            let (receiver, _server) = mcts::self_play(load_network(), n);
+            let (receiver, server) = mcts::self_play(load_network(), n, &*config::KOMI);
+
+            if let Some(interval) = *config::RELOAD_WEIGHTS {
+                watch_network(server, Duration::from_secs(interval));
+            }
 
             for result in receiver.iter().take(n) {
                 println!("{}", result);
@@ -78,13 +184,76 @@ fn main() {
         },
 
         Procedure::PolicyPlay(n) => {
-            let (receiver, _server) = mcts::policy_play(load_network(), n);
+            let (receiver, server) = mcts::policy_play(load_network(), n, &*config::KOMI);
+
+            if let Some(interval) = *config::RELOAD_WEIGHTS {
+                watch_network(server, Duration::from_secs(interval));
+            }
 
             for result in receiver.iter().take(n) {
                 println!("{}", result);
             }
         },
 
+        Procedure::Analyze => {
+            let server = mcts::predict::service(load_network());
+
+            for file in analysis::of(&remaining, &server) {
+                let out_path = file.output_path();
+                let content = file.games.iter()
+                    .map(|game| format!("{}\n", game))
+                    .collect::<String>();
+
+                match fs::write(&out_path, content) {
+                    Ok(()) => println!("{}", out_path.display()),
+                    Err(reason) => {
+                        eprintln!("Could not write {} -- {}", out_path.display(), reason);
+                    }
+                }
+            }
+        },
+
+        Procedure::Match(n) => {
+            let players = load_players(&remaining);
+            let mut sprt = arena::Sprt::new(
+                *config::SPRT_ELO0,
+                *config::SPRT_ELO1,
+                *config::SPRT_ALPHA,
+                *config::SPRT_ALPHA
+            );
+
+            for result in arena::play(&players, n, &*config::KOMI).iter() {
+                println!("{}", result.game);
+
+                // test whether the second player is stronger than the first
+                sprt.add(result.winner.map(|winner| winner == 1));
+
+                let (win_rate, win_lower, win_upper) = sprt.win_rate();
+                let (elo, elo_lower, elo_upper) = sprt.elo();
+                let (llr_lower, llr_upper) = sprt.bounds();
+
+                eprintln!("{} +{} -{} ={} win rate {:.1}% [{:.1}%, {:.1}%] elo {:+.0} [{:+.0}, {:+.0}] llr {:.2} [{:.2}, {:.2}]",
+                    players[1].name,
+                    sprt.wins, sprt.losses, sprt.draws,
+                    100.0 * win_rate, 100.0 * win_lower, 100.0 * win_upper,
+                    elo, elo_lower, elo_upper,
+                    sprt.llr(), llr_lower, llr_upper
+                );
+
+                match sprt.decision() {
+                    arena::Decision::AcceptH0 => {
+                        eprintln!("{} is not stronger than {}", players[1].name, players[0].name);
+                        break
+                    },
+                    arena::Decision::AcceptH1 => {
+                        eprintln!("{} is stronger than {}", players[1].name, players[0].name);
+                        break
+                    },
+                    arena::Decision::Continue => {}
+                }
+            }
+        },
+
         Procedure::Gtp => {
             gtp::run()
         }
@@ -0,0 +1,212 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::{thread_rng, Rng};
+
+use nn::{self, Network, Type, TYPE, Workspace};
+use util::array::*;
+use util::singleton::*;
+use util::types::*;
+
+/// Something that can compute the value and policy of a batch of features,
+/// such as a neural network.
+pub trait Evaluator: Send + Sync {
+    /// Returns the value and policy for each of the given features. The value
+    /// should be in the range `[-1, +1]` from the point of view of the current
+    /// player, and each policy should contain `362` elements that sum to one.
+    ///
+    /// # Arguments
+    ///
+    /// * `features_list` - the features to evaluate
+    ///
+    fn evaluate(&self, features_list: Vec<Array>) -> (Vec<Singleton>, Vec<Array>);
+
+    /// Returns a name that identifies this evaluator, which is recorded in
+    /// the SGF files of the games it played.
+    fn name(&self) -> String;
+}
+
+/// Run the `nn::forward` function for the given features and wrap the
+/// results into `Array` elements.
+///
+/// # Arguments
+///
+/// * `workspace` -
+/// * `features_list` -
+///
+fn forward<T, R>(
+    workspace: &mut Workspace,
+    features_list: Vec<Array>
+) -> (Vec<Singleton>, Vec<Array>)
+    where T: From<f32> + Clone, f32: From<T>,
+          R: From<f32> + Clone, Box<[R]>: Into<Array>,
+          Array: Into<Box<[T]>> + From<Box<[R]>>,
+          Singleton: From<R>,
+{
+    let (value_list, policy_list) = nn::forward::<T, R>(
+        workspace,
+        &features_list.into_iter()
+            .map(|feature| Array::into(feature))
+            .collect()
+    );
+
+    // wrap the results in `Array` so that we can avoid having to pass
+    // generics everywhere
+    let value_list = value_list.into_iter()
+        .map(|value| Singleton::from(value))
+        .collect();
+    let policy_list = policy_list.into_iter()
+        .map(|policy| Array::from(policy))
+        .collect();
+
+    (value_list, policy_list)
+}
+
+impl Evaluator for Network {
+    fn evaluate(&self, features_list: Vec<Array>) -> (Vec<Singleton>, Vec<Array>) {
+        let mut workspace = self.get_workspace(features_list.len());
+
+        match *TYPE {
+            Type::Int8 => forward::<q8, f32>(&mut workspace, features_list),
+            Type::Half => forward::<f16, f16>(&mut workspace, features_list),
+            Type::Single => forward::<f32, f32>(&mut workspace, features_list)
+        }
+    }
+
+    fn name(&self) -> String {
+        Network::name(self)
+    }
+}
+
+/// An evaluator that ignores the features and returns a uniformly random
+/// value and policy.
+pub struct RandomEvaluator;
+
+impl Evaluator for RandomEvaluator {
+    fn evaluate(&self, features_list: Vec<Array>) -> (Vec<Singleton>, Vec<Array>) {
+        let mut rng = thread_rng();
+        let mut value_list = Vec::with_capacity(features_list.len());
+        let mut policy_list = Vec::with_capacity(features_list.len());
+
+        for _ in features_list.iter() {
+            let mut policy = (0..362).map(|_| rng.next_f32()).collect::<Vec<f32>>();
+            let policy_sum: f32 = policy.iter().sum();
+
+            for p in policy.iter_mut() {
+                *p /= policy_sum;
+            }
+
+            value_list.push(Singleton::from(2.0 * rng.next_f32() - 1.0));
+            policy_list.push(Array::from(policy.into_boxed_slice()));
+        }
+
+        (value_list, policy_list)
+    }
+
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+}
+
+/// An evaluator that ignores the features and always returns the same value
+/// and policy.
+pub struct ConstantEvaluator {
+    value: f32,
+    policy: Box<[f32]>
+}
+
+impl ConstantEvaluator {
+    /// Returns an evaluator that always returns the given value and policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to return, in the range `[-1, +1]`
+    /// * `policy` - the policy to return, must contain `362` elements
+    ///
+    pub fn new(value: f32, policy: Box<[f32]>) -> ConstantEvaluator {
+        assert!(-1.0 <= value && value <= 1.0);
+        assert_eq!(policy.len(), 362);
+
+        ConstantEvaluator {
+            value: value,
+            policy: policy
+        }
+    }
+
+    /// Returns an evaluator that always returns the given value and a uniform
+    /// policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to return, in the range `[-1, +1]`
+    ///
+    pub fn uniform(value: f32) -> ConstantEvaluator {
+        ConstantEvaluator::new(value, vec! [1.0 / 362.0; 362].into_boxed_slice())
+    }
+}
+
+impl Evaluator for ConstantEvaluator {
+    fn evaluate(&self, features_list: Vec<Array>) -> (Vec<Singleton>, Vec<Array>) {
+        let value_list = features_list.iter()
+            .map(|_| Singleton::from(self.value))
+            .collect();
+        let policy_list = features_list.iter()
+            .map(|_| Array::from(self.policy.clone()))
+            .collect();
+
+        (value_list, policy_list)
+    }
+
+    fn name(&self) -> String {
+        format!("constant:{}", self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mcts::evaluator::*;
+
+    #[test]
+    fn random() {
+        let features_list = (0..4).map(|_| Array::from(vec! [0.0f32; 11552].into_boxed_slice())).collect();
+        let (value_list, policy_list) = RandomEvaluator.evaluate(features_list);
+
+        assert_eq!(value_list.len(), 4);
+        assert_eq!(policy_list.len(), 4);
+
+        for (value, policy) in value_list.iter().zip(policy_list.iter()) {
+            let policy_sum: f32 = (0..362).map(|i| policy.get(i)).sum();
+
+            assert!(-1.0 <= value.get() && value.get() <= 1.0, "{}", value.get());
+            assert!((policy_sum - 1.0).abs() < 1e-4, "{}", policy_sum);
+        }
+    }
+
+    #[test]
+    fn constant() {
+        let features_list = (0..2).map(|_| Array::from(vec! [0.0f32; 11552].into_boxed_slice())).collect();
+        let (value_list, policy_list) = ConstantEvaluator::uniform(0.5).evaluate(features_list);
+
+        assert_eq!(value_list.len(), 2);
+        assert_eq!(policy_list.len(), 2);
+
+        for (value, policy) in value_list.iter().zip(policy_list.iter()) {
+            assert_eq!(value.get(), 0.5);
+
+            for i in 0..362 {
+                assert_eq!(policy.get(i), 1.0 / 362.0);
+            }
+        }
+    }
+}
@@ -150,55 +150,64 @@ impl<K: Clone + Hash + Eq, V: Clone> LruCache<K, V> {
     }
 }
 
-/// Retrieve the value and policy from the transposition table, if
-/// the `(board, color)`  tuple is not in the transposition table then
-/// it is computed from the given supplier.
-/// 
-/// # Arguments
-/// 
-/// * `board` - the board to get from the table
-/// * `color` - the color to get from the table
-/// * `supplier` - a function that can be used to compute the value
-///   and policy if they are missing from the table.
-/// 
-pub fn get_or_insert<F>(
-    board: &Board,
-    color: Color,
-    supplier: F
-) -> (f32, Box<[f32]>)
-    where F: FnOnce() -> (f32, Box<[f32]>)
-{
-    lazy_static! {
-        static ref TABLE: [Mutex<LruCache<Board, (f32, Box<[f32]>)>>; 3] = {
-            let empty = LruCache::with_capacity(0);
-            let black = LruCache::with_capacity(MAX_CACHE_SIZE + 1);
-            let white = LruCache::with_capacity(MAX_CACHE_SIZE + 1);
-
-            debug_assert_eq!(Color::Black as usize, 1);
-            debug_assert_eq!(Color::White as usize, 2);
-
-            [Mutex::new(empty), Mutex::new(black), Mutex::new(white)]
-        };
-    }
+/// A transposition table from `(board, color)` tuples to the value and policy
+/// that was computed for them.
+pub struct GlobalCache {
+    tables: [Mutex<LruCache<Board, (f32, Box<[f32]>)>>; 3]
+}
 
-    let table = &TABLE[color as usize];
-    let existing = {
-        let mut table = table.lock().unwrap();
+impl GlobalCache {
+    pub fn new() -> GlobalCache {
+        let empty = LruCache::with_capacity(0);
+        let black = LruCache::with_capacity(MAX_CACHE_SIZE + 1);
+        let white = LruCache::with_capacity(MAX_CACHE_SIZE + 1);
 
-        table.get(board).map(|&(ref value, ref policy)| {
-            (value.clone(), policy.clone())
-        })
-    };
+        debug_assert_eq!(Color::Black as usize, 1);
+        debug_assert_eq!(Color::White as usize, 2);
 
-    if let Some((value, policy)) = existing {
-        (value, policy)
-    } else {
-        let (value, policy) = supplier();
-        let mut table = table.lock().unwrap();
+        GlobalCache {
+            tables: [Mutex::new(empty), Mutex::new(black), Mutex::new(white)]
+        }
+    }
+
+    /// Retrieve the value and policy from the transposition table, if
+    /// the `(board, color)`  tuple is not in the transposition table then
+    /// it is computed from the given supplier.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `board` - the board to get from the table
+    /// * `color` - the color to get from the table
+    /// * `supplier` - a function that can be used to compute the value
+    ///   and policy if they are missing from the table.
+    /// 
+    pub fn get_or_insert<F>(
+        &self,
+        board: &Board,
+        color: Color,
+        supplier: F
+    ) -> (f32, Box<[f32]>)
+        where F: FnOnce() -> (f32, Box<[f32]>)
+    {
+        let table = &self.tables[color as usize];
+        let existing = {
+            let mut table = table.lock().unwrap();
+
+            table.get(board).map(|&(ref value, ref policy)| {
+                (value.clone(), policy.clone())
+            })
+        };
 
-        table.insert(board, (value, policy.clone()));
+        if let Some((value, policy)) = existing {
+            (value, policy)
+        } else {
+            let (value, policy) = supplier();
+            let mut table = table.lock().unwrap();
+
+            table.insert(board, (value, policy.clone()));
 
-        (value, policy)
+            (value, policy)
+        }
     }
 }
 
@@ -13,63 +13,162 @@
 // limitations under the License.
 
 mod dirichlet;
+pub mod evaluator;
 mod global_cache;
 pub mod predict;
 mod spin;
+mod transposition;
 pub mod tree;
 
 use rand::{thread_rng, Rng};
 use std::cell::UnsafeCell;
 use std::fmt;
-use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering};
 use std::sync::mpsc::{Receiver, channel};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use time;
 
-use go::sgf::*;
-use go::{symmetry, Board, Color, CHW, HWC};
-use mcts::predict::{PredictService, PredictGuard, PredictRequest};
-use nn::{Network, Type, TYPE};
+use go::sgf::{self, CGoban, Outcome, Property, SgfCoordinate, WinBy};
+use go::{self, symmetry, Board, Color, Ownership, CHW, HWC};
+use mcts::evaluator::Evaluator;
+use mcts::predict::{PredictGuard, PredictRequest, ReloadableService};
+use mcts::transposition::TranspositionTable;
+use nn::{Type, TYPE};
 use util::array::*;
 use util::b85;
-use util::config;
+use util::binary;
+use util::config::{self, KomiStrategy};
 use util::types::*;
 
-pub enum GameResult {
-    Resign(String, Board, Color, f32),
-    Ended(String, Board)
+/// The result of a game played by `self_play` or `policy_play`, which can
+/// be written as an SGF file.
+pub struct GameResult {
+    /// The moves that were played.
+    moves: Vec<sgf::Node>,
+
+    /// The final board position.
+    board: Board,
+
+    /// The komi of the game.
+    komi: f32,
+
+    /// The outcome of the game.
+    outcome: Outcome,
+
+    /// The settings that were used to play the game.
+    settings: String,
+
+    /// The name of the network that played the game.
+    network: String
 }
 
-impl fmt::Display for GameResult {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+impl GameResult {
+    /// Returns the result of a game that ended because one player resigned.
+    ///
+    /// # Arguments
+    ///
+    /// * `moves` - the moves that were played
+    /// * `board` - the final board position
+    /// * `komi` - the komi of the game
+    /// * `winner` - the player that did not resign
+    /// * `settings` - the settings that were used to play the game
+    /// * `network` - the name of the network that played the game
+    ///
+    fn resigned(moves: Vec<sgf::Node>, board: Board, komi: f32, winner: Color, settings: String, network: String) -> GameResult {
+        GameResult {
+            moves: moves,
+            board: board,
+            komi: komi,
+            outcome: Outcome::Win(winner, WinBy::Resign),
+            settings: settings,
+            network: network
+        }
+    }
+
+    /// Returns the result of a game that was played until the end, and which
+    /// is scored according to the final board position.
+    ///
+    /// # Arguments
+    ///
+    /// * `moves` - the moves that were played
+    /// * `board` - the final board position
+    /// * `komi` - the komi of the game
+    /// * `settings` - the settings that were used to play the game
+    /// * `network` - the name of the network that played the game
+    ///
+    fn ended(moves: Vec<sgf::Node>, board: Board, komi: f32, settings: String, network: String) -> GameResult {
+        let (black, white) = board.get_score();
+        let black = black as f32;
+        let white = white as f32 + komi;
+        let outcome = if black > white {
+            Outcome::Win(Color::Black, WinBy::Score(black - white))
+        } else if white > black {
+            Outcome::Win(Color::White, WinBy::Score(white - black))
+        } else {
+            Outcome::Draw
+        };
+
+        GameResult {
+            moves: moves,
+            board: board,
+            komi: komi,
+            outcome: outcome,
+            settings: settings,
+            network: network
+        }
+    }
+
+    /// Returns the SGF game tree of this game, including all of the meta
+    /// data about the game.
+    pub fn to_game_tree(&self) -> sgf::GameTree {
         let now = time::now_utc();
         let iso8601 = time::strftime("%Y-%m-%dT%H:%M:%S%z", &now).unwrap();
+        let engine = format!("{} ({})", env!("CARGO_PKG_NAME"), self.network);
+        let root = sgf::Node {
+            properties: vec! [
+                Property::Unknown("GM".to_string(), vec! ["1".to_string()]),
+                Property::Unknown("FF".to_string(), vec! ["4".to_string()]),
+                Property::Application(format!("{}:{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+                Property::Date(iso8601),
+                Property::Size(self.board.size()),
+                Property::Rules(self.board.rules().to_string()),
+                Property::Komi(self.komi),
+                Property::PlayerBlack(engine.clone()),
+                Property::PlayerWhite(engine),
+                Property::Result(self.outcome.clone()),
+                Property::GameComment(self.settings.clone())
+            ]
+        };
 
-        match *self {
-            GameResult::Resign(ref sgf, _, winner, _) => {
-                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[7.5]RE[{}+Resign]{})", iso8601, winner, sgf)
-            },
-            GameResult::Ended(ref sgf, ref board) => {
-                let (black, white) = board.get_score();
-                let black = black as f32;
-                let white = white as f32 + 7.5;
-                let winner = {
-                    if black > white {
-                        format!("B+{:.1}", black - white)
-                    } else if white > black {
-                        format!("W+{:.1}", white - black)
-                    } else {
-                        format!("0")
-                    }
-                };
-
-                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[7.5]RE[{}]{})", iso8601, winner, sgf)
-            }
+        sgf::GameTree {
+            nodes: ::std::iter::once(root).chain(self.moves.iter().cloned()).collect(),
+            variations: vec! []
         }
     }
 }
 
+impl fmt::Display for GameResult {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{}", self.to_game_tree())
+    }
+}
+
+/// Returns a description of the search settings that are used during
+/// `self_play`, which is recorded in the SGF files.
+fn get_search_settings() -> String {
+    format!("rollouts {} threads {} dirichlet {} temperature {} uct {} rave {}",
+        *config::NUM_ROLLOUT,
+        *config::NUM_THREADS,
+        *config::DIRICHLET_NOISE,
+        *config::TEMPERATURE,
+        *config::UCT_EXP,
+        *config::RAVE_BIAS
+    )
+}
+
 /// Performs a forward pass through the neural network for the given board
 /// position using a random symmetry to increase entropy.
 /// 
@@ -79,7 +178,7 @@ impl fmt::Display for GameResult {
 /// * `board` - the board position
 /// * `color` - the current player
 /// 
-fn forward(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32]>) {
+fn forward<N: Evaluator + 'static>(server: &PredictGuard<N>, board: &Board, color: Color) -> (f32, Box<[f32]>) {
     lazy_static! {
         static ref SYMM: Vec<symmetry::Transform> = vec! [
             symmetry::Transform::Identity,
@@ -93,7 +192,7 @@ fn forward(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32
         ];
     }
 
-    global_cache::get_or_insert(board, color, || {
+    server.get_state().get_cache().get_or_insert(board, color, || {
         // pick a random transformation to apply to the features. This is done
         // to increase the entropy of the game slightly and to ensure the engine
         // learns the game is symmetric (which should help generalize)
@@ -116,7 +215,7 @@ fn forward(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32
         policy[361] = original_policy.get(361);  // copy passing move
 
         for i in 0..361 {
-            let j = t.inverse().apply(i);
+            let j = t.inverse().apply(board.size(), i);
             let (x, y) = (tree::X[j] as usize, tree::Y[j] as usize);
 
             if !board.is_valid(color, x, y) {
@@ -142,7 +241,7 @@ fn forward(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32
             let mut visited = [false; 368];
 
             for i in 0..361 {
-                let j = t.apply(i);
+                let j = t.apply(board.size(), i);
 
                 if i != j && !visited[i] {
                     visited[i] = true;
@@ -186,13 +285,14 @@ fn forward(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32
 /// * `server` -
 /// * `board` -
 /// * `color` -
+/// * `komi` - the komi to add to the score of white
 /// 
-fn score(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32]>) {
+fn score<N: Evaluator + 'static>(server: &PredictGuard<N>, board: &Board, color: Color, komi: f32) -> (f32, Box<[f32]>) {
     let (_, policy) = forward(server, board, color);
     let value = {
         let (black, white) = board.get_score();
         let black = black as f32;
-        let white = white as f32 + 7.5;
+        let white = white as f32 + komi;
         let winner = if black > white { Color::Black } else { Color::White };
 
         if winner == color {
@@ -205,6 +305,97 @@ fn score(server: &PredictGuard, board: &Board, color: Color) -> (f32, Box<[f32]>
     (value, policy)
 }
 
+/// The criteria that determines when a search should stop, the search stops
+/// as soon as any of the criteria has been met. It also determines whether
+/// the search shares the statistics of transposed positions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    /// The number of rollouts that the search tree should contain.
+    pub rollouts: usize,
+
+    /// The maximum amount of time to search for.
+    pub time_limit: Option<Duration>,
+
+    /// Whether to stop once no other move can overtake the most visited
+    /// move within the remaining rollouts (or time).
+    pub early_stop: bool,
+
+    /// The maximum number of bytes that the search tree may use.
+    pub memory_limit: usize,
+
+    /// Whether to share the statistics between every path in the search tree
+    /// that leads to the same position.
+    pub transpositions: bool
+}
+
+impl Default for Budget {
+    fn default() -> Budget {
+        Budget::rollouts(*config::NUM_ROLLOUT)
+    }
+}
+
+impl Budget {
+    /// Returns a budget that stops the search once the search tree contains
+    /// the given number of rollouts. The statistics of transposed positions
+    /// are only shared if `--transpositions` was given.
+    ///
+    /// # Arguments
+    ///
+    /// * `rollouts` - the number of rollouts
+    ///
+    pub fn rollouts(rollouts: usize) -> Budget {
+        Budget {
+            rollouts: rollouts,
+            time_limit: None,
+            early_stop: false,
+            memory_limit: 1024 * 1024 * *config::MEMORY_LIMIT,
+            transpositions: *config::TRANSPOSITIONS
+        }
+    }
+
+    /// Returns this budget with the given time limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_limit` - the maximum amount of time to search for
+    ///
+    pub fn with_time_limit(self, time_limit: Option<Duration>) -> Budget {
+        Budget { time_limit: time_limit, ..self }
+    }
+
+    /// Returns this budget with the given early stopping behaviour.
+    ///
+    /// # Arguments
+    ///
+    /// * `early_stop` - whether to stop once the most visited move cannot be
+    ///   overtaken
+    ///
+    pub fn with_early_stop(self, early_stop: bool) -> Budget {
+        Budget { early_stop: early_stop, ..self }
+    }
+
+    /// Returns this budget with the given memory limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_limit` - the maximum number of bytes of the search tree
+    ///
+    pub fn with_memory_limit(self, memory_limit: usize) -> Budget {
+        Budget { memory_limit: memory_limit, ..self }
+    }
+
+    /// Returns this budget with the given transposition behaviour.
+    ///
+    /// # Arguments
+    ///
+    /// * `transpositions` - whether to share the statistics of transposed
+    ///   positions
+    ///
+    pub fn with_transpositions(self, transpositions: bool) -> Budget {
+        Budget { transpositions: transpositions, ..self }
+    }
+}
+
 /// The shared variables between the master and each worker thread in the `predict` function.
 #[derive(Clone)]
 struct ThreadContext<E: tree::Value + Clone + Send> {
@@ -214,8 +405,35 @@ struct ThreadContext<E: tree::Value + Clone + Send> {
     /// The initial board position at the root the tree.
     starting_point: Board,
 
+    /// The komi to use when scoring finished games.
+    komi: f32,
+
+    /// The statistics that are shared between every path in the tree that
+    /// leads to the same position, or `None` if no statistics are shared.
+    table: Option<Arc<TranspositionTable>>,
+
+    /// The approximate number of bytes used by the tree.
+    memory: Arc<AtomicUsize>,
+
+    /// The number of bytes the tree may use before the search stops.
+    memory_limit: usize,
+
     /// The number of probes that still needs to be done into the tree.
     remaining: Arc<AtomicIsize>,
+
+    /// The time at which the search should stop, even if there are probes
+    /// remaining.
+    deadline: Option<Instant>,
+
+    /// Whether to stop once the most visited move at the root cannot be
+    /// overtaken.
+    early_stop: bool,
+
+    /// The time at which the search started.
+    started: Instant,
+
+    /// The number of probes that needed to be done when the search started.
+    initial: isize
 }
 
 unsafe impl<E: tree::Value + Clone + Send> Send for ThreadContext<E> { }
@@ -234,6 +452,42 @@ fn is_game_over<E: tree::Value>(trace: &tree::NodeTrace<E>) -> bool {
     index == 361 && unsafe { (*node).pass_count >= 1 }
 }
 
+/// Returns true if no move at the root of the search tree of the given context
+/// can overtake the most visited move, with the probes that remains. If the
+/// search has a deadline then the number of probes that remains is the least
+/// of the remaining probes and the number of probes that are expected to
+/// finish before the deadline.
+///
+/// # Arguments
+///
+/// * `context` - the search to check
+///
+fn is_unreachable<E>(context: &ThreadContext<E>) -> bool
+    where E: tree::Value + Clone + Send
+{
+    let remaining = ::std::cmp::max(context.remaining.load(Ordering::Acquire), 0);
+    let remaining = if let Some(deadline) = context.deadline {
+        let now = Instant::now();
+        let elapsed = now.duration_since(context.started);
+        let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+        let done = context.initial - remaining;
+
+        if done <= 0 || elapsed <= 0.0 || now >= deadline {
+            remaining
+        } else {
+            let left = deadline.duration_since(now);
+            let left = left.as_secs() as f64 + left.subsec_nanos() as f64 * 1e-9;
+            let expected = (done as f64 * left / elapsed) as isize;
+
+            ::std::cmp::min(remaining, expected)
+        }
+    } else {
+        remaining
+    };
+
+    unsafe { (*context.root.get()).is_unreachable(remaining as usize) }
+}
+
 /// Worker that probes into the given monte carlo search tree until the context
 /// is exhausted.
 /// 
@@ -242,10 +496,21 @@ fn is_game_over<E: tree::Value>(trace: &tree::NodeTrace<E>) -> bool {
 /// * `context` - 
 /// * `server` - 
 /// 
-fn predict_worker<E>(context: ThreadContext<E>, server: PredictGuard)
-    where E: tree::Value + Clone + Send + 'static
+fn predict_worker<E, N>(context: ThreadContext<E>, server: PredictGuard<N>)
+    where E: tree::Value + Clone + Send + 'static,
+          N: Evaluator + 'static
 {
     while context.remaining.fetch_sub(1, Ordering::SeqCst) > 0 {
+        if context.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            break
+        } else if context.early_stop && is_unreachable(&context) {
+            context.remaining.store(0, Ordering::SeqCst);
+            break
+        } else if context.memory.load(Ordering::Acquire) >= context.memory_limit {
+            context.remaining.store(0, Ordering::SeqCst);
+            break
+        }
+
         loop {
             let mut board = context.starting_point.clone();
             let trace = unsafe { tree::probe::<E>(&mut *context.root.get(), &mut board) };
@@ -254,13 +519,23 @@ fn predict_worker<E>(context: ThreadContext<E>, server: PredictGuard)
                 let &(_, color, _) = trace.last().unwrap();
                 let next_color = color.opposite();
                 let (value, policy) = if is_game_over(&trace) {
-                    score(&server, &board, next_color)
+                    score(&server, &board, next_color, context.komi)
                 } else {
                     forward(&server, &board, next_color)
                 };
 
                 unsafe {
-                    tree::insert::<E>(&trace, next_color, value, policy);
+                    let &(node, _, index) = trace.last().unwrap();
+                    let pass_count = if index == 361 { (*node).pass_count + 1 } else { 0 };
+                    let key = transposition::key(&board, color, index, pass_count);
+
+                    let added = tree::insert::<E>(&trace, next_color, value, policy, key);
+
+                    if let Some(ref table) = context.table {
+                        tree::transpose::<E>(&trace, table, next_color, value);
+                    }
+
+                    context.memory.fetch_add(added, Ordering::AcqRel);
                     break
                 }
             } else {
@@ -270,33 +545,32 @@ fn predict_worker<E>(context: ThreadContext<E>, server: PredictGuard)
     }
 }
 
-/// Predicts the _best_ next move according to the given neural network when applied
-/// to a monte carlo tree search.
+/// Returns the given search tree, or a new search tree if none was given,
+/// with the prior value of the root node filled in.
 /// 
 /// # Arguments
 /// 
 /// * `server` - the server to use during evaluation
-/// * `num_workers` - 
 /// * `starting_tree` - 
 /// * `starting_point` - 
 /// * `starting_color` - 
 /// 
-fn predict_aux<E>(
-    server: &PredictGuard,
-    num_workers: usize,
+fn get_starting_tree<E, N>(
+    server: &PredictGuard<N>,
     starting_tree: Option<tree::Node<E>>,
     starting_point: &Board,
     starting_color: Color
-) -> (f32, usize, tree::Node<E>)
-    where E: tree::Value + Clone + Send + 'static
+) -> tree::Node<E>
+    where E: tree::Value + Clone + Send + 'static,
+          N: Evaluator + 'static
 {
     // if we have a starting tree given, then re-use that tree (after some sanity
     // checks), otherwise we need to query the neural network about what the
     // prior value should be at the root node.
-    let mut starting_tree = if let Some(mut starting_tree) = starting_tree {
+    if let Some(mut starting_tree) = starting_tree {
         assert_eq!(starting_tree.color, starting_color);
 
-        if starting_tree.prior.iter().sum::<f32>() < 1e-4 {
+        if starting_tree.is_empty() {
             // we are missing the prior distribution, this can happend if we
             // fast-forwarded a passing move, but the pass move had not been
             // expanded (since we still need to create the node to record
@@ -304,10 +578,11 @@ fn predict_aux<E>(
             // of consecutive passes).
             let server = server.clone();
             let (_, policy) = forward(&server, starting_point, starting_color);
+            let mut next = tree::Node::new(starting_color, policy);
 
-            for i in 0..362 {
-                starting_tree.prior[i] = policy[i];
-            }
+            next.pass_count = starting_tree.pass_count;
+            next.key = starting_tree.key;
+            starting_tree = next;
         }
 
         starting_tree
@@ -316,7 +591,79 @@ fn predict_aux<E>(
         let (_, mut policy) = forward(&server, starting_point, starting_color);
 
         tree::Node::new(starting_color, policy)
-    };
+    }
+}
+
+/// Start the given number of worker threads that probes into the search tree
+/// of the given context until it is exhausted.
+/// 
+/// # Arguments
+/// 
+/// * `server` - the server to use during evaluation
+/// * `num_workers` - the number of worker threads to start
+/// * `context` - the search tree to probe into
+/// 
+fn spawn_workers<E, N>(
+    server: &PredictGuard<N>,
+    num_workers: usize,
+    context: &ThreadContext<E>
+) -> Vec<JoinHandle<()>>
+    where E: tree::Value + Clone + Send + 'static,
+          N: Evaluator + 'static
+{
+    (0..num_workers).map(|_| {
+        let context = context.clone();
+        let server = server.clone_static();
+
+        thread::spawn(move || predict_worker::<E, N>(context, server))
+    }).collect::<Vec<JoinHandle<()>>>()
+}
+
+/// Wait for the given worker threads to terminate, and then returns the
+/// search tree of the given context.
+/// 
+/// # Arguments
+/// 
+/// * `context` - the search tree that the workers are probing into
+/// * `handles` - the worker threads
+/// 
+fn join_workers<E>(context: ThreadContext<E>, handles: Vec<JoinHandle<()>>) -> tree::Node<E>
+    where E: tree::Value + Clone + Send + 'static
+{
+    // wait for all threads to terminate to avoid any zombie processes
+    for handle in handles.into_iter() { handle.join().unwrap(); }
+
+    assert_eq!(Arc::strong_count(&context.root), 1);
+
+    UnsafeCell::into_inner(Arc::try_unwrap(context.root).ok().expect(""))
+}
+
+/// Predicts the _best_ next move according to the given neural network when applied
+/// to a monte carlo tree search.
+/// 
+/// # Arguments
+/// 
+/// * `server` - the server to use during evaluation
+/// * `num_workers` - 
+/// * `budget` - the criteria that determines when to stop the search
+/// * `starting_tree` - 
+/// * `starting_point` - 
+/// * `starting_color` - 
+/// * `komi` - the komi to use when scoring finished games
+/// 
+fn predict_aux<E, N>(
+    server: &PredictGuard<N>,
+    num_workers: usize,
+    budget: Budget,
+    starting_tree: Option<tree::Node<E>>,
+    starting_point: &Board,
+    starting_color: Color,
+    komi: f32
+) -> (f32, usize, tree::Node<E>)
+    where E: tree::Value + Clone + Send + 'static,
+          N: Evaluator + 'static
+{
+    let mut starting_tree = get_starting_tree(server, starting_tree, starting_point, starting_color);
 
     // add some dirichlet noise to the root node of the search tree in order to increase
     // the entropy of the search and avoid overfitting to the prior value
@@ -324,43 +671,45 @@ fn predict_aux<E>(
 
     // start-up all of the worker threads, and then start listening for requests on the
     // channel we gave each thread.
-    let remaining = if *config::NUM_ROLLOUT > starting_tree.size() {
-        (*config::NUM_ROLLOUT - starting_tree.size()) as isize
+    let remaining = if budget.rollouts > starting_tree.size() {
+        (budget.rollouts - starting_tree.size()) as isize
     } else {
         0
     };
+    let started = Instant::now();
+    let memory = starting_tree.memory_usage();
     let context: ThreadContext<E> = ThreadContext {
         root: Arc::new(UnsafeCell::new(starting_tree)),
         starting_point: starting_point.clone(),
+        komi: komi,
+        table: if budget.transpositions {
+            Some(Arc::new(TranspositionTable::with_capacity(2 * budget.rollouts)))
+        } else {
+            None
+        },
+
+        memory: Arc::new(AtomicUsize::new(memory)),
+        memory_limit: budget.memory_limit,
 
         remaining: Arc::new(AtomicIsize::new(remaining)),
+        deadline: budget.time_limit.map(|time_limit| started + time_limit),
+        early_stop: budget.early_stop,
+        started: started,
+        initial: remaining
     };
 
-    let handles = (0..num_workers).map(|_| {
-        let context = context.clone();
-        let server = server.clone_static();
-
-        thread::spawn(move || predict_worker::<E>(context, server))
-    }).collect::<Vec<JoinHandle<()>>>();
-
-    // wait for all threads to terminate to avoid any zombie processes
-    for handle in handles.into_iter() { handle.join().unwrap(); }
-
-    assert_eq!(Arc::strong_count(&context.root), 1);
-
-    unsafe {
-        let root = UnsafeCell::into_inner(Arc::try_unwrap(context.root).ok().expect(""));
-        let (value, index) = root.best(if starting_point.count() < 8 {
-            *config::TEMPERATURE
-        } else {
-            0.0
-        });
+    let handles = spawn_workers(server, num_workers, &context);
+    let root = join_workers(context, handles);
+    let (value, index) = root.best(if starting_point.count() < 8 {
+        *config::TEMPERATURE
+    } else {
+        0.0
+    });
 
-        #[cfg(feature = "trace-mcts")]
-        eprintln!("{}", tree::to_sgf::<CGoban, E>(&root, starting_point, true));
+    #[cfg(feature = "trace-mcts")]
+    eprintln!("{}", tree::to_sgf::<CGoban, E>(&root, starting_point, komi, true));
 
-        (value, index, root)
-    }
+    (value, index, root)
 }
 
 /// Predicts the _best_ next move according to the given neural network when applied
@@ -370,22 +719,245 @@ fn predict_aux<E>(
 /// 
 /// * `server` - the server to use during evaluation
 /// * `num_workers` - 
+/// * `budget` - the criteria that determines when to stop the search, for
+///   example the number of rollouts or the maximum amount of time
 /// * `starting_tree` -
 /// * `starting_point` -
 /// * `starting_color` -
+/// * `komi` - the komi to use when scoring finished games
 /// 
-pub fn predict<E>(
-    server: &PredictGuard,
+pub fn predict<E, N>(
+    server: &PredictGuard<N>,
     num_workers: Option<usize>,
+    budget: Budget,
     starting_tree: Option<tree::Node<E>>,
     starting_point: &Board,
-    starting_color: Color
+    starting_color: Color,
+    komi: f32
 ) -> (f32, usize, tree::Node<E>)
-    where E: tree::Value + Clone + Send + 'static
+    where E: tree::Value + Clone + Send + 'static,
+          N: Evaluator + 'static
 {
     let num_workers = num_workers.unwrap_or(*config::NUM_THREADS);
 
-    predict_aux::<E>(server, num_workers, starting_tree, starting_point, starting_color)
+    predict_aux::<E, N>(server, num_workers, budget, starting_tree, starting_point, starting_color, komi)
+}
+
+/// The bytes that every search tree file starts with.
+const TREE_MAGIC: &'static [u8; 4] = b"DGST";
+
+/// The version of the search tree file format.
+const TREE_VERSION: u32 = 1;
+
+/// Write the given search tree, together with the board position and komi
+/// that it was searched from, to the given writer.
+///
+/// # Arguments
+///
+/// * `f` - the writer to write to
+/// * `root` - the search tree to write
+/// * `starting_point` - the board position at the root of the search tree
+/// * `komi` - the komi used during the search
+///
+pub fn save_tree<E, W>(f: &mut W, root: &tree::Node<E>, starting_point: &Board, komi: f32) -> io::Result<()>
+    where E: tree::Value,
+          W: io::Write
+{
+    f.write_all(TREE_MAGIC)?;
+    binary::write_u32(f, TREE_VERSION)?;
+    binary::write_f32(f, komi)?;
+    starting_point.write_into(f)?;
+    root.write_into(f)
+}
+
+/// Returns the search tree, the board position and the komi that was
+/// previously written using `save_tree`. The search tree can be passed to
+/// `predict` to continue the search.
+///
+/// # Arguments
+///
+/// * `f` - the reader to read from
+///
+pub fn load_tree<E, R>(f: &mut R) -> io::Result<(tree::Node<E>, Board, f32)>
+    where E: tree::Value,
+          R: io::Read
+{
+    let mut magic = [0; 4];
+
+    f.read_exact(&mut magic)?;
+    if &magic != TREE_MAGIC {
+        return Err(binary::invalid_data("not a search tree"));
+    }
+
+    if binary::read_u32(f)? != TREE_VERSION {
+        return Err(binary::invalid_data("unsupported search tree version"));
+    }
+
+    let komi = binary::read_f32(f)?;
+    let starting_point = Board::read_from(f)?;
+    let root = tree::Node::read_from(f)?;
+
+    Ok((root, starting_point, komi))
+}
+
+/// A search that keeps expanding a search tree in the background, for example
+/// while the opponent is thinking about their move.
+pub struct Ponder<E: tree::Value + Clone + Send> {
+    context: ThreadContext<E>,
+    handles: Vec<JoinHandle<()>>,
+
+    /// The thread that periodically reports on the search tree, if any.
+    reporter: Option<JoinHandle<()>>,
+
+    /// Whether the reporter thread should terminate.
+    is_stopped: Arc<AtomicBool>
+}
+
+impl<E: tree::Value + Clone + Send + 'static> Ponder<E> {
+    /// Start expanding the given search tree in the background, until either
+    /// `stop` is called or the search tree has reached the memory limit.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `server` - the server to use during evaluation
+    /// * `starting_tree` - the search tree to expand
+    /// * `starting_point` - the board position at the root of the search tree
+    /// * `komi` - the komi to use when scoring finished games
+    /// 
+    pub fn start<N>(
+        server: &PredictGuard<N>,
+        starting_tree: tree::Node<E>,
+        starting_point: &Board,
+        komi: f32
+    ) -> Ponder<E>
+        where N: Evaluator + 'static
+    {
+        let starting_color = starting_tree.color;
+
+        Ponder::start_aux(server, Some(starting_tree), starting_point, starting_color, komi)
+    }
+
+    /// Start expanding the given search tree in the background, and call the
+    /// given callback with the search tree every `interval` until `stop` is
+    /// called.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `server` - the server to use during evaluation
+    /// * `starting_tree` - the search tree to expand, or `None` to start a
+    ///   new search tree
+    /// * `starting_point` - the board position at the root of the search tree
+    /// * `starting_color` - the color of the player to move
+    /// * `komi` - the komi to use when scoring finished games
+    /// * `interval` - the time between each call to `callback`
+    /// * `callback` - the function to call with the search tree
+    /// 
+    pub fn analyze<N, F>(
+        server: &PredictGuard<N>,
+        starting_tree: Option<tree::Node<E>>,
+        starting_point: &Board,
+        starting_color: Color,
+        komi: f32,
+        interval: Duration,
+        mut callback: F
+    ) -> Ponder<E>
+        where N: Evaluator + 'static,
+              F: FnMut(&tree::Node<E>) + Send + 'static
+    {
+        let mut ponder = Ponder::start_aux(server, starting_tree, starting_point, starting_color, komi);
+        let context = ponder.context.clone();
+        let is_stopped = ponder.is_stopped.clone();
+
+        ponder.reporter = Some(thread::spawn(move || {
+            let mut next_report = Instant::now() + interval;
+
+            loop {
+                let now = Instant::now();
+
+                if is_stopped.load(Ordering::SeqCst) {
+                    break
+                } else if now < next_report {
+                    thread::park_timeout(next_report - now);
+                } else {
+                    // the workers are still expanding the search tree, so the
+                    // callback must lock any node that it reads from (as
+                    // `tree::to_analyze` does)
+                    callback(unsafe { &*context.root.get() });
+                    next_report = now + interval;
+                }
+            }
+        }));
+
+        ponder
+    }
+
+    /// Start expanding the given search tree in the background, until either
+    /// `stop` is called or the search tree has reached the memory limit.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `server` - the server to use during evaluation
+    /// * `starting_tree` - the search tree to expand
+    /// * `starting_point` - the board position at the root of the search tree
+    /// * `starting_color` - the color of the player to move
+    /// * `komi` - the komi to use when scoring finished games
+    /// 
+    fn start_aux<N>(
+        server: &PredictGuard<N>,
+        starting_tree: Option<tree::Node<E>>,
+        starting_point: &Board,
+        starting_color: Color,
+        komi: f32
+    ) -> Ponder<E>
+        where N: Evaluator + 'static
+    {
+        let starting_tree = get_starting_tree(server, starting_tree, starting_point, starting_color);
+        let remaining = ::std::isize::MAX;
+        let memory = starting_tree.memory_usage();
+        let context: ThreadContext<E> = ThreadContext {
+            root: Arc::new(UnsafeCell::new(starting_tree)),
+            starting_point: starting_point.clone(),
+            komi: komi,
+            table: if *config::TRANSPOSITIONS {
+                // older positions are replaced once the table is full, so it
+                // does not need to fit the entire search tree
+                Some(Arc::new(TranspositionTable::with_capacity(20 * *config::NUM_ROLLOUT)))
+            } else {
+                None
+            },
+
+            memory: Arc::new(AtomicUsize::new(memory)),
+            memory_limit: 1024 * 1024 * *config::MEMORY_LIMIT,
+
+            remaining: Arc::new(AtomicIsize::new(remaining)),
+            deadline: None,
+            early_stop: false,
+            started: Instant::now(),
+            initial: remaining
+        };
+        let handles = spawn_workers(server, *config::NUM_THREADS, &context);
+
+        Ponder {
+            context: context,
+            handles: handles,
+            reporter: None,
+            is_stopped: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    /// Stop expanding the search tree, and returns it once all of the
+    /// background threads has terminated.
+    pub fn stop(self) -> tree::Node<E> {
+        self.context.remaining.store(0, Ordering::SeqCst);
+        self.is_stopped.store(true, Ordering::SeqCst);
+
+        if let Some(reporter) = self.reporter {
+            reporter.thread().unpark();
+            reporter.join().unwrap();
+        }
+
+        join_workers(self.context, self.handles)
+    }
 }
 
 /// Play a game against the engine and return the result of the game.
@@ -394,11 +966,13 @@ pub fn predict<E>(
 /// 
 /// * `server` - the server to use during evaluation
 /// * `num_parallel` - the number of games that are being played in parallel
+/// * `komi` - the komi of the game
 /// 
-fn self_play_one(server: &PredictGuard, num_parallel: &Arc<AtomicUsize>) -> GameResult
+fn self_play_one<N>(server: &PredictGuard<N>, num_parallel: &Arc<AtomicUsize>, komi: f32) -> GameResult
+    where N: Evaluator + 'static
 {
-    let mut board = Board::new();
-    let mut sgf = String::new();
+    let mut board = Board::with_rules(19, *config::RULES);
+    let mut moves = vec! [];
     let mut current = Color::Black;
     let mut pass_count = 0;
     let mut count = 0;
@@ -407,16 +981,23 @@ fn self_play_one(server: &PredictGuard, num_parallel: &Arc<AtomicUsize>) -> Game
     // engine playing pointless capture sequences at the end of the game
     // that does not change the final result.
     let allow_resign = thread_rng().next_f32() < 0.95;
+    let network = server.get_state().get_evaluator().name();
     let mut root = None;
 
+    // the visit distribution of each search is used as the policy target
+    // during training, so never stop a search early
+    let budget = Budget::rollouts(*config::NUM_ROLLOUT);
+
     while count < 722 {
         let num_workers = *config::NUM_THREADS / num_parallel.load(Ordering::Acquire);
-        let (value, index, tree) = predict_aux::<tree::DefaultValue>(
+        let (value, index, tree) = predict_aux::<tree::DefaultValue, N>(
             &server,
             num_workers,
+            budget,
             root,
             &board,
-            current
+            current,
+            komi
         );
 
         debug_assert!(0.0 <= value && value <= 1.0);
@@ -427,34 +1008,44 @@ fn self_play_one(server: &PredictGuard, num_parallel: &Arc<AtomicUsize>) -> Game
         let value_sgf = if current == Color::Black { 2.0 * value - 1.0 } else { -2.0 * value + 1.0 };
 
         if allow_resign && value < 0.05 {  // resign the game if the evaluation looks bad
-            return GameResult::Resign(sgf, board, current.opposite(), -value);
+            return GameResult::resigned(moves, board, komi, current.opposite(), get_search_settings(), network);
         } else if index == 361 {  // passing move
-            sgf += &format!(";{}[]P[{}]V[{}]", current, b85::encode(&policy), value_sgf);
+            moves.push(sgf::Node {
+                properties: vec! [
+                    Property::Move(current, None),
+                    Property::Policy(b85::encode(&policy)),
+                    Property::Value(value_sgf),
+                    Property::Network(network.clone())
+                ]
+            });
             pass_count += 1;
 
             if pass_count >= 2 {
-                return GameResult::Ended(sgf, board)
+                return GameResult::ended(moves, board, komi, get_search_settings(), network)
             }
 
             root = tree::Node::forward(tree, 361);
         } else {
             let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
 
-            sgf += &format!(";{}[{}]P[{}]V[{}]",
-                current,
-                CGoban::to_sgf(x, y),
-                b85::encode(&policy),
-                value_sgf
-            );
+            let mut properties = vec! [
+                Property::Move(current, Some(CGoban::to_point(x, y, board.size()))),
+                Property::Policy(b85::encode(&policy)),
+                Property::Value(value_sgf),
+                Property::Network(network.clone())
+            ];
+
             if prior_index != 361 {
-                sgf += &format!("TR[{}]",
-                    CGoban::to_sgf(
-                        tree::X[prior_index] as usize,
-                        tree::Y[prior_index] as usize
-                    )
+                let prior_point = CGoban::to_point(
+                    tree::X[prior_index] as usize,
+                    tree::Y[prior_index] as usize,
+                    board.size()
                 );
-            };
 
+                properties.push(Property::Unknown("TR".to_string(), vec! [sgf::format_point(prior_point)]));
+            }
+
+            moves.push(sgf::Node { properties: properties });
             pass_count = 0;
             board.place(current, x, y);
             root = tree::Node::forward(tree, index);
@@ -464,19 +1055,24 @@ fn self_play_one(server: &PredictGuard, num_parallel: &Arc<AtomicUsize>) -> Game
         count += 1;
     }
 
-    GameResult::Ended(sgf, board)
+    GameResult::ended(moves, board, komi, get_search_settings(), network)
 }
 
 /// Play games against the engine and return the result of the games
-/// over the channel.
+/// over the channel. The evaluator can be replaced using the returned
+/// service, in which case every game that starts afterwards uses the new
+/// evaluator.
 /// 
 /// # Arguments
 /// 
-/// * `network` - the neural network to use during evaluation
+/// * `evaluator` - the neural network to use during evaluation
 /// * `num_games` - the number of games to generate
+/// * `komi` - the komi to use for each game
 /// 
-pub fn self_play(network: Network, num_games: usize) -> (Receiver<GameResult>, PredictService) {
-    let server = predict::service(network);
+pub fn self_play<N>(evaluator: N, num_games: usize, komi: &KomiStrategy) -> (Receiver<GameResult>, Arc<ReloadableService<N>>)
+    where N: Evaluator + 'static
+{
+    let server = Arc::new(ReloadableService::new(evaluator));
     let (sender, receiver) = channel();
 
     // spawn the worker threads that generate the self-play games
@@ -488,11 +1084,13 @@ pub fn self_play(network: Network, num_games: usize) -> (Receiver<GameResult>, P
         let num_workers = num_workers.clone();
         let processed = processed.clone();
         let sender = sender.clone();
-        let server = server.lock().clone_static();
+        let server = server.clone();
+        let komi = komi.clone();
 
         thread::spawn(move || {
             while processed.fetch_add(1, Ordering::SeqCst) < num_games {
-                let result = self_play_one(&server, &num_workers);
+                let service = server.current();
+                let result = self_play_one(&service.lock(), &num_workers, komi.sample());
 
                 if sender.send(result).is_err() {
                     break
@@ -514,13 +1112,15 @@ pub fn self_play(network: Network, num_games: usize) -> (Receiver<GameResult>, P
 /// # Arguments
 /// 
 /// * `server` - the server to use during evaluation
+/// * `komi` - the komi of the game
 /// 
-fn policy_play_one(server: &PredictGuard) -> GameResult {
-    let mut board = Board::new();
-    let mut sgf = String::new();
+fn policy_play_one<N: Evaluator + 'static>(server: &PredictGuard<N>, komi: f32) -> GameResult {
+    let mut board = Board::with_rules(19, *config::RULES);
+    let mut moves = vec! [];
     let mut current = Color::Black;
     let mut pass_count = 0;
     let mut count = 0;
+    let network = server.get_state().get_evaluator().name();
 
     while pass_count < 2 && count < 722 && !board.is_scoreable() {
         let (_, policy) = forward(&server, &board, current);
@@ -549,17 +1149,24 @@ fn policy_play_one(server: &PredictGuard) -> GameResult {
 
         if let Some(index) = index {
             if index == 361 {  // pass
-                sgf += &format!(";{}[]", current);
+                moves.push(sgf::Node {
+                    properties: vec! [Property::Move(current, None), Property::Network(network.clone())]
+                });
                 pass_count += 1;
             } else {  // normal move
                 let (x, y) = (tree::X[index] as usize, tree::Y[index] as usize);
 
-                sgf += &format!(";{}[{}]", current, CGoban::to_sgf(x, y));
+                moves.push(sgf::Node {
+                    properties: vec! [
+                        Property::Move(current, Some(CGoban::to_point(x, y, board.size()))),
+                        Property::Network(network.clone())
+                    ]
+                });
                 pass_count = 0;
                 board.place(current, x, y);
             }
         } else {  // no valid moves remaining
-            sgf += &format!(";{}[]", current);
+            moves.push(sgf::Node { properties: vec! [Property::Move(current, None)] });
             pass_count += 1;
         }
 
@@ -569,7 +1176,7 @@ fn policy_play_one(server: &PredictGuard) -> GameResult {
     }
 
     // if the receiver has terminated then quit
-    GameResult::Ended(sgf, board)
+    GameResult::ended(moves, board, komi, "policy".to_string(), network)
 }
 
 /// Play games against the engine and return the results of the game over
@@ -579,11 +1186,14 @@ fn policy_play_one(server: &PredictGuard) -> GameResult {
 /// 
 /// # Arguments
 /// 
-/// * `network` - the neural network to use during evaluation
+/// * `evaluator` - the neural network to use during evaluation
 /// * `num_games` - 
+/// * `komi` - the komi to use for each game
 /// 
-pub fn policy_play(network: Network, num_games: usize) -> (Receiver<GameResult>, PredictService) {
-    let server = predict::service(network);
+pub fn policy_play<N>(evaluator: N, num_games: usize, komi: &KomiStrategy) -> (Receiver<GameResult>, Arc<ReloadableService<N>>)
+    where N: Evaluator + 'static
+{
+    let server = Arc::new(ReloadableService::new(evaluator));
     let (sender, receiver) = channel();
 
     // spawn the worker threads that generate the self-play games
@@ -591,11 +1201,13 @@ pub fn policy_play(network: Network, num_games: usize) -> (Receiver<GameResult>,
 
     for _ in 0..num_games {
         let sender = sender.clone();
-        let server = server.lock().clone_static();
+        let server = server.clone();
+        let komi = komi.clone();
 
         thread::spawn(move || {
             loop {
-                let result = policy_play_one(&server);
+                let service = server.current();
+                let result = policy_play_one(&service.lock(), komi.sample());
 
                 if sender.send(result).is_err() {
                     break
@@ -606,3 +1218,422 @@ pub fn policy_play(network: Network, num_games: usize) -> (Receiver<GameResult>,
 
     (receiver, server)
 }
+
+/// Returns a move for the given player during an ownership playout, which
+/// is picked stochastically according to the policy network among the moves
+/// that are worth playing during a playout.
+///
+/// # Arguments
+///
+/// * `server` - the server to use during evaluation
+/// * `board` - the board position
+/// * `color` - the color of the move
+///
+fn playout_move<N: Evaluator + 'static>(server: &PredictGuard<N>, board: &Board, color: Color) -> Option<usize> {
+    let (_, policy) = forward(server, board, color);
+    let candidates = (0..361)
+        .filter(|&i| policy[i].is_finite())
+        .filter(|&i| board.is_playout_move(color, tree::X[i] as usize, tree::Y[i] as usize))
+        .collect::<Vec<usize>>();
+    let policy_sum: f32 = candidates.iter().map(|&i| policy[i]).sum();
+
+    if candidates.is_empty() {
+        None
+    } else if policy_sum <= 1e-6 {
+        thread_rng().choose(&candidates).map(|&i| i)
+    } else {
+        let threshold = policy_sum * thread_rng().next_f32();
+        let mut so_far = 0.0f32;
+
+        for &i in &candidates {
+            so_far += policy[i];
+
+            if so_far >= threshold {
+                return Some(i);
+            }
+        }
+
+        candidates.last().map(|&i| i)
+    }
+}
+
+/// Returns the ownership statistics of the given board position, gathered
+/// from playouts where both players plays according to the policy network.
+/// The playouts are spread over several threads so that the network can
+/// evaluate them in batches.
+///
+/// # Arguments
+///
+/// * `server` - the server to use during evaluation
+/// * `starting_point` - the board position to estimate the ownership of
+/// * `starting_color` - the color of the player to make the next move
+/// * `num_playouts` - the number of playouts to perform
+///
+pub fn ownership<N: Evaluator + 'static>(
+    server: &PredictGuard<N>,
+    starting_point: &Board,
+    starting_color: Color,
+    num_playouts: usize
+) -> Ownership
+{
+    let num_workers = ::std::cmp::min(num_playouts, *config::NUM_THREADS);
+    let remaining = Arc::new(AtomicIsize::new(num_playouts as isize));
+    let ownership = Arc::new(Mutex::new(Ownership::new()));
+    let handles = (0..num_workers).map(|_| {
+        let remaining = remaining.clone();
+        let ownership = ownership.clone();
+        let starting_point = starting_point.clone();
+        let server = server.clone_static();
+
+        thread::spawn(move || {
+            while remaining.fetch_sub(1, Ordering::SeqCst) > 0 {
+                let board = go::playout(&starting_point, starting_color, |board, color| {
+                    playout_move(&server, board, color)
+                });
+
+                ownership.lock().unwrap().add(&board);
+            }
+        })
+    }).collect::<Vec<JoinHandle<()>>>();
+
+    for handle in handles.into_iter() {
+        handle.join().unwrap();
+    }
+
+    match Arc::try_unwrap(ownership) {
+        Ok(ownership) => ownership.into_inner().unwrap(),
+        Err(_) => unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::*;
+    use mcts::*;
+    use mcts::evaluator::*;
+
+    #[test]
+    fn predict_random() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let (value, index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::default(),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+
+        assert!(0.0 <= value && value <= 1.0, "{}", value);
+        assert!(index == 361 || board.is_valid(Color::Black, tree::X[index] as usize, tree::Y[index] as usize));
+        assert_eq!(tree.color, Color::Black);
+    }
+
+    #[test]
+    fn predict_time_limit() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let start = Instant::now();
+        let (_value, index, _tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::default().with_time_limit(Some(Duration::from_millis(10))),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+        let elapsed = start.elapsed();
+
+        // the search may overshoot the time limit by the rollouts that were
+        // already in progress when it expired, and the time it takes to stop
+        // the worker threads
+        assert!(elapsed < Duration::from_millis(10 + 250), "{:?}", elapsed);
+        assert!(index == 361 || board.is_valid(Color::Black, tree::X[index] as usize, tree::Y[index] as usize));
+    }
+
+    #[test]
+    fn predict_rollouts() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let (_value, _index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::rollouts(100),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+
+        assert!(tree.size() >= 100 && tree.size() < 110, "{}", tree.size());
+    }
+
+    #[test]
+    fn predict_transpositions() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let (_value, _index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::rollouts(100).with_transpositions(true),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+
+        assert!(tree.size() >= 100 && tree.size() < 110, "{}", tree.size());
+    }
+
+    #[test]
+    fn predict_early_stop() {
+        let mut policy = vec! [0.0; 362];
+        policy[60] = 1.0;
+
+        let service = predict::service(ConstantEvaluator::new(0.0, policy.into_boxed_slice()));
+        let (_value, index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(1),
+            Budget::rollouts(800).with_early_stop(true),
+            None,
+            &Board::new(),
+            Color::Black,
+            7.5
+        );
+
+        assert_eq!(index, 60);
+        assert!(tree.size() < 800, "{}", tree.size());
+    }
+
+    #[test]
+    fn predict_memory_limit() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let (_value, _index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(1),
+            Budget::rollouts(800).with_memory_limit(64 * 1024),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+
+        assert!(tree.size() < 800, "{}", tree.size());
+        assert!(tree.memory_usage() < 96 * 1024, "{}", tree.memory_usage());
+    }
+
+    #[test]
+    fn save_load_tree() {
+        let service = predict::service(RandomEvaluator);
+        let mut board = Board::new();
+        let mut buf = vec! [];
+
+        board.place(Color::Black, 3, 3);
+
+        let (_value, _index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(1),
+            Budget::rollouts(200),
+            None,
+            &board,
+            Color::White,
+            6.5
+        );
+        let size = tree.size();
+
+        save_tree(&mut buf, &tree, &board, 6.5).unwrap();
+
+        let (other, other_board, komi) = load_tree::<tree::DefaultValue, _>(&mut &buf[..]).unwrap();
+
+        assert_eq!(other.size(), size);
+        assert_eq!(other.color, Color::White);
+        assert!(other_board == board);
+        assert_eq!(komi, 6.5);
+        assert!(load_tree::<tree::DefaultValue, _>(&mut &buf[1..]).is_err());
+
+        // continue the search from where it was saved
+        let (_value, _index, other) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(1),
+            Budget::rollouts(400),
+            Some(other),
+            &other_board,
+            Color::White,
+            komi
+        );
+
+        assert!(other.size() > size, "{} <= {}", other.size(), size);
+    }
+
+    #[test]
+    fn ponder_random() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let (_value, index, tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::default(),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+        let size = tree.size();
+        let ponder = Ponder::start(&service.lock(), tree, &board, 7.5);
+        let tree = ponder.stop();
+
+        assert!(tree.size() >= size);
+        assert!(tree::Node::forward(tree, index).is_some());
+    }
+
+    #[test]
+    fn ponder_until_stopped() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let tree = tree::Node::<tree::DefaultValue>::new(Color::Black, vec! [0.0; 362].into_boxed_slice());
+        let ponder = Ponder::start(&service.lock(), tree, &board, 7.5);
+
+        // the search is only limited by the memory of the search tree, and
+        // not by the number of rollouts
+        thread::sleep(Duration::from_millis(50));
+        assert!(ponder.context.remaining.load(Ordering::SeqCst) > (10 * *config::NUM_ROLLOUT) as isize);
+
+        let tree = ponder.stop();
+
+        assert!(tree.size() > 0);
+    }
+
+    #[test]
+    fn analyze_random() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::new();
+        let num_reports = Arc::new(AtomicUsize::new(0));
+        let num_reports_ = num_reports.clone();
+        let ponder = Ponder::<tree::DefaultValue>::analyze(
+            &service.lock(),
+            None,
+            &board,
+            Color::Black,
+            7.5,
+            Duration::from_millis(10),
+            move |root| {
+                assert_eq!(root.color, Color::Black);
+                num_reports_.fetch_add(1, Ordering::SeqCst);
+            }
+        );
+
+        thread::sleep(Duration::from_millis(100));
+
+        let tree = ponder.stop();
+        let num_reports = num_reports.load(Ordering::SeqCst);
+
+        assert_eq!(tree.color, Color::Black);
+        assert!(num_reports > 0, "{} reports", num_reports);
+    }
+
+    #[test]
+    fn predict_small() {
+        let service = predict::service(RandomEvaluator);
+        let board = Board::with_size(9);
+        let (_value, index, _tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::default(),
+            None,
+            &board,
+            Color::Black,
+            7.5
+        );
+
+        assert!(index == 361 || (tree::X[index] < 9 && tree::Y[index] < 9));
+    }
+
+    #[test]
+    fn predict_pass() {
+        let mut policy = vec! [0.0; 362];
+        policy[361] = 1.0;
+
+        let service = predict::service(ConstantEvaluator::new(0.0, policy.into_boxed_slice()));
+        let (_value, index, _tree) = predict::<tree::DefaultValue, _>(
+            &service.lock(),
+            Some(4),
+            Budget::default(),
+            None,
+            &Board::new(),
+            Color::White,
+            7.5
+        );
+
+        assert_eq!(index, 361);
+    }
+
+    #[test]
+    fn score_komi() {
+        let service = predict::service(RandomEvaluator);
+        let mut board = Board::with_size(5);
+
+        for y in 0..5 {
+            board.place(Color::Black, 1, y);
+            board.place(Color::White, 3, y);
+        }
+
+        assert_eq!(score(&service.lock(), &board, Color::Black, 0.5).0, 0.0);
+        assert_eq!(score(&service.lock(), &board, Color::Black, -0.5).0, 1.0);
+        assert_eq!(score(&service.lock(), &board, Color::White, 0.5).0, 1.0);
+    }
+
+    #[test]
+    fn self_play_constant() {
+        let (receiver, _service) = self_play(ConstantEvaluator::uniform(1.0), 1, &KomiStrategy::Fixed(6.5));
+        let result = format!("{}", receiver.recv().unwrap());
+
+        assert!(result.starts_with("(;GM[1]FF[4]"));
+        assert!(result.contains("KM[6.5]"));
+        assert!(result.contains("NN[constant:1]"));
+    }
+
+    #[test]
+    fn game_result_network() {
+        let result = GameResult::ended(vec! [], Board::new(), 7.5, "".to_string(), "a.json:0123abcd".to_string());
+        let result = format!("{}", result);
+
+        assert!(result.contains("PB[dream_go (a.json:0123abcd)]"), "{}", result);
+        assert!(result.contains("PW[dream_go (a.json:0123abcd)]"), "{}", result);
+    }
+
+    #[test]
+    fn ownership_small() {
+        let service = predict::service(RandomEvaluator);
+        let mut board = Board::with_size(5);
+
+        // both players have three eyes, so there are no moves left to play
+        for y in 0..5 {
+            board.place(Color::Black, 1, y);
+            board.place(Color::White, 2, y);
+            board.place(Color::White, 3, y);
+
+            if y % 2 == 1 {
+                board.place(Color::Black, 0, y);
+                board.place(Color::White, 4, y);
+            }
+        }
+
+        let ownership = ownership(&service.lock(), &board, Color::Black, 4);
+
+        assert_eq!(ownership.get(0), 1.0);
+        assert_eq!(ownership.get(4), -1.0);
+    }
+
+    #[test]
+    fn policy_play_random() {
+        let (receiver, _service) = policy_play(RandomEvaluator, 1, &KomiStrategy::Choice(vec! [0.5, 0.5]));
+        let result = format!("{}", receiver.recv().unwrap());
+
+        assert!(result.starts_with("(;GM[1]FF[4]"));
+        assert!(result.contains("KM[0.5]"));
+    }
+}
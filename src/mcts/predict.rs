@@ -12,19 +12,70 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use parallel::{self, OneSender};
-use nn::{self, Network, Type, TYPE, Workspace};
+use mcts::evaluator::Evaluator;
+use mcts::global_cache::GlobalCache;
+use nn::Network;
 use util::array::*;
+use util::config;
 use util::singleton::*;
-use util::types::*;
 
-pub type PredictGuard<'a> = parallel::ServiceGuard<'a, PredictState>;
-pub type PredictService = parallel::Service<PredictState>;
+pub type PredictGuard<'a, N = Network> = parallel::ServiceGuard<'a, PredictState<N>>;
+pub type PredictService<N = Network> = parallel::Service<PredictState<N>>;
 
-pub fn service(network: Network) -> PredictService {
-    PredictService::new(None, PredictState::new(network))
+pub fn service<N: Evaluator + 'static>(evaluator: N) -> PredictService<N> {
+    PredictService::new(None, PredictState::new(evaluator))
+}
+
+/// A `PredictService` whose evaluator can be replaced while it is in use,
+/// for example when a new network becomes available during self-play.
+///
+/// Replacing the evaluator starts a new service, and the old service keeps
+/// running until everyone that acquired it has dropped it. This ensures that
+/// any queued requests are still answered, and that a game that started with
+/// one evaluator finishes with the same evaluator.
+pub struct ReloadableService<N: Evaluator + 'static = Network> {
+    current: Mutex<Arc<PredictService<N>>>
+}
+
+impl<N: Evaluator + 'static> ReloadableService<N> {
+    /// Returns a service that uses the given evaluator until it is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluator` - the initial evaluator
+    ///
+    pub fn new(evaluator: N) -> ReloadableService<N> {
+        ReloadableService {
+            current: Mutex::new(Arc::new(service(evaluator)))
+        }
+    }
+
+    /// Returns the service of the current evaluator. Every request sent to
+    /// the returned service is answered by the same evaluator, even if it is
+    /// replaced in the meantime.
+    pub fn current(&self) -> Arc<PredictService<N>> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Replace the evaluator that is returned by future calls to `current`.
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluator` - the new evaluator
+    ///
+    pub fn replace(&self, evaluator: N) {
+        let next = Arc::new(service(evaluator));
+        let previous = ::std::mem::replace(&mut *self.current.lock().unwrap(), next);
+
+        // drop the previous service outside of the lock, since it waits for
+        // any pending requests if this was the last reference to it
+        drop(previous);
+    }
 }
 
 pub enum PredictRequest {
@@ -37,86 +88,192 @@ pub enum PredictRequest {
 }
 
 struct PredictShared {
-    /// The features to get the value and policy for.
+    /// The features to get the value and policy for, in the order they were
+    /// received.
     features_list: Vec<Array>,
 
     /// The sender to response to each of the features in `features_list`
     /// over.
     sender_list: Vec<OneSender<Option<(Singleton, Array)>>>,
 
+    /// The time when each of the features in `features_list` was received.
+    received_list: Vec<Instant>,
+
     /// All threads that want to get notified when something changed.
-    waiting_list: Vec<OneSender<Option<(Singleton, Array)>>>
+    waiting_list: Vec<OneSender<Option<(Singleton, Array)>>>,
+
+    /// The time when the most recent evaluation started.
+    started_at: Option<Instant>,
+
+    /// The average time it takes to evaluate a batch.
+    evaluation_time: Option<Duration>,
+
+    /// The number of evaluated batches of each size.
+    batch_sizes: Vec<usize>,
+
+    /// The number of evaluated batches whose oldest request waited the given
+    /// number of milliseconds.
+    queue_latency: Vec<usize>
+}
+
+/// Statistics about the batches that has been evaluated by a `PredictState`,
+/// which can be used to tune the batch size and latency bound.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchStatistics {
+    /// The number of evaluated batches of each size, indexed by the batch
+    /// size.
+    pub batch_sizes: Vec<usize>,
+
+    /// The number of evaluated batches whose oldest request waited for the
+    /// given number of milliseconds, indexed by the number of milliseconds.
+    /// The last element also counts any batch that waited for longer.
+    pub queue_latency: Vec<usize>,
+
+    /// The average time it takes to evaluate a batch, if any batch has been
+    /// evaluated.
+    pub evaluation_time: Option<Duration>
 }
 
-pub struct PredictState {
-    network: Network,
+impl fmt::Display for BatchStatistics {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let total = ::std::cmp::max(1, self.batch_sizes.iter().sum::<usize>()) as f32;
+
+        for (batch_size, &count) in self.batch_sizes.iter().enumerate().skip(1) {
+            writeln!(fmt, "batch size {:3}: {:5.1}% ({})", batch_size, 100.0 * count as f32 / total, count)?;
+        }
+
+        let last = self.queue_latency.len() - 1;
+
+        for (millis, &count) in self.queue_latency.iter().enumerate() {
+            let bound = if millis == last { ">=" } else { "  " };
+
+            writeln!(fmt, "latency {}{:3} ms: {:5.1}% ({})", bound, millis, 100.0 * count as f32 / total, count)?;
+        }
+
+        if let Some(evaluation_time) = self.evaluation_time {
+            write!(fmt, "evaluation time: {:.2} ms", as_millis(evaluation_time))
+        } else {
+            write!(fmt, "evaluation time: -")
+        }
+    }
+}
+
+/// Returns the given duration in milliseconds.
+///
+/// # Arguments
+///
+/// * `duration` - the duration to convert
+///
+fn as_millis(duration: Duration) -> f32 {
+    1e3 * duration.as_secs() as f32 + 1e-6 * duration.subsec_nanos() as f32
+}
+
+pub struct PredictState<N: Evaluator = Network> {
+    evaluator: N,
     shared: Mutex<PredictShared>,
 
+    /// The value and policy of every position that has been evaluated
+    /// recently.
+    cache: GlobalCache,
+
     /// The number of requests that are being processed by the GPU at
     /// this moment
     running_count: AtomicUsize,
+
+    /// The maximum number of features to evaluate in a single batch.
+    max_batch_size: usize,
+
+    /// The maximum amount of time a request should wait in the queue before
+    /// it is evaluated, even if the batch is not full.
+    max_latency: Duration
 }
 
-impl PredictState {
-    pub fn new(network: Network) -> PredictState {
+impl<N: Evaluator> PredictState<N> {
+    /// Returns a state that uses the batch size and latency bound from the
+    /// command-line.
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluator` - the evaluator to use for the predictions
+    ///
+    pub fn new(evaluator: N) -> PredictState<N> {
+        let max_latency = Duration::from_millis(*config::BATCH_LATENCY);
+
+        PredictState::with_batching(evaluator, *config::BATCH_SIZE, max_latency)
+    }
+
+    /// Returns a state that evaluates at most `max_batch_size` features at
+    /// once, and that does not let a request wait for more than (roughly)
+    /// `max_latency` for the batch to fill up.
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluator` - the evaluator to use for the predictions
+    /// * `max_batch_size` - the maximum number of features in a batch
+    /// * `max_latency` - the maximum time a request should wait in the queue
+    ///
+    pub fn with_batching(evaluator: N, max_batch_size: usize, max_latency: Duration) -> PredictState<N> {
+        assert!(max_batch_size > 0);
+
         PredictState {
-            network: network,
+            evaluator: evaluator,
             shared: Mutex::new(PredictShared {
                 features_list: vec! [],
                 sender_list: vec! [],
-                waiting_list: vec! []
+                received_list: vec! [],
+                waiting_list: vec! [],
+                started_at: None,
+                evaluation_time: None,
+                batch_sizes: vec! [0; max_batch_size + 1],
+                queue_latency: vec! [0; 2 * as_millis(max_latency).ceil() as usize + 2]
             }),
 
-            running_count: AtomicUsize::new(0)
+            cache: GlobalCache::new(),
+            running_count: AtomicUsize::new(0),
+            max_batch_size: max_batch_size,
+            max_latency: max_latency
         }
     }
 
-    /// Returns the network used to perform the predictions.
-    pub fn get_network<'a>(&'a self) -> &'a Network {
-        &self.network
+    /// Returns the evaluator used to perform the predictions.
+    pub fn get_evaluator<'a>(&'a self) -> &'a N {
+        &self.evaluator
+    }
+
+    /// Returns the cache of recently evaluated positions.
+    pub(super) fn get_cache<'a>(&'a self) -> &'a GlobalCache {
+        &self.cache
     }
 
-    /// Run the `nn::forward` function for the given features and wrap the
-    /// results into `Array` elements. This version assumes the neural network
-    /// use `f32` weights.
-    /// 
+    /// Returns statistics about the batches that has been evaluated so far.
+    pub fn statistics(&self) -> BatchStatistics {
+        let shared = self.shared.lock().unwrap();
+
+        BatchStatistics {
+            batch_sizes: shared.batch_sizes.clone(),
+            queue_latency: shared.queue_latency.clone(),
+            evaluation_time: shared.evaluation_time
+        }
+    }
+
+    /// Evaluate the `batch_size` oldest features, and returns the lock to
+    /// the shared state once the responses has been sent.
+    ///
     /// # Arguments
-    /// 
-    /// * `workspace` - 
-    /// * `features_list` - 
-    /// 
-    fn forward<T, R>(
-        workspace: &mut Workspace,
-        features_list: Vec<Array>
-    ) -> (Vec<Singleton>, Vec<Array>)
-        where T: From<f32> + Clone,
-              R: From<f32> + Clone, Box<[R]>: Into<Array>,
-              Array: Into<Box<[T]>> + From<Box<[R]>>,
-              Singleton: From<R>,
-    {
-        let (value_list, policy_list) = nn::forward::<T, R>(
-            workspace,
-            &features_list.into_iter()
-                .map(|feature| Array::into(feature))
-                .collect()
-        );
-
-        // wrap the results in `Array` so that we can avoid having to pass
-        // generics everywhere
-        let value_list = value_list.into_iter()
-            .map(|value| Singleton::from(value))
-            .collect();
-        let policy_list = policy_list.into_iter()
-            .map(|policy| Array::from(policy))
-            .collect();
-
-        (value_list, policy_list)
-    }
-
-    fn predict(&self, mut shared: MutexGuard<PredictShared>, batch_size: usize) {
-        let num_items = shared.features_list.len();
-        let features_list = shared.features_list.split_off(num_items - batch_size);
-        let sender_list = shared.sender_list.split_off(num_items - batch_size);
+    ///
+    /// * `shared` - the lock to the shared state
+    /// * `batch_size` - the number of features to evaluate
+    ///
+    fn predict<'a>(&'a self, mut shared: MutexGuard<'a, PredictShared>, batch_size: usize) -> MutexGuard<'a, PredictShared> {
+        let now = Instant::now();
+        let features_list = shared.features_list.drain(0..batch_size).collect::<Vec<_>>();
+        let sender_list = shared.sender_list.drain(0..batch_size).collect::<Vec<_>>();
+        let waited = now - shared.received_list.drain(0..batch_size).next().unwrap();
+        let latency_bucket = ::std::cmp::min(as_millis(waited) as usize, shared.queue_latency.len() - 1);
+
+        shared.batch_sizes[batch_size] += 1;
+        shared.queue_latency[latency_bucket] += 1;
+        shared.started_at = Some(now);
 
         // get ride of our MutexGuard to `shared` to allow for parallel execution
         // while we are busy running the forward pass through the network.
@@ -127,14 +284,17 @@ impl PredictState {
 
         // perform the neural network predictions and then inform all of
         // the receivers
-        let mut workspace = self.network.get_workspace(batch_size);
-        let (value_list, policy_list) = match *TYPE {
-            Type::Int8 => PredictState::forward::<q8, f32>(&mut workspace, features_list),
-            Type::Half => PredictState::forward::<f16, f16>(&mut workspace, features_list),
-            Type::Single => PredictState::forward::<f32, f32>(&mut workspace, features_list)
-        };
+        let (value_list, policy_list) = self.evaluator.evaluate(features_list);
+        let elapsed = now.elapsed();
+
+        // update the (exponential moving) average evaluation time, before
+        // any receiver can observe that this evaluation has finished
+        let mut shared = self.shared.lock().unwrap();
 
-        drop(workspace);
+        shared.evaluation_time = Some(match shared.evaluation_time {
+            None => elapsed,
+            Some(average) => (average * 7 + elapsed) / 8
+        });
 
         // send out our predictions to all of the receivers
         let response_iter = value_list.into_iter().zip(policy_list.into_iter());
@@ -144,7 +304,6 @@ impl PredictState {
         }
 
         // wake up all of the receivers waiting for something to change
-        let mut shared = self.shared.lock().unwrap();
         let num_waiting = shared.waiting_list.len();
 
         for waiting in shared.waiting_list.drain(0..num_waiting) {
@@ -153,45 +312,91 @@ impl PredictState {
 
         // decrease the number of running neural network evaluations
         self.running_count.fetch_sub(1, Ordering::AcqRel);
+
+        shared
     }
 
-    fn check(&self, mut shared: MutexGuard<PredictShared>, has_more: bool) {
+    /// Returns whether the oldest request in a partial batch should be
+    /// evaluated now, instead of waiting for the running evaluation to finish
+    /// so that more requests can be added to the batch. This is the case if
+    /// the running evaluation is expected to finish after the latency bound
+    /// of the oldest request.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared` - the shared state
+    ///
+    fn is_overdue(&self, shared: &PredictShared) -> bool {
+        let now = Instant::now();
+        let waited = now - shared.received_list[0];
+
+        if waited >= self.max_latency {
+            true
+        } else if let (Some(started_at), Some(evaluation_time)) = (shared.started_at, shared.evaluation_time) {
+            let finished_at = started_at + evaluation_time;
+
+            finished_at > now && finished_at - now > self.max_latency - waited
+        } else {
+            false
+        }
+    }
+
+    /// Returns the number of features to evaluate next, if a batch should be
+    /// evaluated at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `shared` - the shared state
+    /// * `has_more` - whether there are more requests immediately available
+    ///
+    fn check(&self, shared: &mut PredictShared, has_more: bool) -> Option<usize> {
         let num_requests = shared.features_list.len();
-        let batch_size = 64;  // FIXME: read from config
 
-        if num_requests >= batch_size {
+        if num_requests >= self.max_batch_size {
             // the batch is full, start an evaluation
             self.running_count.fetch_add(1, Ordering::SeqCst);
-            self.predict(shared, batch_size);
-        } else if has_more {
+
+            Some(self.max_batch_size)
+        } else if num_requests > 0 && self.is_overdue(shared) {
+            // the oldest request has waited for too long (or will have by
+            // the time the running evaluation finish), so evaluate a partial
+            // batch in parallel with the running evaluation
+            self.running_count.fetch_add(1, Ordering::SeqCst);
+
+            Some(num_requests)
+        } else if num_requests > 0 && has_more {
             // wait for the rest of the enqueued requests before evaluating
             // the batch
+            None
         } else if num_requests > 0 && self.running_count.compare_and_swap(0, 1, Ordering::SeqCst) == 0 {
             // nothing is running at the moment, may as well make use of
             // the device so start evaluating a partial batch
-            self.predict(shared, num_requests);
-        } else if num_requests > 0 && !has_more {
-            // immediately evaluate when we hit a barrier in order to:
-            //   1. minimize the latency between request and response
-            //   2. avoid a race condition where a request that arrives
-            //      during an evaluation does not trigger one.
-            self.running_count.fetch_add(1, Ordering::SeqCst);
-            self.predict(shared, num_requests);
-        } else if num_requests == 0 && self.running_count.load(Ordering::Acquire) == 0 {
-            // everything is asleep? probably a race condition between the
-            // pending message being sent and it being received. Just wake
-            // everything up and it should normalize.
-            let num_waiting = shared.waiting_list.len();
-
-            for waiting in shared.waiting_list.drain(0..num_waiting) {
-                waiting.send(None);
+            Some(num_requests)
+        } else if num_requests > 0 {
+            // something is running at the moment, so wait for it to finish
+            // and then evaluate whatever requests has arrived until then.
+            // The running evaluation always check for this once it has
+            // finished, so these requests will not be forgotten.
+            None
+        } else {
+            if self.running_count.load(Ordering::Acquire) == 0 {
+                // everything is asleep? probably a race condition between the
+                // pending message being sent and it being received. Just wake
+                // everything up and it should normalize.
+                let num_waiting = shared.waiting_list.len();
+
+                for waiting in shared.waiting_list.drain(0..num_waiting) {
+                    waiting.send(None);
+                }
             }
+
+            None
         }
     }
 }
 
-impl parallel::ServiceImpl for PredictState {
-    type State = PredictState;
+impl<N: Evaluator + 'static> parallel::ServiceImpl for PredictState<N> {
+    type State = PredictState<N>;
     type Request = PredictRequest;
     type Response = Option<(Singleton, Array)>;
 
@@ -206,12 +411,149 @@ impl parallel::ServiceImpl for PredictState {
             PredictRequest::Ask(features) => {
                 shared.features_list.push(features);
                 shared.sender_list.push(sender);
+                shared.received_list.push(Instant::now());
             },
             PredictRequest::Wait => {
                 shared.waiting_list.push(sender);
             }
         };
 
-        state.check(shared, has_more);
+        // keep evaluating batches until there is nothing more to do, once an
+        // evaluation has finished there are no requests immediately
+        // available to this thread since it has been busy
+        let mut has_more = has_more;
+
+        while let Some(batch_size) = state.check(&mut shared, has_more) {
+            shared = state.predict(shared, batch_size);
+            has_more = false;
+        }
+    }
+
+    fn get_wake_up_interval(state: &Self::State) -> Option<Duration> {
+        Some(state.max_latency)
+    }
+
+    fn wake_up(state: &Self::State) {
+        // the last request to arrive might have been told that there were
+        // more requests available, which another worker thread then took
+        // care of, so make sure the latency bound holds for any requests that
+        // are still waiting for a batch to fill up
+        let mut shared = state.shared.lock().unwrap();
+
+        while let Some(batch_size) = state.check(&mut shared, false) {
+            shared = state.predict(shared, batch_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mcts::evaluator::ConstantEvaluator;
+    use mcts::predict::*;
+
+    #[test]
+    fn reload() {
+        let server = ReloadableService::new(ConstantEvaluator::uniform(1.0));
+        let first = server.current();
+
+        server.replace(ConstantEvaluator::uniform(-1.0));
+
+        let second = server.current();
+
+        assert_eq!(first.get_evaluator().name(), "constant:1");
+        assert_eq!(second.get_evaluator().name(), "constant:-1");
+
+        // the previous service keeps answering requests until it is dropped
+        let (value, _) = first.lock().send(PredictRequest::Ask(features())).unwrap();
+
+        assert_eq!(value.get(), 1.0);
+    }
+
+    /// Returns an empty feature vector.
+    fn features() -> Array {
+        Array::from(vec! [0.0f32; 11552].into_boxed_slice())
+    }
+
+    #[test]
+    fn check_batches() {
+        let state = PredictState::with_batching(ConstantEvaluator::uniform(0.0), 4, Duration::from_secs(60));
+        let mut shared = state.shared.lock().unwrap();
+
+        for _ in 0..2 {
+            shared.features_list.push(features());
+            shared.received_list.push(Instant::now());
+        }
+
+        // wait for more requests if they are available, otherwise start
+        // evaluating a partial batch since nothing is running
+        assert_eq!(state.check(&mut shared, true), None);
+        assert_eq!(state.check(&mut shared, false), Some(2));
+
+        // something is running, so wait for it to finish
+        assert_eq!(state.check(&mut shared, false), None);
+
+        // unless the batch is full
+        for _ in 0..2 {
+            shared.features_list.push(features());
+            shared.received_list.push(Instant::now());
+        }
+
+        assert_eq!(state.check(&mut shared, true), Some(4));
+    }
+
+    #[test]
+    fn check_latency() {
+        let state = PredictState::with_batching(ConstantEvaluator::uniform(0.0), 4, Duration::from_millis(10));
+        let mut shared = state.shared.lock().unwrap();
+
+        shared.features_list.push(features());
+        shared.received_list.push(Instant::now() - Duration::from_millis(20));
+        state.running_count.store(1, Ordering::SeqCst);
+
+        assert_eq!(state.check(&mut shared, true), Some(1));
+
+        // the running evaluation is expected to finish after the latency
+        // bound, so do not wait for it
+        shared.received_list[0] = Instant::now();
+        shared.started_at = Some(Instant::now());
+        shared.evaluation_time = Some(Duration::from_secs(1));
+
+        assert_eq!(state.check(&mut shared, false), Some(1));
+
+        shared.evaluation_time = Some(Duration::from_millis(1));
+
+        assert_eq!(state.check(&mut shared, false), None);
+    }
+
+    #[test]
+    fn wake_up() {
+        let state = PredictState::with_batching(ConstantEvaluator::uniform(1.0), 4, Duration::from_millis(10));
+        let (tx, rx) = parallel::one_channel();
+
+        // a request that was told that more requests were on their way, which
+        // were then taken care of by another worker thread
+        <PredictState<ConstantEvaluator> as parallel::ServiceImpl>::process(&state, PredictRequest::Ask(features()), tx, true);
+
+        assert_eq!(state.shared.lock().unwrap().features_list.len(), 1);
+
+        // so the request is not answered until a worker thread wakes up
+        <PredictState<ConstantEvaluator> as parallel::ServiceImpl>::wake_up(&state);
+
+        let (value, _) = parallel::OneReceiver::recv(rx).unwrap().unwrap();
+
+        assert_eq!(value.get(), 1.0);
+    }
+
+    #[test]
+    fn statistics() {
+        let service = PredictService::new(None, PredictState::with_batching(ConstantEvaluator::uniform(0.0), 4, Duration::from_millis(10)));
+
+        service.lock().send(PredictRequest::Ask(features())).unwrap();
+
+        let statistics = service.statistics();
+
+        assert_eq!(statistics.batch_sizes, vec! [0, 1, 0, 0, 0]);
+        assert_eq!(statistics.queue_latency.iter().sum::<usize>(), 1);
+        assert!(statistics.evaluation_time.is_some());
     }
 }
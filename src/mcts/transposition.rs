@@ -0,0 +1,222 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::UnsafeCell;
+
+use go::{Board, Color};
+use mcts::spin::Mutex;
+use mcts::tree::{X, Y};
+
+/// Random 64-bit integer that is combined with the index of each vertex that
+/// the player to move cannot play at because of ko, before it is mixed into
+/// the key of a position.
+const KO: u64 = 0x1b0e2f9d3a6c4b57;
+
+/// Random 64-bit integer that is multiplied with the number of consecutive
+/// passes and mixed into the key of a position.
+const PASS: u64 = 0x9e3779b97f4a7c15;
+
+/// Returns a 64-bit integer where every bit depends on every bit of the given
+/// integer, using the finalizer of _SplitMix64_.
+///
+/// # Arguments
+///
+/// * `x` - the integer to mix
+///
+fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    x ^ (x >> 31)
+}
+
+/// Returns the key that identifies the given position in a transposition
+/// table. Two positions have the same key if they have the same stones, the
+/// same player to move, the same number of consecutive passes, and the same
+/// vertices are forbidden by ko.
+///
+/// Only the vertices next to the most recent move are checked for ko, which
+/// are the only vertices that can be forbidden by a basic ko.
+///
+/// # Arguments
+///
+/// * `board` - the board position
+/// * `color` - the color of the player that made the most recent move
+/// * `index` - the index of the most recent move
+/// * `pass_count` - the number of consecutive passes
+///
+pub fn key(board: &Board, color: Color, index: usize, pass_count: i32) -> u64 {
+    let mut key = board.zobrist_hash_after(color) ^ (pass_count as u64).wrapping_mul(PASS);
+
+    if index != 361 {
+        let size = board.size();
+        let (x, y) = (X[index] as usize, Y[index] as usize);
+        let neighbours = [
+            (x > 0, index.wrapping_sub(1)),
+            (x + 1 < size, index + 1),
+            (y > 0, index.wrapping_sub(19)),
+            (y + 1 < size, index + 19)
+        ];
+
+        for &(is_valid, other) in neighbours.iter() {
+            if is_valid && board.at(X[other] as usize, Y[other] as usize).is_none() && board._is_ko(color.opposite(), other) {
+                key ^= mix(KO ^ other as u64);
+            }
+        }
+    }
+
+    key
+}
+
+/// The shared statistics of a single position.
+struct Entry {
+    /// Spinlock used to protect the data in this entry during modifications.
+    lock: Mutex,
+
+    /// The key of the position whose statistics are stored in this entry.
+    key: u64,
+
+    /// The total number of times the position has been backed up.
+    count: i32,
+
+    /// The average value of the position, from the perspective of the player
+    /// that made the most recent move.
+    value: f32
+}
+
+/// A fixed size hash table of the statistics of every position in a search
+/// tree, which is shared between every path that leads to the same position.
+/// If two positions maps to the same entry then the most recent one replaces
+/// the other.
+pub struct TranspositionTable {
+    entries: Box<[UnsafeCell<Entry>]>,
+    mask: usize
+}
+
+unsafe impl Send for TranspositionTable { }
+unsafe impl Sync for TranspositionTable { }
+
+impl TranspositionTable {
+    /// Returns an empty transposition table with room for at least the given
+    /// number of positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the number of positions
+    ///
+    pub fn with_capacity(capacity: usize) -> TranspositionTable {
+        let capacity = ::std::cmp::max(capacity, 1024).next_power_of_two();
+
+        TranspositionTable {
+            entries: (0..capacity).map(|_| {
+                UnsafeCell::new(Entry { lock: Mutex::new(), key: 0, count: 0, value: 0.0 })
+            }).collect::<Vec<_>>().into_boxed_slice(),
+            mask: capacity - 1
+        }
+    }
+
+    /// Add the given value to the statistics of the given position, and returns
+    /// the number of values and the average value of the position.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key of the position
+    /// * `value` - the value to add, from the perspective of the player that
+    ///   made the most recent move
+    ///
+    pub fn update(&self, key: u64, value: f32) -> (i32, f32) {
+        let entry = &self.entries[(key as usize) & self.mask];
+
+        unsafe {
+            let entry = &mut *entry.get();
+            let _guard = entry.lock.lock();
+
+            if entry.key != key {
+                entry.key = key;
+                entry.count = 0;
+                entry.value = 0.0;
+            }
+
+            entry.count += 1;
+            entry.value += (value - entry.value) / (entry.count as f32);
+
+            (entry.count, entry.value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use go::{Board, Color};
+    use mcts::transposition::*;
+
+    #[test]
+    fn transpose() {
+        let mut board_1 = Board::new();
+        let mut board_2 = Board::new();
+
+        board_1.place(Color::Black, 3, 3);
+        board_1.place(Color::White, 15, 15);
+        board_1.place(Color::Black, 15, 3);
+
+        board_2.place(Color::Black, 15, 3);
+        board_2.place(Color::White, 15, 15);
+        board_2.place(Color::Black, 3, 3);
+
+        assert_eq!(key(&board_1, Color::Black, 60, 0), key(&board_2, Color::Black, 72, 0));
+        assert!(key(&board_1, Color::Black, 60, 0) != key(&board_1, Color::White, 60, 0));
+        assert!(key(&board_1, Color::Black, 60, 0) != key(&board_1, Color::Black, 60, 1));
+    }
+
+    #[test]
+    fn ko() {
+        // . X O .
+        // X O . O
+        // . X O .
+        let mut board = Board::new();
+
+        board.place(Color::Black, 1, 0);
+        board.place(Color::White, 2, 0);
+        board.place(Color::Black, 0, 1);
+        board.place(Color::White, 3, 1);
+        board.place(Color::Black, 1, 2);
+        board.place(Color::White, 2, 2);
+        board.place(Color::Black, 2, 1);
+        board.place(Color::White, 1, 1);
+
+        assert_eq!(board.at(2, 1), None);
+        assert!(!board.is_valid(Color::Black, 2, 1));
+        assert!(key(&board, Color::White, 20, 0) != board.zobrist_hash_after(Color::White));
+    }
+
+    #[test]
+    fn small_board() {
+        let mut board = Board::with_size(9);
+
+        board.place(Color::Black, 8, 4);
+
+        assert_eq!(key(&board, Color::Black, 84, 0), board.zobrist_hash_after(Color::Black));
+    }
+
+    #[test]
+    fn update() {
+        let table = TranspositionTable::with_capacity(16);
+
+        assert_eq!(table.update(1, 1.0), (1, 1.0));
+        assert_eq!(table.update(1, 0.0), (2, 0.5));
+        assert_eq!(table.update(2, 0.0), (1, 0.0));
+        assert_eq!(table.update(1 + 1024, 1.0), (1, 1.0));
+        assert_eq!(table.update(1, 1.0), (1, 1.0));
+    }
+}
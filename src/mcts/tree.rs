@@ -12,15 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use go::sgf::SgfCoordinate;
+use go::sgf::{self, Property, SgfCoordinate};
 use go::{Board, Color};
 use mcts::spin::Mutex;
+use mcts::transposition::TranspositionTable;
+use util::binary;
 use util::config;
 
 use ordered_float::OrderedFloat;
 use rand::{thread_rng, Rng};
 use std::fmt;
+use std::io;
+use std::mem;
 use std::ptr;
+use std::slice;
 
 lazy_static! {
     /// Mapping from policy index to the `x` coordinate it represents.
@@ -31,6 +36,10 @@ lazy_static! {
 }
 
 pub trait Value {
+    /// Whether this value function uses the all moves as first statistics,
+    /// which are not allocated otherwise.
+    const AMAF: bool;
+
     unsafe fn update<E: Value>(trace: &NodeTrace<E>, color: Color, value: f32);
     fn get<E: Value>(node: &Node<E>, dst: &mut [f32]);
     fn get_ref<E: Value>(node: &Node<E>, dst: &mut [f32]);
@@ -46,6 +55,8 @@ pub trait Value {
 pub struct RAVE;
 
 impl Value for RAVE {
+    const AMAF: bool = true;
+
     #[inline]
     unsafe fn update<E: Value>(trace: &NodeTrace<E>, color: Color, value: f32) {
         PUCT::update::<E>(trace, color, value);
@@ -55,10 +66,12 @@ impl Value for RAVE {
 
             for &(other, other_color, _) in trace.iter().take(i) {
                 if node_color == other_color {
-                    let _guard = (*other).lock.lock();
+                    if let Some(edge) = (*other).edge(index) {
+                        let _guard = (*other).lock.lock();
 
-                    (*other).amaf_count[index] += 1;
-                    (*other).amaf[index] += (value_ - (*other).amaf[index]) / ((*other).amaf_count[index] as f32);
+                        (*other).amaf_count[edge] += 1;
+                        (*other).amaf[edge] += (value_ - (*other).amaf[edge]) / ((*other).amaf_count[edge] as f32);
+                    }
                 }
             }
         }
@@ -77,7 +90,7 @@ impl Value for RAVE {
         let b_sqr = *config::RAVE_BIAS * *config::RAVE_BIAS;
         let uct_exp = *config::UCT_EXP;
 
-        for i in 0..362 {
+        for i in 0..node.vertices.len() {
             let exp_bonus = sqrt_n * ((1 + node.count[i]) as f32).recip();
             let value = if node.count[i] == 0 && node.amaf_count[i] == 0 {
                 node.value[i]
@@ -98,9 +111,10 @@ impl Value for RAVE {
 
     #[inline]
     fn get<E: Value>(node: &Node<E>, dst: &mut [f32]) {
-        if cfg!(target_arch = "x86_64") {
+        if cfg!(target_arch = "x86_64") && !node.vertices.is_empty() {
             let sqrt_n = ((1 + node.total_count) as f32).sqrt();
             let b_sqr = *config::RAVE_BIAS * *config::RAVE_BIAS;
+            let num_blocks = node.vertices.len() / 8;
 
             unsafe {
                 const ONE: f32 = 1.0f32;
@@ -111,7 +125,7 @@ impl Value for RAVE {
                     vbroadcastss ymm13, [rbx]         # ymm13 = 4 * b_sqr
                     vbroadcastss ymm14, [rcx]         # ymm14 = exploration_rate
                     vbroadcastss ymm15, [rdx]         # ymm15 = 1.0
-                    mov rcx, 46                       # loop counter
+                    mov rcx, [r14]                    # loop counter
                     xor rax, rax                      # rax = 0
 
                     1:
@@ -162,7 +176,8 @@ impl Value for RAVE {
                       "{rax}"(&sqrt_n),
                       "{rbx}"(&(4.0f32 * b_sqr)),
                       "{rcx}"(&*config::UCT_EXP),
-                      "{rdx}"(&ONE)
+                      "{rdx}"(&ONE),
+                      "{r14}"(&num_blocks)
                     : "memory", "ymm0", "ymm1", "ymm2", "ymm3", "ymm4", "ymm5", "ymm6",
                       "ymm7", "ymm8", "ymm9", "ymm11", "ymm12", "ymm13", "ymm14", "ymm15"
                     : "intel", "volatile"
@@ -183,15 +198,18 @@ impl Value for RAVE {
 pub struct PUCT;
 
 impl Value for PUCT {
+    const AMAF: bool = false;
+
     #[inline]
     unsafe fn update<E: Value>(trace: &NodeTrace<E>, color: Color, value: f32) {
         for &(node, _, index) in trace.iter() {
             let value_ = if color == (*node).color { value } else { 1.0 - value };
+            let edge = (*node).edge(index).unwrap();
 
             // incremental update of the average value
             let _guard = (*node).lock.lock();
 
-            (*node).value[index] += (value_ - (*node).value[index]) / ((*node).count[index] as f32);
+            (*node).value[edge] += (value_ - (*node).value[edge]) / ((*node).count[edge] as f32);
         }
     }
 
@@ -207,7 +225,7 @@ impl Value for PUCT {
         let sqrt_n = ((1 + node.total_count) as f32).sqrt();
         let uct_exp = *config::UCT_EXP;
 
-        for i in 0..362 {
+        for i in 0..node.vertices.len() {
             let exp_bonus = sqrt_n * ((1 + node.count[i]) as f32).recip();
 
             dst[i] = node.value[i] + node.prior[i] * uct_exp * exp_bonus
@@ -216,8 +234,9 @@ impl Value for PUCT {
 
     #[inline]
     fn get<E: Value>(node: &Node<E>, dst: &mut [f32]) {
-        if cfg!(target_arch = "x86_64") {
+        if cfg!(target_arch = "x86_64") && !node.vertices.is_empty() {
             let sqrt_n = ((1 + node.total_count) as f32).sqrt();
+            let num_blocks = node.vertices.len() / 8;
 
             unsafe {
                 const ONE: i32 = 1;
@@ -226,7 +245,7 @@ impl Value for PUCT {
                     vbroadcastss ymm3, [r12]  # ymm3 = exploration_rate
                     vbroadcastss ymm4, [r13]  # ymm4 = sqrt (total_count + 1)
                     vbroadcastss ymm5, [r14]  # ymm5 = 1
-                    mov rcx, 46               # loop counter
+                    mov rcx, [r15]            # loop counter
 
                     1:
                     vmovups ymm0, [ r8]       # ymm0 = count[i]
@@ -256,7 +275,8 @@ impl Value for PUCT {
                       "{r11}"(dst.as_ptr()),
                       "{r12}"(&*config::UCT_EXP),
                       "{r13}"(&sqrt_n),
-                      "{r14}"(&ONE)
+                      "{r14}"(&ONE),
+                      "{r15}"(&num_blocks)
                     : "memory", "rcx", "ymm0", "ymm1", "ymm2", "ymm3", "ymm4", "ymm5"
                     : "intel", "volatile"
                 );
@@ -350,7 +370,19 @@ fn argmax(array: &[f32]) -> Option<usize> {
     }
 }
 
-/// A monte carlo search tree.
+/// The vertex of the padding edges that are added to make the number of edges
+/// of a node dividable by eight. These edges have a prior value of `-Inf` so
+/// they are never selected.
+const PADDING: u16 = ::std::u16::MAX;
+
+/// The maximum depth of a search tree that `Node::read_from` accepts, which is
+/// the maximum number of moves that are played in a game.
+const MAX_DEPTH: usize = 722;
+
+/// A monte carlo search tree. The edges of each node are stored in a compact
+/// form where only the legal moves (according to the prior value) are
+/// present, and the sub-trees are only allocated once the node is traversed
+/// for the first time.
 pub struct Node<E: Value> {
     /// Spinlock used to protect the data in this node during modifications.
     lock: Mutex,
@@ -361,38 +393,53 @@ pub struct Node<E: Value> {
     /// The number of consecutive passes to reach this node.
     pub pass_count: i32,
 
+    /// The key of the position of this node in the transposition table.
+    pub key: u64,
+
     /// The total number of times any edge has been traversed.
     total_count: i32,
 
+    /// The vertex that each edge represents, in increasing order.
+    vertices: Box<[u16]>,
+
     /// The number of times each edge has been traversed
-    count: [i32; 368],
+    count: Box<[i32]>,
 
     /// The prior value of each edge as indicated by the policy.
-    pub prior: [f32; 368],
+    pub prior: Box<[f32]>,
 
     /// The average value for the sub-tree of each edge.
-    value: [f32; 368],
+    value: Box<[f32]>,
 
-    /// The average value for the all moves as first heuristic of each edge.
-    amaf: [f32; 368],
+    /// The average value for the all moves as first heuristic of each edge,
+    /// this is empty unless the value function uses it.
+    amaf: Box<[f32]>,
 
-    /// The total number of all moves as first updates each edge has received.
-    amaf_count: [i32; 368],
+    /// The total number of all moves as first updates each edge has received,
+    /// this is empty unless the value function uses it.
+    amaf_count: Box<[i32]>,
 
     /// Whether some thread is currently busy (or is done) expanding the given
     /// child. This is used to avoid the same child being expanded multiple
     /// times by different threads.
-    expanding: [bool; 362],
+    expanding: Box<[bool]>,
 
-    /// The sub-tree that each edge points towards.
-    children: [*mut Node<E>; 362]
+    /// The sub-tree that each edge points towards, or null if no edge has
+    /// been traversed yet.
+    children: *mut *mut Node<E>
 }
 
 impl<E: Value> Drop for Node<E> {
     fn drop(&mut self) {
-        for &child in self.children.iter() {
-            if !child.is_null() {
-                unsafe { Box::from_raw(child); }
+        if !self.children.is_null() {
+            unsafe {
+                let children = Box::from_raw(slice::from_raw_parts_mut(self.children, self.vertices.len()));
+
+                for &child in children.iter() {
+                    if !child.is_null() {
+                        Box::from_raw(child);
+                    }
+                }
             }
         }
     }
@@ -410,107 +457,205 @@ impl<E: Value> Node<E> {
     pub fn new(color: Color, prior: Box<[f32]>) -> Node<E> {
         assert_eq!(prior.len(), 362);
 
-        // copy the prior values into an array size that is dividable
-        // by 16 to ensure we can use 256-bit wide SIMD registers.
-        let mut prior_padding = [::std::f32::NEG_INFINITY; 368];
+        // only keep the legal moves, and pad them to a size that is dividable
+        // by 8 to ensure we can use 256-bit wide SIMD registers.
+        let mut vertices = (0..362).filter(|&i| prior[i].is_finite()).map(|i| i as u16).collect::<Vec<u16>>();
+        let mut prior_padding = vertices.iter().map(|&i| prior[i as usize]).collect::<Vec<f32>>();
 
-        for i in 0..362 {
-            prior_padding[i] = prior[i];
+        while vertices.len() % 8 != 0 {
+            vertices.push(PADDING);
+            prior_padding.push(::std::f32::NEG_INFINITY);
         }
 
+        let n = vertices.len();
+        let n_amaf = if E::AMAF { n } else { 0 };
+
         Node {
             lock: Mutex::new(),
             color: color,
             pass_count: 0,
+            key: 0,
             total_count: 0,
-            count: [0; 368],
-            prior: prior_padding,
-            value: [0.0f32; 368],
-            amaf: [0.0f32; 368],
-            amaf_count: [0; 368],
-            expanding: [false; 362],
-            children: [ptr::null_mut(); 362]
+            vertices: vertices.into_boxed_slice(),
+            count: vec! [0; n].into_boxed_slice(),
+            prior: prior_padding.into_boxed_slice(),
+            value: vec! [0.0f32; n].into_boxed_slice(),
+            amaf: vec! [0.0f32; n_amaf].into_boxed_slice(),
+            amaf_count: vec! [0; n_amaf].into_boxed_slice(),
+            expanding: vec! [false; n].into_boxed_slice(),
+            children: ptr::null_mut()
         }
     }
 
+    /// Returns the number of bytes used by a node with the given number of
+    /// edges, assuming the sub-trees of the edges has been allocated.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - the number of edges (including padding)
+    ///
+    fn size_of(n: usize) -> usize {
+        let per_edge = mem::size_of::<u16>()
+            + mem::size_of::<i32>()
+            + mem::size_of::<f32>()
+            + mem::size_of::<f32>()
+            + mem::size_of::<bool>()
+            + mem::size_of::<*mut Node<E>>()
+            + if E::AMAF { mem::size_of::<f32>() + mem::size_of::<i32>() } else { 0 };
+
+        mem::size_of::<Node<E>>() + n * per_edge
+    }
+
     /// Returns the total size of this search tree.
     pub fn size(&self) -> usize {
         self.total_count as usize
     }
 
-    fn as_sgf<S: SgfCoordinate>(&self, fmt: &mut fmt::Formatter, meta: bool) -> fmt::Result {
-        // annotate the top-10 moves to make it easier to navigate for the
-        // user.
-        let mut children = (0..362).collect::<Vec<usize>>();
-        children.sort_by_key(|&i| -self.count[i]);
+    /// Returns the approximate number of bytes used by this search tree.
+    pub fn memory_usage(&self) -> usize {
+        let children = (0..self.vertices.len())
+            .map(|i| self.child(i))
+            .filter(|child| !child.is_null())
+            .map(|child| unsafe { (*child).memory_usage() })
+            .sum::<usize>();
 
-        if meta {
-            for i in 0..10 {
-                let j = children[i];
-
-                if j != 361 && self.count[j] > 0 {
-                    lazy_static! {
-                        static ref LABELS: Vec<&'static str> = vec! [
-                            "1", "2", "3", "4", "5", "6", "7", "8", "9", "10"
-                        ];
-                    }
+        Node::<E>::size_of(self.vertices.len()) + children
+    }
 
-                    write!(fmt, "LB[{}:{}]",
-                        S::to_sgf(X[j] as usize, Y[j] as usize),
-                        LABELS[i]
-                    )?;
-                }
-            }
+    /// Returns the edge that represents the given vertex, if it is a legal
+    /// move.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertex` - the vertex to look for
+    ///
+    #[inline]
+    fn edge(&self, vertex: usize) -> Option<usize> {
+        self.vertices.binary_search(&(vertex as u16)).ok()
+    }
 
-            // mark all valid moves with a triangle (for debugging the symmetry code)
-            /*
-            for i in 0..361 {
-                if self.prior[i].is_finite() {
-                    write!(fmt, "TR[{}]",
-                        S::to_sgf(X[j] as usize, Y[j] as usize),
-                    )?;
-                }
+    /// Returns the sub-tree of the given edge, or null if it has not been
+    /// expanded.
+    ///
+    /// # Arguments
+    ///
+    /// * `edge` - the edge to get the sub-tree of
+    ///
+    #[inline]
+    fn child(&self, edge: usize) -> *mut Node<E> {
+        if self.children.is_null() {
+            ptr::null_mut()
+        } else {
+            unsafe { *self.children.offset(edge as isize) }
+        }
+    }
+
+    /// Returns true if no move can overtake the most visited move, if the
+    /// given number of additional rollouts are performed. This is safe to call
+    /// while other threads are probing into the search tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `remaining` - the number of additional rollouts
+    ///
+    pub fn is_unreachable(&self, remaining: usize) -> bool {
+        let _guard = self.lock.lock();
+        let mut first = 0;
+        let mut second = 0;
+
+        for &count in self.count.iter() {
+            if count > first {
+                second = first;
+                first = count;
+            } else if count > second {
+                second = count;
             }
-            */
         }
 
-        let mut uct = [::std::f32::NEG_INFINITY; 368];
-        E::get::<E>(self, &mut uct);
+        (first - second) as usize > remaining
+    }
 
-        for i in children {
-            // do not output nodes that has not been visited to reduce the
-            // size of the final SGF file.
-            if self.count[i] == 0 {
-                continue;
-            }
+    /// Returns the principal variation of this search tree, which follows the
+    /// most visited move at every level. If no moves have been explored then
+    /// `None` is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - the width and height of the board
+    ///
+    pub fn to_principal_variation<S: SgfCoordinate>(&self, size: usize) -> Option<sgf::GameTree> {
+        let mut parent = sgf::Node { properties: vec! [] };
 
-            write!(fmt, "(")?;
-            write!(fmt, ";{}[{}]",
-                if self.color == Color::Black { "B" } else { "W" },
-                if i == 361 { "tt".to_string() } else { S::to_sgf(X[i] as usize, Y[i] as usize) },
-            )?;
-            write!(fmt, "C[prior {:.4} value {:.4} (visits {} / total {}) amaf {:.4} (visits {}) uct {:.4}]",
-                self.prior[i],
-                self.value[i],
-                self.count[i],
-                self.total_count,
-                self.amaf[i],
-                self.amaf_count[i],
-                uct[i]
-            )?;
+        self.as_sgf::<S>(&mut parent, size, false, true).into_iter().next()
+    }
 
-            unsafe {
-                let child = self.children[i];
+    /// Returns the variations of this search tree, where each variation is
+    /// one of the explored moves. If `meta` is true then labels for the
+    /// most visited moves are added to the given parent node.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - the SGF node that contains the move leading to this
+    ///   search tree
+    /// * `size` - the width and height of the board
+    /// * `meta` - whether to label the most visited moves
+    /// * `greedy` - whether to only include the most visited move
+    ///
+    fn as_sgf<S: SgfCoordinate>(&self, parent: &mut sgf::Node, size: usize, meta: bool, greedy: bool) -> Vec<sgf::GameTree> {
+        // annotate the top-10 moves to make it easier to navigate for the
+        // user.
+        let mut children = (0..self.vertices.len()).collect::<Vec<usize>>();
+        children.sort_by_key(|&i| -self.count[i]);
 
-                if !child.is_null() {
-                    (*child).as_sgf::<S>(fmt, meta)?;
-                }
+        if meta {
+            let labels = children.iter().take(10).enumerate()
+                .map(|(i, &j)| (i, j, self.vertices[j] as usize))
+                .filter(|&(_, j, v)| v != 361 && self.count[j] > 0)
+                .map(|(i, _, v)| format!("{}:{}", S::to_sgf(X[v] as usize, Y[v] as usize, size), i + 1))
+                .collect::<Vec<String>>();
+
+            if !labels.is_empty() {
+                parent.properties.push(Property::Unknown("LB".to_string(), labels));
             }
-
-            write!(fmt, ")")?;
         }
 
-        Ok(())
+        let mut uct = [::std::f32::NEG_INFINITY; 368];
+        E::get::<E>(self, &mut uct);
+
+        children.into_iter()
+            .filter(|&i| self.count[i] > 0)  // do not output nodes that has not been visited
+            .take(if greedy { 1 } else { 362 })
+            .map(|i| {
+                let v = self.vertices[i] as usize;
+                let vertex = if v == 361 {
+                    None
+                } else {
+                    Some(S::to_point(X[v] as usize, Y[v] as usize, size))
+                };
+                let mut node = sgf::Node {
+                    properties: vec! [
+                        Property::Move(self.color, vertex),
+                        Property::Comment(format!("prior {:.4} value {:.4} (visits {} / total {}) amaf {:.4} (visits {}) uct {:.4}",
+                            self.prior[i],
+                            self.value[i],
+                            self.count[i],
+                            self.total_count,
+                            self.amaf.get(i).cloned().unwrap_or(0.0),
+                            self.amaf_count.get(i).cloned().unwrap_or(0),
+                            uct[i]
+                        ))
+                    ]
+                };
+                let child = self.child(i);
+                let variations = if child.is_null() {
+                    vec! []
+                } else {
+                    unsafe { (*child).as_sgf::<S>(&mut node, size, meta, greedy) }
+                };
+
+                sgf::GameTree { nodes: vec! [node], variations: variations }
+            })
+            .collect()
     }
 
     /// Returns the sub-tree that contains the exploration of the given move index.
@@ -520,14 +665,16 @@ impl<E: Value> Node<E> {
     /// * `self` - the search tree to pluck the child from
     /// * `index` - the move to pluck the sub-tree for
     /// 
-    pub fn forward(mut self, index: usize) -> Option<Node<E>> {
-        let child = self.children[index];
+    pub fn forward(self, index: usize) -> Option<Node<E>> {
+        let edge = self.edge(index);
+        let child = edge.map_or(ptr::null_mut(), |edge| self.child(edge));
 
         if child.is_null() {
             if index == 361 {
                 // we need to record that were was a pass so that we have the correct
-                // pass count in the root node.
-                let prior = vec! [0.0f32; 362].into_boxed_slice();
+                // pass count in the root node. We do not know the prior value of the
+                // node, so it does not get any edges until it is known.
+                let prior = vec! [::std::f32::NEG_INFINITY; 362].into_boxed_slice();
                 let mut next = Node::new(self.color.opposite(), prior);
                 next.pass_count = self.pass_count + 1;
 
@@ -537,13 +684,180 @@ impl<E: Value> Node<E> {
             }
         } else {
             Some(unsafe {
-                self.children[index] = ptr::null_mut();
+                *self.children.offset(edge.unwrap() as isize) = ptr::null_mut();
 
-                ptr::read(child)
+                *Box::from_raw(child)
             })
         }
     }
 
+    /// Returns true if this node has no edges, which is the case if the prior
+    /// values of this node are not known.
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Write this search tree, including every sub-tree that has been
+    /// expanded, to the given writer. This should not be called while some
+    /// other thread is searching the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the writer to write to
+    ///
+    pub fn write_into<W: io::Write>(&self, f: &mut W) -> io::Result<()> {
+        binary::write_u8(f, self.color as u8)?;
+        binary::write_u8(f, E::AMAF as u8)?;
+        binary::write_u32(f, self.pass_count as u32)?;
+        binary::write_u64(f, self.key)?;
+        binary::write_u32(f, self.total_count as u32)?;
+        binary::write_u16(f, self.vertices.len() as u16)?;
+
+        for i in 0..self.vertices.len() {
+            binary::write_u16(f, self.vertices[i])?;
+            binary::write_u32(f, self.count[i] as u32)?;
+            binary::write_f32(f, self.prior[i])?;
+            binary::write_f32(f, self.value[i])?;
+
+            if E::AMAF {
+                binary::write_f32(f, self.amaf[i])?;
+                binary::write_u32(f, self.amaf_count[i] as u32)?;
+            }
+        }
+
+        for i in 0..self.vertices.len() {
+            let child = self.child(i);
+
+            if child.is_null() {
+                binary::write_u8(f, 0)?;
+            } else {
+                binary::write_u8(f, 1)?;
+                unsafe { (*child).write_into(f)?; }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a search tree that was previously written using `write_into`.
+    /// Search trees that are deeper than `MAX_DEPTH`, or whose statistics are
+    /// not consistent, are rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the reader to read from
+    ///
+    pub fn read_from<R: io::Read>(f: &mut R) -> io::Result<Node<E>> {
+        Node::read_from_aux(f, 0)
+    }
+
+    /// Returns a sub-tree that was previously written using `write_into`,
+    /// whose root is at the given depth of the search tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the reader to read from
+    /// * `depth` - the depth of the root of the sub-tree
+    ///
+    fn read_from_aux<R: io::Read>(f: &mut R, depth: usize) -> io::Result<Node<E>> {
+        if depth > MAX_DEPTH {
+            return Err(binary::invalid_data("search tree is too deep"));
+        }
+
+        let color = match binary::read_u8(f)? {
+            1 => Color::Black,
+            2 => Color::White,
+            _ => { return Err(binary::invalid_data("unknown color")) }
+        };
+
+        if binary::read_u8(f)? != E::AMAF as u8 {
+            return Err(binary::invalid_data("search tree uses a different value function"));
+        }
+
+        let pass_count = binary::read_u32(f)? as i32;
+        let key = binary::read_u64(f)?;
+        let total_count = binary::read_u32(f)? as i32;
+        let n = binary::read_u16(f)? as usize;
+
+        if n % 8 != 0 || n > 368 {
+            return Err(binary::invalid_data("invalid number of edges"));
+        } else if pass_count < 0 || total_count < 0 {
+            return Err(binary::invalid_data("negative visit count"));
+        }
+
+        let mut node = Node::new(color, vec! [::std::f32::NEG_INFINITY; 362].into_boxed_slice());
+        let n_amaf = if E::AMAF { n } else { 0 };
+
+        node.pass_count = pass_count;
+        node.key = key;
+        node.total_count = total_count;
+        node.vertices = vec! [PADDING; n].into_boxed_slice();
+        node.count = vec! [0; n].into_boxed_slice();
+        node.prior = vec! [::std::f32::NEG_INFINITY; n].into_boxed_slice();
+        node.value = vec! [0.0f32; n].into_boxed_slice();
+        node.amaf = vec! [0.0f32; n_amaf].into_boxed_slice();
+        node.amaf_count = vec! [0; n_amaf].into_boxed_slice();
+        node.expanding = vec! [false; n].into_boxed_slice();
+
+        for i in 0..n {
+            node.vertices[i] = binary::read_u16(f)?;
+            node.count[i] = binary::read_u32(f)? as i32;
+            node.prior[i] = binary::read_f32(f)?;
+            node.value[i] = binary::read_f32(f)?;
+
+            if E::AMAF {
+                node.amaf[i] = binary::read_f32(f)?;
+                node.amaf_count[i] = binary::read_u32(f)? as i32;
+            }
+
+            let is_sorted = i == 0 || node.vertices[i - 1] < node.vertices[i] || node.vertices[i] == PADDING;
+            let is_valid = node.vertices[i] < 362 || node.vertices[i] == PADDING;
+
+            if !is_sorted || !is_valid {
+                return Err(binary::invalid_data("invalid edge"));
+            } else if node.count[i] < 0 || (E::AMAF && node.amaf_count[i] < 0) {
+                return Err(binary::invalid_data("negative visit count"));
+            }
+
+            if node.vertices[i] != PADDING {
+                let is_probability = |x: f32| x.is_finite() && x >= 0.0 && x <= 1.0;
+
+                if !node.prior[i].is_finite() || node.prior[i] < 0.0 {
+                    return Err(binary::invalid_data("invalid prior value"));
+                } else if !is_probability(node.value[i]) || (E::AMAF && !is_probability(node.amaf[i])) {
+                    return Err(binary::invalid_data("invalid value"));
+                }
+            }
+        }
+
+        if node.count.iter().map(|&c| c as i64).sum::<i64>() != total_count as i64 {
+            return Err(binary::invalid_data("total visit count does not match the edges"));
+        }
+
+        if n > 0 {
+            let children: Box<[*mut Node<E>]> = vec! [ptr::null_mut(); n].into_boxed_slice();
+
+            node.children = Box::into_raw(children) as *mut *mut Node<E>;
+        }
+
+        for i in 0..n {
+            if binary::read_u8(f)? != 0 {
+                let child = Node::read_from_aux(f, depth + 1)?;
+
+                if child.color != color.opposite() {
+                    return Err(binary::invalid_data("child has the wrong color"));
+                }
+
+                node.expanding[i] = true;
+                unsafe {
+                    *node.children.offset(i as isize) = Box::into_raw(Box::new(child));
+                }
+            }
+        }
+
+        Ok(node)
+    }
+
     /// Returns the best move according to the current search tree. This is
     /// determined as the most visited child. If the temperature is non-zero
     /// then this process is stochastic, so that the probability that a move
@@ -556,16 +870,23 @@ impl<E: Value> Node<E> {
     ///   the selection is greedy.
     /// 
     pub fn best(&self, temperature: f32) -> (f32, usize) {
-        if temperature <= 9e-2 { // greedy
-            let max_i = (0..362).max_by_key(|&i| self.count[i]).unwrap();
+        let n = self.vertices.len();
+
+        if n == 0 {
+            (0.5, 361)
+        } else if temperature <= 9e-2 { // greedy
+            // `max_by_key` returns the last of any tied moves, so iterate in
+            // reverse to pick the first one, which is the same move that is
+            // listed first by `as_sgf`
+            let max_i = (0..n).rev().max_by_key(|&i| self.count[i]).unwrap();
 
-            (self.value[max_i], max_i)
+            (self.value[max_i], self.vertices[max_i] as usize)
         } else {
             let t = (temperature as f64).recip();
-            let mut s = vec! [0.0; 362];
+            let mut s = vec! [0.0; n];
             let mut s_total = 0.0;
 
-            for i in 0..362 {
+            for i in 0..n {
                 let count = (self.count[i] as f64).powf(t);
 
                 s_total += count;
@@ -575,17 +896,18 @@ impl<E: Value> Node<E> {
             debug_assert!(s_total.is_finite());
 
             let threshold = s_total * thread_rng().next_f64();
-            let max_i = (0..362).filter(|&i| s[i] >= threshold).next().unwrap();
+            let max_i = (0..n).filter(|&i| s[i] >= threshold).next().unwrap();
 
-            (self.value[max_i], max_i)
+            (self.value[max_i], self.vertices[max_i] as usize)
         }
     }
 
     /// Returns the best move according to the prior value of the root node.
     pub fn prior(&self) -> (f32, usize) {
-        let max_i = (0..362).max_by_key(|&i| OrderedFloat(self.prior[i])).unwrap();
-
-        (self.prior[max_i], max_i)
+        match (0..self.vertices.len()).max_by_key(|&i| OrderedFloat(self.prior[i])) {
+            Some(max_i) => (self.prior[max_i], self.vertices[max_i] as usize),
+            None => (0.0, 361)
+        }
     }
 
     /// Returns a vector containing the _correct_ normalized probability that each move
@@ -594,20 +916,26 @@ impl<E: Value> Node<E> {
         let mut s = vec! [T::from(0.0f32); 362];
         let mut s_total = 0.0f32;
 
-        for i in 0..362 {
+        for i in 0..self.vertices.len() {
             s_total += self.count[i] as f32;
         }
 
-        for i in 0..362 {
-            s[i] = T::from(self.count[i] as f32 / s_total);
+        for i in 0..self.vertices.len() {
+            if self.vertices[i] != PADDING {
+                s[self.vertices[i] as usize] = T::from(self.count[i] as f32 / s_total);
+            }
         }
 
         s.into_boxed_slice()
     }
 
-    /// Returns the child with the maximum UCT value, and increase its visit count
+    /// Returns the edge with the maximum UCT value, and increase its visit count
     /// by one.
     fn select<'a>(&'a mut self) -> Option<usize> {
+        if self.vertices.is_empty() {
+            return None;
+        }
+
         // compute all UCB1 values for each node before trying to figure out which
         // to pick to make it possible to do it with SIMD.
         let mut uct = [::std::f32::NEG_INFINITY; 368];
@@ -618,7 +946,7 @@ impl<E: Value> Node<E> {
         // is already expanding the node we want to expand.
         let _guard = self.lock.lock();
         let max_i = argmax(&uct).and_then(|i| {
-            if self.expanding[i] && self.children[i].is_null() {
+            if self.expanding[i] && self.child(i).is_null() {
                 None  // someone else is already expanding this node
             } else {
                 Some(i)
@@ -626,6 +954,12 @@ impl<E: Value> Node<E> {
         });
 
         if let Some(max_i) = max_i {
+            if self.children.is_null() {
+                let children: Box<[*mut Node<E>]> = vec! [ptr::null_mut(); self.vertices.len()].into_boxed_slice();
+
+                self.children = Box::into_raw(children) as *mut *mut Node<E>;
+            }
+
             self.total_count += 1;
             self.count[max_i] += 1;
             self.expanding[max_i] = true;
@@ -654,7 +988,9 @@ pub unsafe fn probe<E>(root: &mut Node<E>, board: &mut Board) -> Option<NodeTrac
     let mut current = root;
 
     loop {
-        if let Some(next_child) = current.select() {
+        if let Some(next_edge) = current.select() {
+            let next_child = current.vertices[next_edge] as usize;
+
             trace.push((current as *mut Node<E>, current.color, next_child));
 
             if next_child != 361 {  // not a passing move
@@ -667,7 +1003,7 @@ pub unsafe fn probe<E>(root: &mut Node<E>, board: &mut Board) -> Option<NodeTrac
             }
 
             //
-            let child = current.children[next_child];
+            let child = current.child(next_edge);
 
             if child.is_null() {
                 break
@@ -678,10 +1014,11 @@ pub unsafe fn probe<E>(root: &mut Node<E>, board: &mut Board) -> Option<NodeTrac
             // undo the entire trace, since we added virtual losses (optimistically)
             // on the way down.
             for (node, _, next_child) in trace.into_iter() {
+                let next_edge = (*node).edge(next_child).unwrap();
                 let _guard = (*node).lock.lock();
 
                 (*node).total_count -= 1;
-                (*node).count[next_child] -= 1;
+                (*node).count[next_edge] -= 1;
             }
 
             return None;
@@ -700,18 +1037,29 @@ pub unsafe fn probe<E>(root: &mut Node<E>, board: &mut Board) -> Option<NodeTrac
 /// * `color` -
 /// * `value` -
 /// * `prior` -
+/// * `key` - the key of the new node in the transposition table
 /// 
-pub unsafe fn insert<E>(trace: &NodeTrace<E>, color: Color, value: f32, prior: Box<[f32]>)
+/// Returns the approximate number of bytes that were added to the tree.
+/// 
+pub unsafe fn insert<E>(trace: &NodeTrace<E>, color: Color, value: f32, prior: Box<[f32]>, key: u64) -> usize
     where E: Value
 {
+    let mut added = 0;
+
     if let Some(&(node, _, index)) = trace.last() {
         let mut next = Box::new(Node::new(color, prior));
         if index == 361 {
             next.pass_count = (*node).pass_count + 1;
         }
+        next.key = key;
+
+        let edge = (*node).edge(index).unwrap();
+        let _guard = (*node).lock.lock();
+
+        if (*node).child(edge).is_null() {
+            added = Node::<E>::size_of(next.vertices.len());
 
-        if (*node).children[index].is_null() {
-            (*node).children[index] = Box::into_raw(next);
+            *(*node).children.offset(edge as isize) = Box::into_raw(next);
         } else {
             debug_assert!(index == 361);
 
@@ -722,6 +1070,43 @@ pub unsafe fn insert<E>(trace: &NodeTrace<E>, color: Color, value: f32, prior: B
     }
 
     E::update::<E>(trace, color, value);
+    added
+}
+
+/// Add the given value to the shared statistics of every position in the given
+/// trace, and replace the average value of each edge with the shared average
+/// of the position it leads to. The visit counts of each edge are not shared,
+/// so the exploration of each path remains independent of how often other
+/// paths have visited the same position.
+///
+/// # Arguments
+///
+/// * `trace` - the trace to update
+/// * `table` - the transposition table
+/// * `color` - the color of the player to move at the end of the trace
+/// * `value` - the value of the end of the trace, from the perspective of `color`
+///
+pub unsafe fn transpose<E>(trace: &NodeTrace<E>, table: &TranspositionTable, color: Color, value: f32)
+    where E: Value
+{
+    for &(node, _, index) in trace.iter() {
+        let edge = (*node).edge(index).unwrap();
+        let child = (*node).child(edge);
+
+        if !child.is_null() {
+            let value_ = if color == (*node).color { value } else { 1.0 - value };
+            let (count, average) = table.update((*child).key, value_);
+
+            // only trust the shared statistics if every visit to this edge
+            // has been recorded in them, otherwise the entry has been replaced
+            // by another position (or the tree is older than the table).
+            let _guard = (*node).lock.lock();
+
+            if count >= (*node).count[edge] {
+                (*node).value[edge] = average;
+            }
+        }
+    }
 }
 
 /// Type alias for `Node<E>` that acts as a wrapper for calling `as_sgf` from
@@ -730,36 +1115,50 @@ pub struct ToSgf<'a, S: SgfCoordinate, E: Value + 'a> {
     _coordinate_format: ::std::marker::PhantomData<S>,
     starting_point: Board,
     root: &'a Node<E>,
+    komi: f32,
     meta: bool
 }
 
 impl<'a, S: SgfCoordinate, E: Value + 'a> fmt::Display for ToSgf<'a, S, E> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let size = self.starting_point.size();
+        let mut root = sgf::Node { properties: vec! [] };
+
         if self.meta {
-            // add the standard SGF prefix
-            write!(fmt, "(;GM[1]FF[4]SZ[19]RU[Chinese]KM[7.5]PL[{}]",
-                if self.root.color == Color::Black { "B" } else { "W" }
-            )?;
+            // add the standard SGF meta data
+            root.properties.extend(vec! [
+                Property::Unknown("GM".to_string(), vec! ["1".to_string()]),
+                Property::Unknown("FF".to_string(), vec! ["4".to_string()]),
+                Property::Size(size),
+                Property::Rules(self.starting_point.rules().to_string()),
+                Property::Komi(self.komi),
+                Property::ToPlay(self.root.color)
+            ]);
 
             // write the starting point to the SGF file as pre-set variables
-            for y in 0..19 {
-                for x in 0..19 {
-                    match self.starting_point.at(x, y) {
-                        None => Ok(()),
-                        Some(Color::Black) => write!(fmt, "AB[{}", S::to_sgf(x, y)),
-                        Some(Color::White) => write!(fmt, "AW[{}]", S::to_sgf(x, y))
-                    }?
-                }
-            }
+            let vertices = (0..size).flat_map(|y| (0..size).map(move |x| (x, y)));
+            let stones = |color: Color| {
+                vertices.clone()
+                    .filter(|&(x, y)| self.starting_point.at(x, y) == Some(color))
+                    .map(|(x, y)| S::to_point(x, y, size))
+                    .collect::<Vec<(usize, usize)>>()
+            };
 
-            // write the actual search tree
-            self.root.as_sgf::<S>(fmt, self.meta)?;
+            root.properties.push(Property::AddBlack(stones(Color::Black)));
+            root.properties.push(Property::AddWhite(stones(Color::White)));
+        }
 
-            // add the standard SGF suffix
-            write!(fmt, ")")
+        let variations = self.root.as_sgf::<S>(&mut root, size, self.meta, false);
+
+        if self.meta {
+            write!(fmt, "{}", sgf::GameTree { nodes: vec! [root], variations: variations })
         } else {
             // write the actual search tree
-            self.root.as_sgf::<S>(fmt, self.meta)
+            for variation in variations.iter() {
+                write!(fmt, "{}", variation)?;
+            }
+
+            Ok(())
         }
     }
 }
@@ -771,9 +1170,10 @@ impl<'a, S: SgfCoordinate, E: Value + 'a> fmt::Display for ToSgf<'a, S, E> {
 /// 
 /// * `root` -
 /// * `starting_point` -
+/// * `komi` - the komi of the game
 /// * `meta` - whether to include the SGF meta data (rules, etc.)
 /// 
-pub fn to_sgf<'a, S, E>(root: &'a Node<E>, starting_point: &Board, meta: bool) -> ToSgf<'a, S, E>
+pub fn to_sgf<'a, S, E>(root: &'a Node<E>, starting_point: &Board, komi: f32, meta: bool) -> ToSgf<'a, S, E>
     where S: SgfCoordinate,
           E: Value
 {
@@ -781,6 +1181,7 @@ pub fn to_sgf<'a, S, E>(root: &'a Node<E>, starting_point: &Board, meta: bool) -
         _coordinate_format: ::std::marker::PhantomData::default(),
         starting_point: starting_point.clone(),
         root: &root,
+        komi: komi,
         meta: meta
     }
 }
@@ -810,23 +1211,27 @@ impl fmt::Display for PrettyVertex {
 
 /// Iterator that traverse the most likely path down a search tree
 pub struct GreedyPath<'a, E: Value + 'a> {
-    current: &'a Node<E>,
+    current: Option<&'a Node<E>>,
 }
 
 impl<'a, E: Value + 'a> Iterator for GreedyPath<'a, E> {
     type Item = usize;
 
     fn next(&mut self) -> Option<usize> {
-        let max_i = (0..362).max_by_key(|&i| self.current.count[i]).unwrap();
+        let current = self.current?;
+        let _guard = current.lock.lock();
+        let max_i = (0..current.vertices.len()).rev().max_by_key(|&i| current.count[i])?;
 
-        if self.current.count[max_i] == 0 {
+        if current.count[max_i] == 0 {
             None
         } else {
-            unsafe {
-                self.current = &*self.current.children[max_i];
-            }
+            // the child may not have been expanded yet, if some other thread
+            // is still busy evaluating it
+            let child = current.child(max_i);
 
-            Some(max_i)
+            self.current = if child.is_null() { None } else { Some(unsafe { &*child }) };
+
+            Some(current.vertices[max_i] as usize)
         }
     }
 }
@@ -839,16 +1244,16 @@ pub struct ToPretty<'a, E: Value + 'a> {
 
 impl<'a, E: Value + 'a> fmt::Display for ToPretty<'a, E> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let is_additional = (0..362).any(|i| self.root.amaf_count[i] > 0);
-        let mut children = (0..362).collect::<Vec<usize>>();
+        let is_additional = self.root.amaf_count.iter().any(|&count| count > 0);
+        let mut children = (0..self.root.vertices.len()).collect::<Vec<usize>>();
         children.sort_by_key(|&i| -self.root.count[i]);
 
         // print a summary containing the total tree size
-        let total_value: f32 = (0..362)
+        let total_value: f32 = (0..self.root.vertices.len())
             .map(|i| (self.root.count[i] as f32) * self.root.value[i])
             .sum();
         let norm_value = total_value / (self.root.total_count as f32);
-        let likely_path: String = GreedyPath { current: self.root }
+        let likely_path: String = GreedyPath { current: Some(self.root) }
                 .map(|i| PrettyVertex { inner: i })
                 .map(|v| format!("{}", v))
                 .collect::<Vec<String>>().join(" ");
@@ -865,9 +1270,9 @@ impl<'a, E: Value + 'a> fmt::Display for ToPretty<'a, E> {
                 continue;
             }
 
-            let pretty_vertex = PrettyVertex { inner: i };
-            let child = unsafe { &*self.root.children[i] };
-            let likely_path: String = GreedyPath { current: child }
+            let pretty_vertex = PrettyVertex { inner: self.root.vertices[i] as usize };
+            let child = unsafe { &*self.root.child(i) };
+            let likely_path: String = GreedyPath { current: Some(child) }
                     .map(|i| PrettyVertex { inner: i })
                     .map(|v| format!("{}", v))
                     .collect::<Vec<String>>().join(" ");
@@ -908,12 +1313,81 @@ pub fn to_pretty<'a, E: Value>(root: &'a Node<E>) -> ToPretty<'a, E> {
     ToPretty { root: root }
 }
 
+/// Type alias for `Node<E>` that acts as a wrapper for writing the statistics
+/// of the root of a search tree in the format expected by the `lz-analyze`
+/// GTP command.
+pub struct ToAnalyze<'a, E: Value + 'a> {
+    root: &'a Node<E>,
+}
+
+impl<'a, E: Value + 'a> fmt::Display for ToAnalyze<'a, E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let to_vertex = |i: usize| -> String {
+            let vertex = format!("{}", PrettyVertex { inner: i });
+
+            if i == 361 { vertex } else { vertex.to_uppercase() }
+        };
+
+        // take a snapshot of the statistics of the root, since the search may
+        // still be running in the background
+        let mut children = {
+            let _guard = self.root.lock.lock();
+
+            (0..self.root.vertices.len())
+                .filter(|&i| self.root.count[i] > 0)
+                .map(|i| {
+                    let vertex = self.root.vertices[i] as usize;
+
+                    (vertex, self.root.count[i], self.root.value[i], self.root.prior[i], self.root.child(i))
+                })
+                .collect::<Vec<_>>()
+        };
+        children.sort_by_key(|&(_, count, _, _, _)| -count);
+
+        for (order, &(vertex, count, value, prior, child)) in children.iter().enumerate() {
+            let likely_path = if child.is_null() {
+                vec! []
+            } else {
+                GreedyPath { current: Some(unsafe { &*child }) }.collect::<Vec<usize>>()
+            };
+
+            if order > 0 {
+                write!(fmt, " ")?;
+            }
+
+            write!(fmt, "info move {} visits {} winrate {} prior {} order {} pv {}",
+                to_vertex(vertex),
+                count,
+                (10000.0 * value) as i32,
+                (10000.0 * prior) as i32,
+                order,
+                ::std::iter::once(vertex).chain(likely_path.into_iter())
+                    .map(|j| to_vertex(j))
+                    .collect::<Vec<String>>().join(" ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a marker that contains the statistics of the candidate moves at
+/// the root of the given search tree, and can be written as a single line
+/// of `info` entries for the `lz-analyze` GTP command.
+///
+/// # Arguments
+///
+/// * `root` - the root of the search tree
+///
+pub fn to_analyze<'a, E: Value>(root: &'a Node<E>) -> ToAnalyze<'a, E> {
+    ToAnalyze { root: root }
+}
+
 #[cfg(test)]
 mod tests {
     use test::{self, Bencher};
     use rand::{XorShiftRng, Rng};
     use go::*;
-    use mcts::param::*;
     use mcts::tree::*;
 
     #[bench]
@@ -954,7 +1428,7 @@ mod tests {
         prior.into_boxed_slice()
     }
 
-    unsafe fn bench_test<C: Param, E: Value>(b: &mut Bencher) {
+    unsafe fn bench_test<E: Value>(b: &mut Bencher) {
         let mut rng = XorShiftRng::new_unseeded();
         let mut root = Node::<E>::new(Color::Black, get_prior_distribution(&mut rng));
 
@@ -965,8 +1439,8 @@ mod tests {
 
             // check so that the reference and asm implementation gives back the same
             // value
-            E::get::<C, E>(&root, &mut dst_asm);
-            E::get_ref::<C, E>(&root, &mut dst_ref);
+            E::get::<E>(&root, &mut dst_asm);
+            E::get_ref::<E>(&root, &mut dst_ref);
 
             for i in 0..362 {
                 // because of numeric instabilities and approximations the answers may
@@ -981,12 +1455,12 @@ mod tests {
             }
 
             // expand the tree by one probe so that the root values change
-            let trace = probe::<C, E>(&mut root, &mut board).unwrap();
+            let trace = probe::<E>(&mut root, &mut board).unwrap();
             let &(_, color, _) = trace.last().unwrap();
             let next_color = color.opposite();
             let (value, policy) = (rng.next_f32(), get_prior_distribution(&mut rng));
 
-            insert::<C, E>(&trace, next_color, value, policy);
+            insert::<E>(&trace, next_color, value, policy, 0);
         }
 
         // benchmark the value function only
@@ -995,19 +1469,281 @@ mod tests {
         b.iter(|| {
             let mut dst = test::black_box([0.0f32; 368]);
 
-            E::get::<C, E>(&root, &mut dst);
+            E::get::<E>(&root, &mut dst);
             dst
         });
 
     }
 
+    #[test]
+    fn analyze() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let mut root = Node::<PUCT>::new(Color::Black, get_prior_distribution(&mut rng));
+
+        for _ in 0..100 {
+            let mut board = Board::new();
+            let trace = unsafe { probe::<PUCT>(&mut root, &mut board).unwrap() };
+            let &(_, color, _) = trace.last().unwrap();
+            let (value, policy) = (rng.next_f32(), get_prior_distribution(&mut rng));
+
+            unsafe { insert::<PUCT>(&trace, color.opposite(), value, policy, 0); }
+        }
+
+        let analysis = format!("{}", to_analyze(&root));
+        let entries = analysis.split("info ").filter(|e| !e.is_empty()).collect::<Vec<&str>>();
+
+        assert!(analysis.starts_with("info move "));
+        assert_eq!(entries.len(), (0..362).filter(|&i| root.count[i] > 0).count());
+        assert!(entries[0].contains(" order 0 pv "));
+        assert!(!analysis.contains('\n'));
+    }
+
+    #[test]
+    fn is_unreachable() {
+        let mut root = Node::<PUCT>::new(Color::Black, vec! [0.0; 362].into_boxed_slice());
+
+        root.count[10] = 20;
+        root.count[20] = 15;
+        root.count[30] = 5;
+
+        assert!(root.is_unreachable(4));
+        assert!(!root.is_unreachable(5));
+        assert!(!root.is_unreachable(100));
+    }
+
+    #[test]
+    fn compact() {
+        let mut prior = vec! [::std::f32::NEG_INFINITY; 362];
+        prior[60] = 0.7;
+        prior[72] = 0.2;
+        prior[361] = 0.1;
+
+        let root = Node::<PUCT>::new(Color::Black, prior.into_boxed_slice());
+
+        assert_eq!(&root.vertices[..3], &[60, 72, 361]);
+        assert_eq!(root.vertices.len(), 8);
+        assert!(root.children.is_null());
+        assert_eq!(root.prior(), (0.7, 60));
+        assert!(root.memory_usage() < 1024);
+        assert!(root.forward(72).is_none());
+    }
+
+    #[test]
+    fn transpositions() {
+        let mut root = Node::<PUCT>::new(Color::Black, vec! [0.0; 362].into_boxed_slice());
+        let table = TranspositionTable::with_capacity(16);
+
+        assert!(root.select().is_some());  // allocate the children
+
+        for &index in [10, 20].iter() {
+            let mut child = Box::new(Node::<PUCT>::new(Color::White, vec! [0.0; 362].into_boxed_slice()));
+            child.key = 42;
+
+            unsafe { *root.children.offset(index) = Box::into_raw(child); }
+        }
+
+        // both edges leads to the same position, so the second edge should
+        // share the value of the first one
+        root.count[10] = 1;
+        unsafe { transpose::<PUCT>(&vec! [(&mut root as *mut _, Color::Black, 10)], &table, Color::White, 0.0); }
+        assert_eq!(root.value[10], 1.0);
+
+        root.count[20] = 1;
+        unsafe { transpose::<PUCT>(&vec! [(&mut root as *mut _, Color::Black, 20)], &table, Color::White, 1.0); }
+        assert_eq!(root.value[20], 0.5);
+    }
+
+    #[test]
+    fn principal_variation() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let mut root = Node::<PUCT>::new(Color::Black, get_prior_distribution(&mut rng));
+
+        for _ in 0..100 {
+            let mut board = Board::new();
+            let trace = unsafe { probe::<PUCT>(&mut root, &mut board).unwrap() };
+            let &(_, color, _) = trace.last().unwrap();
+            let (value, policy) = (rng.next_f32(), get_prior_distribution(&mut rng));
+
+            unsafe { insert::<PUCT>(&trace, color.opposite(), value, policy, 0); }
+        }
+
+        let (_, best) = root.best(0.0);
+        let mut variation = root.to_principal_variation::<sgf::CGoban>(19).unwrap();
+        let first = if best == 361 { None } else { Some((X[best] as usize, Y[best] as usize)) };
+
+        assert_eq!(variation.nodes[0].properties[0], sgf::Property::Move(Color::Black, first));
+
+        loop {
+            assert_eq!(variation.nodes.len(), 1);
+            assert!(variation.variations.len() <= 1);
+
+            match variation.variations.pop() {
+                Some(next) => { variation = next; },
+                None => break
+            }
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let mut root = Node::<RAVE>::new(Color::Black, get_prior_distribution(&mut rng));
+        let mut buf = vec! [];
+
+        for _ in 0..100 {
+            let mut board = Board::new();
+            let trace = unsafe { probe::<RAVE>(&mut root, &mut board).unwrap() };
+            let &(_, color, _) = trace.last().unwrap();
+            let (value, policy) = (rng.next_f32(), get_prior_distribution(&mut rng));
+
+            unsafe { insert::<RAVE>(&trace, color.opposite(), value, policy, 0); }
+        }
+
+        root.write_into(&mut buf).unwrap();
+
+        let mut other = Node::<RAVE>::read_from(&mut &buf[..]).unwrap();
+
+        assert_eq!(other.size(), root.size());
+        assert_eq!(other.memory_usage(), root.memory_usage());
+        assert_eq!(&other.amaf_count[..], &root.amaf_count[..]);
+        assert_eq!(format!("{}", to_analyze(&other)), format!("{}", to_analyze(&root)));
+        assert!(Node::<PUCT>::read_from(&mut &buf[..]).is_err());
+        assert!(Node::<RAVE>::read_from(&mut &buf[..buf.len() - 1]).is_err());
+
+        // the search should be able to continue from the loaded tree
+        let mut board = Board::new();
+
+        assert!(unsafe { probe::<RAVE>(&mut other, &mut board).is_some() });
+    }
+
+    /// Write a `PUCT` node with the given color, total visit count, and
+    /// edges `(vertex, count, prior, value)` padded to eight edges, into the
+    /// given buffer in the same format as `write_into`. The flags that
+    /// indicate which edges has a child are not written.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - the buffer to write the node into
+    /// * `color` - the color of the node
+    /// * `total_count` - the total visit count of the node
+    /// * `edges` - the (non-padding) edges of the node
+    ///
+    fn write_edges(buf: &mut Vec<u8>, color: Color, total_count: i32, edges: &[(u16, i32, f32, f32)]) {
+        binary::write_u8(buf, color as u8).unwrap();
+        binary::write_u8(buf, PUCT::AMAF as u8).unwrap();
+        binary::write_u32(buf, 0).unwrap();
+        binary::write_u64(buf, 0).unwrap();
+        binary::write_u32(buf, total_count as u32).unwrap();
+        binary::write_u16(buf, 8).unwrap();
+
+        for i in 0..8 {
+            let (vertex, count, prior, value) = if i < edges.len() {
+                edges[i]
+            } else {
+                (PADDING, 0, ::std::f32::NEG_INFINITY, 0.0)
+            };
+
+            binary::write_u16(buf, vertex).unwrap();
+            binary::write_u32(buf, count as u32).unwrap();
+            binary::write_f32(buf, prior).unwrap();
+            binary::write_f32(buf, value).unwrap();
+        }
+    }
+
+    /// Assert that reading a search tree from the given buffer fails with
+    /// the given message.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - the buffer to read the search tree from
+    /// * `message` - the expected error message
+    ///
+    fn assert_invalid(buf: &[u8], message: &str) {
+        match Node::<PUCT>::read_from(&mut &buf[..]) {
+            Err(ref err) => {
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(format!("{}", err), message);
+            },
+            Ok(_) => panic!("read an invalid search tree")
+        }
+    }
+
+    #[test]
+    fn read_too_deep() {
+        let mut buf = vec! [];
+        let mut color = Color::Black;
+
+        // a search tree where every node has a single child, which is deeper
+        // than any game
+        for _ in 0..(MAX_DEPTH + 10) {
+            write_edges(&mut buf, color, 1, &[(0, 1, 0.5, 0.5)]);
+            binary::write_u8(&mut buf, 1).unwrap();
+            color = color.opposite();
+        }
+
+        assert_invalid(&buf, "search tree is too deep");
+    }
+
+    #[test]
+    fn read_negative_count() {
+        let mut buf = vec! [];
+
+        write_edges(&mut buf, Color::Black, -1, &[(0, -1, 0.5, 0.5)]);
+        buf.extend_from_slice(&[0; 8]);
+
+        assert_invalid(&buf, "negative visit count");
+    }
+
+    #[test]
+    fn read_wrong_total_count() {
+        let mut buf = vec! [];
+
+        write_edges(&mut buf, Color::Black, 3, &[(0, 1, 0.5, 0.5), (1, 1, 0.5, 0.5)]);
+        buf.extend_from_slice(&[0; 8]);
+
+        assert_invalid(&buf, "total visit count does not match the edges");
+    }
+
+    #[test]
+    fn read_non_finite_prior() {
+        let mut buf = vec! [];
+
+        write_edges(&mut buf, Color::Black, 1, &[(0, 1, ::std::f32::NAN, 0.5)]);
+        buf.extend_from_slice(&[0; 8]);
+
+        assert_invalid(&buf, "invalid prior value");
+    }
+
+    #[test]
+    fn read_invalid_value() {
+        let mut buf = vec! [];
+
+        write_edges(&mut buf, Color::Black, 1, &[(0, 1, 0.5, 1.5)]);
+        buf.extend_from_slice(&[0; 8]);
+
+        assert_invalid(&buf, "invalid value");
+    }
+
+    #[test]
+    fn read_wrong_child_color() {
+        let mut buf = vec! [];
+
+        write_edges(&mut buf, Color::Black, 1, &[(0, 1, 0.5, 0.5)]);
+        binary::write_u8(&mut buf, 1).unwrap();
+        write_edges(&mut buf, Color::Black, 0, &[(1, 0, 0.5, 0.0)]);
+        buf.extend_from_slice(&[0; 8]);
+        buf.extend_from_slice(&[0; 7]);
+
+        assert_invalid(&buf, "child has the wrong color");
+    }
+
     #[bench]
     fn puct(b: &mut Bencher) {
-        unsafe { bench_test::<Standard, PUCT>(b); }
+        unsafe { bench_test::<PUCT>(b); }
     }
 
     #[bench]
     fn rave(b: &mut Bencher) {
-        unsafe { bench_test::<Standard, RAVE>(b); }
+        unsafe { bench_test::<RAVE>(b); }
     }
 }
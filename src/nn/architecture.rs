@@ -0,0 +1,273 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use nn::loader::LoadError;
+use nn::ops::Tensor;
+
+/// The number of vertices in each feature plane.
+const NUM_VERTICES: usize = 361;
+
+/// The number of input planes that `Board::get_features` produces.
+const NUM_BOARD_FEATURES: usize = 32;
+
+/// The shape of a neural network, which is a tower of residual blocks that is
+/// followed by a policy head and a value head. This is stored together with
+/// the weights, so that networks of different sizes can be loaded without
+/// re-compiling the engine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Architecture {
+    /// The number of residual blocks in the tower.
+    pub num_blocks: usize,
+
+    /// The number of channels in each residual block.
+    pub num_channels: usize,
+
+    /// The number of input planes.
+    pub num_features: usize,
+
+    /// The number of channels of the convolution in the policy head.
+    pub num_policy_channels: usize,
+
+    /// The number of channels of the convolution in the value head.
+    pub num_value_channels: usize,
+
+    /// The number of hidden units in the fully connected layer of the value
+    /// head.
+    pub num_value_hidden: usize
+}
+
+impl Default for Architecture {
+    /// Returns the architecture of the weights that were written before the
+    /// architecture was stored together with them.
+    fn default() -> Architecture {
+        Architecture {
+            num_blocks: 19,
+            num_channels: 128,
+            num_features: NUM_BOARD_FEATURES,
+            num_policy_channels: 2,
+            num_value_channels: 1,
+            num_value_hidden: 256
+        }
+    }
+}
+
+impl Architecture {
+    /// Returns the architecture described by the given properties, any
+    /// property that is missing keeps its default value.
+    ///
+    /// # Arguments
+    ///
+    /// * `properties` - the name and value of each property
+    ///
+    pub fn from_properties(properties: &HashMap<String, String>) -> Result<Architecture, LoadError> {
+        let mut out = Architecture::default();
+
+        for (key, value) in properties.iter() {
+            let value = value.parse::<usize>()
+                .map_err(|_| LoadError::BadArchitecture(format!("architecture property {} is not a number -- {}", key, value)))?;
+
+            match key.as_str() {
+                "num_blocks" => { out.num_blocks = value },
+                "num_channels" => { out.num_channels = value },
+                "num_features" => { out.num_features = value },
+                "num_policy_channels" => { out.num_policy_channels = value },
+                "num_value_channels" => { out.num_value_channels = value },
+                "num_value_hidden" => { out.num_value_hidden = value },
+                _ => { return Err(LoadError::BadArchitecture(format!("unknown architecture property -- {}", key))) }
+            }
+        }
+
+        if out.num_channels == 0 || out.num_policy_channels == 0 || out.num_value_channels == 0 || out.num_value_hidden == 0 {
+            Err(LoadError::BadArchitecture("the architecture contains a layer without any channels".to_string()))
+        } else if out.num_features != NUM_BOARD_FEATURES {
+            Err(LoadError::BadArchitecture(format!("unsupported number of input planes -- {}", out.num_features)))
+        } else {
+            Ok(out)
+        }
+    }
+
+    /// Returns the number of elements in the input features of a single
+    /// board position.
+    pub fn features_size(&self) -> usize {
+        self.num_features * NUM_VERTICES
+    }
+
+    /// Returns the number of elements in the output of the tower for a single
+    /// board position.
+    pub fn tower_size(&self) -> usize {
+        self.num_channels * NUM_VERTICES
+    }
+
+    /// Returns the name of the scope of the given residual block.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - the index of the residual block, starting at zero
+    ///
+    pub fn residual(&self, i: usize) -> String {
+        format!("{:02}_residual", i + 2)
+    }
+
+    /// Returns the name of the tensor that contains the output of the tower.
+    pub fn tower_output(&self) -> String {
+        if self.num_blocks == 0 {
+            "01_upsample/output:0".to_string()
+        } else {
+            format!("{}/output_2:0", self.residual(self.num_blocks - 1))
+        }
+    }
+
+    /// Returns the name of the scope of the policy head.
+    pub fn policy(&self) -> String {
+        format!("{:02}p_policy", self.num_blocks + 2)
+    }
+
+    /// Returns the name of the scope of the value head.
+    pub fn value(&self) -> String {
+        format!("{:02}v_value", self.num_blocks + 2)
+    }
+
+    /// Returns the name and the shape of every weight tensor that a network
+    /// with this architecture contains. The shape of a convolution is
+    /// `[out, in, h, w]` and the shape of a fully connected layer is
+    /// `[in, out]`.
+    pub(super) fn weights(&self) -> Vec<(String, Vec<usize>)> {
+        let (c, f) = (self.num_channels, self.num_features);
+        let (p, v, h) = (self.num_policy_channels, self.num_value_channels, self.num_value_hidden);
+        let mut out = vec! [
+            ("01_upsample/weights:0".to_string(), vec! [c, f, 3, 3]),
+            ("01_upsample/offset:0".to_string(), vec! [c])
+        ];
+
+        for i in 0..self.num_blocks {
+            let residual = self.residual(i);
+
+            out.push((format!("{}/weights_1:0", residual), vec! [c, c, 3, 3]));
+            out.push((format!("{}/weights_2:0", residual), vec! [c, c, 3, 3]));
+            out.push((format!("{}/offset_1:0", residual), vec! [c]));
+            out.push((format!("{}/offset_2:0", residual), vec! [c]));
+        }
+
+        let policy = self.policy();
+        let value = self.value();
+
+        out.push((format!("{}/downsample:0", policy), vec! [p, c, 1, 1]));
+        out.push((format!("{}/offset:0", policy), vec! [p]));
+        out.push((format!("{}/weights:0", policy), vec! [p * NUM_VERTICES, 362]));
+        out.push((format!("{}/bias:0", policy), vec! [362]));
+        out.push((format!("{}/downsample:0", value), vec! [v, c, 1, 1]));
+        out.push((format!("{}/offset:0", value), vec! [v]));
+        out.push((format!("{}/weights_1:0", value), vec! [v * NUM_VERTICES, h]));
+        out.push((format!("{}/bias_1:0", value), vec! [h]));
+        out.push((format!("{}/weights_2:0", value), vec! [h, 1]));
+        out.push((format!("{}/bias_2:0", value), vec! [1]));
+        out
+    }
+
+    /// Returns an error if some weight of this architecture is missing from
+    /// the given tensors, or has the wrong number of elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `tensors` - the tensors to check
+    ///
+    pub fn check(&self, tensors: &HashMap<String, Tensor>) -> Result<(), LoadError> {
+        for (name, shape) in self.weights() {
+            let size = shape.iter().product::<usize>();
+            let actual = tensors.get(&name)
+                .and_then(|tensor| tensor.host.as_ref())
+                .map(|host| host.len());
+
+            match actual {
+                None => {
+                    return Err(LoadError::BadTensor { name: name, reason: "missing tensor".to_string() })
+                },
+                Some(actual) if actual != size => {
+                    let reason = format!("has {} elements, expected {}", actual, size);
+
+                    return Err(LoadError::BadTensor { name: name, reason: reason })
+                },
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nn::architecture::*;
+    use util::types::*;
+
+    /// Returns tensors with the correct size for every weight in the given
+    /// architecture.
+    fn tensors_for(architecture: &Architecture) -> HashMap<String, Tensor> {
+        architecture.weights().into_iter()
+            .map(|(name, shape)| {
+                let mut tensor = Tensor::default();
+                let size = shape.iter().product::<usize>();
+                tensor.set_host(vec! [f16::from(0.0); size].into_boxed_slice());
+
+                (name, tensor)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_names() {
+        let architecture = Architecture::default();
+
+        assert_eq!(architecture.features_size(), 11552);
+        assert_eq!(architecture.tower_output(), "20_residual/output_2:0");
+        assert_eq!(architecture.policy(), "21p_policy");
+        assert_eq!(architecture.value(), "21v_value");
+    }
+
+    #[test]
+    fn from_properties() {
+        let properties = vec! [
+            ("num_blocks".to_string(), "6".to_string()),
+            ("num_channels".to_string(), "64".to_string())
+        ].into_iter().collect();
+        let architecture = Architecture::from_properties(&properties).unwrap();
+
+        assert_eq!(architecture.num_blocks, 6);
+        assert_eq!(architecture.num_channels, 64);
+        assert_eq!(architecture.num_value_hidden, 256);
+        assert_eq!(architecture.tower_output(), "07_residual/output_2:0");
+        assert_eq!(architecture.policy(), "08p_policy");
+
+        let unknown = vec! [("num_heads".to_string(), "2".to_string())].into_iter().collect();
+        let features = vec! [("num_features".to_string(), "18".to_string())].into_iter().collect();
+
+        assert!(Architecture::from_properties(&unknown).is_err());
+        assert!(Architecture::from_properties(&features).is_err());
+    }
+
+    #[test]
+    fn check() {
+        let small = Architecture { num_blocks: 1, num_channels: 4, ..Architecture::default() };
+        let mut tensors = tensors_for(&small);
+
+        assert!(small.check(&tensors).is_ok());
+        assert!(Architecture::default().check(&tensors).is_err());
+
+        tensors.get_mut("02_residual/weights_1:0").unwrap()
+            .set_host(vec! [f16::from(0.0); 4].into_boxed_slice());
+        assert!(small.check(&tensors).is_err());
+    }
+}
@@ -0,0 +1,638 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use libc::c_void;
+use std::collections::HashMap;
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+use nn::architecture::Architecture;
+use nn::graph::{self, Graph, Ops};
+use nn::ops::Tensor;
+
+/// The number of vertices in each feature plane.
+const NUM_VERTICES: usize = 361;
+
+/// The width (and height) of each feature plane.
+const WIDTH: usize = 19;
+
+/// The weights of the neural network, converted to `f32` and stored in host
+/// memory so that they can be shared between all workspaces.
+pub struct Builder {
+    tensors: Arc<HashMap<String, Box<[f32]>>>,
+    architecture: Architecture
+}
+
+impl Builder {
+    pub fn new(architecture: Architecture, tensors: HashMap<String, Tensor>) -> Builder {
+        let tensors = tensors.into_iter()
+            .filter_map(|(name, tensor)| {
+                tensor.host.as_ref().map(|host| {
+                    let values = host.iter()
+                        .map(|&x| f32::from(x))
+                        .collect::<Vec<f32>>();
+
+                    (name, values.into_boxed_slice())
+                })
+            })
+            .collect();
+
+        Builder {
+            tensors: Arc::new(tensors),
+            architecture: architecture
+        }
+    }
+
+    /// Returns a workspace that can be used to perform a forward pass through
+    /// the network on the CPU.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_size` - the number of features to evaluate in each pass
+    ///
+    pub fn get_workspace(&self, batch_size: usize) -> Workspace {
+        Workspace {
+            tensors: self.tensors.clone(),
+            architecture: self.architecture,
+            sizes: HashMap::new(),
+            slots: HashMap::new(),
+            batch_size: batch_size
+        }
+    }
+}
+
+pub struct Workspace {
+    tensors: Arc<HashMap<String, Box<[f32]>>>,
+    architecture: Architecture,
+
+    /// The number of elements, per batch, of each intermediate tensor that
+    /// has been computed so far.
+    sizes: HashMap<String, usize>,
+
+    /// The host memory backing each slot.
+    slots: HashMap<String, Box<[f32]>>,
+
+    pub(super) batch_size: usize
+}
+
+impl Graph for Workspace {
+    fn get_input(&mut self, size_in_bytes: Option<usize>) -> *mut c_void {
+        self.get_slot("00_input/features:0", size_in_bytes.unwrap_or(0))
+    }
+
+    fn get_policy_output(&mut self, size_in_bytes: Option<usize>) -> *mut c_void {
+        self.get_slot("99_output/policy:0", size_in_bytes.unwrap_or(0))
+    }
+
+    fn get_value_output(&mut self, size_in_bytes: Option<usize>) -> *mut c_void {
+        self.get_slot("99_output/value:0", size_in_bytes.unwrap_or(0))
+    }
+
+    fn get_slot(&mut self, name: &'static str, size_in_bytes: usize) -> *mut c_void {
+        let entry = self.slots.entry(name.to_string());
+        let slot = entry.or_insert_with(|| {
+            assert!(size_in_bytes > 0, "missing slot -- {}", name);
+
+            vec! [0.0f32; (size_in_bytes + 3) / 4].into_boxed_slice()
+        });
+
+        slot.as_mut_ptr() as *mut c_void
+    }
+
+    fn get_batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    fn get_workspace_size(&self) -> usize {
+        0
+    }
+
+    fn get_architecture(&self) -> Architecture {
+        self.architecture
+    }
+}
+
+/// Returns a slice of `len` elements starting at the given pointer.
+///
+/// # Arguments
+///
+/// * `data` - a pointer to a slot in a `Workspace`
+/// * `len` - the number of elements in the slice
+///
+unsafe fn as_slice<'a>(data: *const c_void, len: usize) -> &'a [f32] {
+    slice::from_raw_parts(data as *const f32, len)
+}
+
+/// Returns a mutable slice of `len` elements starting at the given pointer.
+///
+/// # Arguments
+///
+/// * `data` - a pointer to a slot in a `Workspace`
+/// * `len` - the number of elements in the slice
+///
+unsafe fn as_slice_mut<'a>(data: *mut c_void, len: usize) -> &'a mut [f32] {
+    slice::from_raw_parts_mut(data as *mut f32, len)
+}
+
+/// Returns the cross-correlation between the given input and weights, with
+/// the offset added to each output channel. The input is expected in `NCHW`
+/// format, and the weights in `KCHW` format. The input is zero-padded so
+/// that the output has the same height and width as the input.
+///
+/// # Arguments
+///
+/// * `input` - the input tensor
+/// * `batch_size` - the number of images in the input tensor
+/// * `k` - the number of output channels
+/// * `c` - the number of input channels
+/// * `f` - the height and width of the filter
+/// * `weights` - the filter weights
+/// * `offset` - the offset for each output channel
+///
+fn conv2d(
+    input: &[f32],
+    batch_size: usize,
+    k: usize,
+    c: usize,
+    f: usize,
+    weights: &[f32],
+    offset: &[f32]
+) -> Vec<f32>
+{
+    let pad = f / 2;
+    let mut output = vec! [0.0f32; batch_size * k * NUM_VERTICES];
+
+    debug_assert!(input.len() >= batch_size * c * NUM_VERTICES);
+    debug_assert!(weights.len() == k * c * f * f);
+    debug_assert!(offset.len() == k);
+
+    for n in 0..batch_size {
+        for o in 0..k {
+            let out = &mut output[(n * k + o) * NUM_VERTICES..(n * k + o + 1) * NUM_VERTICES];
+
+            for v in out.iter_mut() {
+                *v = offset[o];
+            }
+
+            for i in 0..c {
+                let plane = &input[(n * c + i) * NUM_VERTICES..(n * c + i + 1) * NUM_VERTICES];
+
+                for dy in 0..f {
+                    let y_start = if dy < pad { pad - dy } else { 0 };
+                    let y_end = if dy > pad { WIDTH + pad - dy } else { WIDTH };
+
+                    for dx in 0..f {
+                        let x_start = if dx < pad { pad - dx } else { 0 };
+                        let x_end = if dx > pad { WIDTH + pad - dx } else { WIDTH };
+                        let w = weights[((o * c + i) * f + dy) * f + dx];
+
+                        for y in y_start..y_end {
+                            let src = (y + dy - pad) * WIDTH;
+                            let dst = y * WIDTH;
+
+                            for x in x_start..x_end {
+                                out[dst + x] += w * plane[src + x + dx - pad];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Returns `weights * input + offset` for each element in the batch, where
+/// the weights are stored in column-major order as a `k x c` matrix.
+///
+/// # Arguments
+///
+/// * `input` - the input tensor
+/// * `batch_size` - the number of elements in the batch
+/// * `k` - the number of outputs
+/// * `c` - the number of inputs
+/// * `weights` - the weights matrix
+/// * `offset` - the offset of each output
+///
+fn linear(
+    input: &[f32],
+    batch_size: usize,
+    k: usize,
+    c: usize,
+    weights: &[f32],
+    offset: &[f32]
+) -> Vec<f32>
+{
+    let mut output = vec! [0.0f32; batch_size * k];
+
+    debug_assert!(input.len() >= batch_size * c);
+    debug_assert!(weights.len() == k * c);
+    debug_assert!(offset.len() == k);
+
+    for n in 0..batch_size {
+        let out = &mut output[n * k..(n + 1) * k];
+
+        out.copy_from_slice(offset);
+
+        for j in 0..c {
+            let x = input[n * c + j];
+            let column = &weights[j * k..(j + 1) * k];
+
+            for (o, &w) in out.iter_mut().zip(column.iter()) {
+                *o += w * x;
+            }
+        }
+    }
+
+    output
+}
+
+/// Replace each element of the given array with its softmax, where the
+/// softmax is computed independently for each chunk of `size` elements.
+///
+/// # Arguments
+///
+/// * `values` - the values to replace
+/// * `size` - the number of elements in each instance
+///
+fn softmax(values: &mut [f32], size: usize) {
+    for chunk in values.chunks_mut(size) {
+        let max_value = chunk.iter().fold(::std::f32::NEG_INFINITY, |acc, &x| if x > acc { x } else { acc });
+        let mut total = 0.0f32;
+
+        for v in chunk.iter_mut() {
+            *v = (*v - max_value).exp();
+            total += *v;
+        }
+
+        for v in chunk.iter_mut() {
+            *v /= total;
+        }
+    }
+}
+
+pub struct Runtime;
+
+impl Runtime {
+    /// Returns the number of elements, per batch, in the given named
+    /// intermediate tensor.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` - the workspace that computed the tensor
+    /// * `name` - the name of the tensor
+    ///
+    fn get_size(workspace: &Workspace, name: &str) -> usize {
+        match workspace.sizes.get(name) {
+            Some(&size) => size,
+            None => panic!("missing tensor -- {}", name)
+        }
+    }
+
+    /// Applies the given element-wise function to the input and stores the
+    /// result in the output, which may alias the input.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `input_name` -
+    /// * `input_data` -
+    /// * `output_name` -
+    /// * `output_data` -
+    /// * `f` - the function to apply to each element
+    ///
+    fn map<F: Fn(f32) -> f32>(
+        workspace: &mut Workspace,
+        input_name: String,
+        input_data: *const c_void,
+        output_name: String,
+        output_data: *mut c_void,
+        f: F
+    )
+    {
+        let size = Runtime::get_size(workspace, &input_name);
+        let len = workspace.batch_size * size;
+        let values = unsafe {
+            as_slice(input_data, len).iter()
+                .map(|&x| f(x))
+                .collect::<Vec<f32>>()
+        };
+
+        unsafe {
+            as_slice_mut(output_data, len).copy_from_slice(&values);
+        }
+
+        workspace.sizes.insert(output_name, size);
+    }
+}
+
+impl Ops<Workspace> for Runtime {
+    fn convolution(
+        workspace: &mut Workspace,
+        _input_name: String,
+        input_data: *const c_void,
+        k: i32,
+        c: i32,
+        h: i32,
+        w: i32,
+        weights_name: String,
+        offset_name: String,
+        output_name: String,
+        output_data: *mut c_void,
+        _workspace_data: *mut c_void,
+        _workspace_size: usize
+    )
+    {
+        debug_assert!(h == w);
+
+        let (batch_size, k, c, f) = (workspace.batch_size, k as usize, c as usize, h as usize);
+        let mut output = unsafe {
+            conv2d(
+                as_slice(input_data, batch_size * c * NUM_VERTICES),
+                batch_size, k, c, f,
+                &workspace.tensors[&weights_name],
+                &workspace.tensors[&offset_name]
+            )
+        };
+
+        for v in output.iter_mut() {
+            *v = v.max(0.0);
+        }
+
+        unsafe {
+            as_slice_mut(output_data, output.len()).copy_from_slice(&output);
+        }
+
+        workspace.sizes.insert(output_name, k * NUM_VERTICES);
+    }
+
+    fn residual_block(
+        workspace: &mut Workspace,
+        input_name: String,
+        input_data: *const c_void,
+        k: i32,
+        c: i32,
+        h: i32,
+        w: i32,
+        weights_name_1: String,
+        weights_name_2: String,
+        offset_name_1: String,
+        offset_name_2: String,
+        output_name_1: String,
+        output_data_1: *mut c_void,
+        output_name_2: String,
+        output_data_2: *mut c_void,
+        workspace_data: *mut c_void,
+        workspace_size: usize
+    )
+    {
+        Runtime::convolution(
+            workspace,
+            input_name, input_data,
+            k, c, h, w,
+            weights_name_1,
+            offset_name_1,
+            output_name_1.clone(), output_data_1,
+            workspace_data, workspace_size
+        );
+
+        // the output of the second convolution is allowed to alias the input
+        // of the residual block, so compute the whole convolution before we
+        // blend it with the input.
+        let (batch_size, k, f) = (workspace.batch_size, k as usize, h as usize);
+        let mut output = unsafe {
+            conv2d(
+                as_slice(output_data_1, batch_size * k * NUM_VERTICES),
+                batch_size, k, k, f,
+                &workspace.tensors[&weights_name_2],
+                &workspace.tensors[&offset_name_2]
+            )
+        };
+
+        unsafe {
+            let input = as_slice(input_data, output.len());
+
+            for (v, &x) in output.iter_mut().zip(input.iter()) {
+                *v = (*v + x).max(0.0);
+            }
+
+            as_slice_mut(output_data_2, output.len()).copy_from_slice(&output);
+        }
+
+        workspace.sizes.insert(output_name_2, k * NUM_VERTICES);
+    }
+
+    fn linear(
+        workspace: &mut Workspace,
+        _input_name: String,
+        input_data: *const c_void,
+        k: i32,
+        c: i32,
+        weights_name: String,
+        offset_name: String,
+        output_name: String,
+        output_data: *mut c_void,
+        _workspace_data: *mut c_void,
+        _workspace_size: usize
+    )
+    {
+        let (batch_size, k, c) = (workspace.batch_size, k as usize, c as usize);
+        let output = unsafe {
+            linear(
+                as_slice(input_data, batch_size * c),
+                batch_size, k, c,
+                &workspace.tensors[&weights_name],
+                &workspace.tensors[&offset_name]
+            )
+        };
+
+        unsafe {
+            as_slice_mut(output_data, output.len()).copy_from_slice(&output);
+        }
+
+        workspace.sizes.insert(output_name, k);
+    }
+
+    fn softmax(
+        workspace: &mut Workspace,
+        input_name: String,
+        input_data: *const c_void,
+        output_name: String,
+        output_data: *mut c_void
+    )
+    {
+        let size = Runtime::get_size(workspace, &input_name);
+        let len = workspace.batch_size * size;
+        let mut values = unsafe { as_slice(input_data, len).to_vec() };
+
+        softmax(&mut values, size);
+
+        unsafe {
+            as_slice_mut(output_data, len).copy_from_slice(&values);
+        }
+
+        workspace.sizes.insert(output_name, size);
+    }
+
+    fn relu(
+        workspace: &mut Workspace,
+        input_name: String,
+        input_data: *const c_void,
+        output_name: String,
+        output_data: *mut c_void
+    )
+    {
+        Runtime::map(workspace, input_name, input_data, output_name, output_data, |x| x.max(0.0));
+    }
+
+    fn tanh(
+        workspace: &mut Workspace,
+        input_name: String,
+        input_data: *const c_void,
+        output_name: String,
+        output_data: *mut c_void
+    )
+    {
+        Runtime::map(workspace, input_name, input_data, output_name, output_data, |x| x.tanh());
+    }
+
+    fn get_slot(graph: &mut Workspace, name: &'static str, size_in_bytes: usize) -> *mut c_void {
+        if size_in_bytes == 0 {
+            ptr::null_mut()  // the CPU does not need any scratch memory
+        } else {
+            graph.get_slot(name, size_in_bytes)
+        }
+    }
+}
+
+/// Returns the value and policy tensors obtained from a forward pass
+/// through the neural network on the CPU.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+///
+pub fn forward<T, R>(
+    workspace: &mut Workspace,
+    features: &Vec<Box<[T]>>
+) -> (Vec<R>, Vec<Box<[R]>>)
+    where T: Clone, f32: From<T>, R: From<f32> + Clone
+{
+    let batch_size = workspace.batch_size;
+    let features_size = workspace.architecture.features_size();
+
+    debug_assert!(batch_size == features.len());
+
+    workspace.sizes.clear();
+    workspace.sizes.insert("00_input/output:0".to_string(), features_size);
+
+    unsafe {
+        let input = as_slice_mut(
+            workspace.get_input(Some(4 * batch_size * features_size)),
+            batch_size * features_size
+        );
+
+        for (i, feature) in features.iter().enumerate() {
+            assert_eq!(feature.len(), features_size);
+
+            for (j, x) in feature.iter().enumerate() {
+                input[i * features_size + j] = f32::from(x.clone());
+            }
+        }
+    }
+
+    graph::tower::<Runtime, _>(workspace);
+    graph::policy::<Runtime, _>(workspace);
+    graph::value::<Runtime, _>(workspace);
+
+    let (policy, value) = unsafe {
+        (
+            as_slice(workspace.get_policy_output(None), batch_size * 362),
+            as_slice(workspace.get_value_output(None), batch_size)
+        )
+    };
+
+    let softmax = policy.chunks(362)
+        .map(|p| p.iter().map(|&x| R::from(x)).collect::<Vec<R>>().into_boxed_slice())
+        .collect();
+    let value = value.iter()
+        .map(|&x| R::from(x))
+        .collect();
+
+    (value, softmax)
+}
+
+#[cfg(test)]
+mod tests {
+    use nn::cpu::*;
+
+    #[test]
+    fn conv2d_identity() {
+        let mut input = vec! [0.0f32; 2 * NUM_VERTICES];
+        let mut weights = vec! [0.0f32; 2 * 3 * 3];
+
+        for (i, x) in input.iter_mut().enumerate() {
+            *x = i as f32;
+        }
+
+        weights[4] = 1.0;  // the center of the first channel
+        weights[9 + 4] = 2.0;  // the center of the second channel
+
+        let output = conv2d(&input, 1, 1, 2, 3, &weights, &[0.5]);
+
+        for i in 0..NUM_VERTICES {
+            assert_eq!(output[i], 0.5 + input[i] + 2.0 * input[NUM_VERTICES + i]);
+        }
+    }
+
+    #[test]
+    fn conv2d_padding() {
+        let input = vec! [1.0f32; NUM_VERTICES];
+        let weights = vec! [1.0f32; 3 * 3];
+        let output = conv2d(&input, 1, 1, 1, 3, &weights, &[0.0]);
+
+        assert_eq!(output[0], 4.0);  // corner
+        assert_eq!(output[1], 6.0);  // edge
+        assert_eq!(output[20], 9.0);  // center
+        assert_eq!(output[360], 4.0);  // corner
+    }
+
+    #[test]
+    fn linear_column_major() {
+        let weights = [  // 2x3 in column-major order
+            1.0f32, 4.0f32,
+            2.0f32, 5.0f32,
+            3.0f32, 6.0f32
+        ];
+        let input = [1.0f32, 1.0f32, 2.0f32];
+        let output = linear(&input, 1, 2, 3, &weights, &[0.5, -0.5]);
+
+        assert_eq!(output, vec! [9.5, 20.5]);
+    }
+
+    #[test]
+    fn softmax_instances() {
+        let mut values = [1.0f32, 1.0f32, 0.0f32, 1000.0f32];
+
+        softmax(&mut values, 2);
+
+        assert_eq!(values[0], 0.5);
+        assert_eq!(values[1], 0.5);
+        assert_eq!(values[2], 0.0);
+        assert_eq!(values[3], 1.0);
+    }
+}
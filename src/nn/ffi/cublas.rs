@@ -64,8 +64,9 @@ pub enum Operation {
 
 pub type Handle = *const c_void;
 
-#[link(name = "cublas")]
-extern {
+dynamic_extern! {
+    ["libcublas.so", "libcublas.so.10.0", "libcublas.so.9.2", "libcublas.so.9.1", "libcublas.so.9.0", "libcublas.so.8.0"];
+
     pub fn cublasCreate_v2(handle: *mut Handle) -> Status;
     pub fn cublasDestroy_v2(handle: Handle) -> Status;
 
@@ -118,9 +118,9 @@ pub enum MemcpyKind {
 pub type Event = *const c_void;
 pub type Stream = *const c_void;
 
-#[link(name = "cuda")]
-#[link(name = "cudart")]
-extern {
+dynamic_extern! {
+    ["libcudart.so", "libcudart.so.10.0", "libcudart.so.9.2", "libcudart.so.9.1", "libcudart.so.9.0", "libcudart.so.8.0"];
+
     pub fn cudaFree(devPtr: *const c_void) -> Error;
     pub fn cudaMalloc(devPtr: *mut *mut c_void, size: usize) -> Error;
     pub fn cudaMemcpy(dst: *mut c_void, src: *const c_void, count: usize, kind: MemcpyKind) -> Error;
@@ -128,6 +128,7 @@ extern {
 
     #[cfg(feature = "trace-cuda")]
     pub fn cudaDeviceSynchronize() -> Error;
+    pub fn cudaGetDeviceCount(count: *mut c_int) -> Error;
     pub fn cudaRuntimeGetVersion(version: *mut c_int) -> Error;
     pub fn cudaDeviceGetAttribute(value: *mut c_int, attr: DeviceAttr, device: c_int) -> Error;
 
@@ -161,8 +161,9 @@ pub type Handle = *const c_void;
 pub type OpTensorDescriptor = *const c_void;
 pub type TensorDescriptor = *const c_void;
 
-#[link(name = "cudnn")]
-extern {
+dynamic_extern! {
+    ["libcudnn.so", "libcudnn.so.7", "libcudnn.so.6"];
+
     pub fn cudnnCreate(handle: *mut Handle) -> Status;
     pub fn cudnnDestroy(handle: Handle) -> Status;
 
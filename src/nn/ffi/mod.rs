@@ -12,6 +12,96 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use libc::{self, c_void};
+use std::ffi::CString;
+
+/// A shared library that has been loaded at runtime, so that the CUDA
+/// libraries are only required if the user wants to run on the GPU.
+pub struct Library {
+    handle: *mut c_void
+}
+
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    /// Returns the first of the given shared libraries that could be
+    /// loaded, or `None` if none of them could be loaded.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `names` - the file names of the shared library, in order of preference
+    /// 
+    pub fn open(names: &[&str]) -> Option<Library> {
+        names.iter()
+            .filter_map(|name| {
+                let name = CString::new(*name).unwrap();
+                let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_LAZY) };
+
+                if handle.is_null() {
+                    None
+                } else {
+                    Some(Library { handle })
+                }
+            })
+            .next()
+    }
+
+    /// Returns the address of the given symbol in this library.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `name` - the name of the symbol
+    /// 
+    pub fn symbol(&self, name: &str) -> *mut c_void {
+        let c_name = CString::new(name).unwrap();
+        let symbol = unsafe { libc::dlsym(self.handle, c_name.as_ptr()) };
+
+        assert!(!symbol.is_null(), "could not find the symbol -- {}", name);
+        symbol
+    }
+}
+
+/// Declare the given functions as being provided by the first shared
+/// library in the given list that can be loaded, and generate an
+/// `is_loaded` function that returns whether any of them could be loaded.
+/// Each symbol is looked up the first time its function is called.
+macro_rules! dynamic_extern {
+    (
+        [$($library:expr),*];
+        $(
+            $(#[$meta:meta])*
+            pub fn $name:ident($($arg:ident: $arg_ty:ty),* $(,)*) -> $ret:ty;
+        )*
+    ) => {
+        lazy_static! {
+            static ref LIBRARY: Option<::nn::ffi::Library> = ::nn::ffi::Library::open(&[$($library),*]);
+        }
+
+        /// Returns whether the shared library could be loaded.
+        pub fn is_loaded() -> bool {
+            LIBRARY.is_some()
+        }
+
+        $(
+            $(#[$meta])*
+            #[allow(non_snake_case)]
+            pub unsafe fn $name($($arg: $arg_ty),*) -> $ret {
+                lazy_static! {
+                    static ref SYMBOL: usize = match *LIBRARY {
+                        Some(ref library) => library.symbol(stringify!($name)) as usize,
+                        None => panic!("could not load the shared library for -- {}", stringify!($name))
+                    };
+                }
+
+                let f: unsafe extern "C" fn($($arg_ty),*) -> $ret = ::std::mem::transmute(*SYMBOL);
+
+                f($($arg),*)
+            }
+        )*
+    }
+}
+
 pub mod cublas;
 pub mod cuda;
 pub mod cudnn;
@@ -32,6 +122,18 @@ mod tests {
     use nn::ffi::*;
     use util::types::*;
 
+    #[test]
+    fn open_missing_library() {
+        assert!(Library::open(&["libdream_go_does_not_exist.so"]).is_none());
+    }
+
+    #[test]
+    fn open_library() {
+        let library = Library::open(&["libdream_go_does_not_exist.so", "libc.so.6"]).unwrap();
+
+        assert!(!library.symbol("strlen").is_null());
+    }
+
     #[test]
     fn sgemm() {
         let mut handle: cublas::Handle = ptr::null_mut();
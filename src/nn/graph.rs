@@ -16,6 +16,7 @@ use libc::c_void;
 use std::collections::HashMap;
 use std::ptr;
 
+use nn::architecture::Architecture;
 use nn::ffi::cublas;
 use nn::ffi::cuda;
 use nn::ffi::cudnn;
@@ -64,6 +65,9 @@ pub trait Graph {
 
     /// Returns the size (in bytes) of the maximum workspace needed.
     fn get_workspace_size(&self) -> usize;
+
+    /// Returns the architecture of the network in this graph.
+    fn get_architecture(&self) -> Architecture;
 }
 
 pub trait Ops<G: Graph> {
@@ -630,10 +634,12 @@ impl Ops<Workspace> for Runtime {
 
 pub struct Builder {
     pub(super) tensors: HashMap<String, Tensor>,
+    pub(super) architecture: Architecture
 }
 
 pub struct Workspace {
     pub(super) tensors: HashMap<String, Tensor>,
+    pub(super) architecture: Architecture,
 
     // handles for cuBLAS and cuDNN
     pub(super) handle_blas: cublas::Handle,
@@ -684,6 +690,10 @@ impl Graph for Builder {
     fn get_workspace_size(&self) -> usize {
         0
     }
+
+    fn get_architecture(&self) -> Architecture {
+        self.architecture
+    }
 }
 
 impl Graph for Workspace {
@@ -722,12 +732,17 @@ impl Graph for Workspace {
     fn get_workspace_size(&self) -> usize {
         self.workspace_size
     }
+
+    fn get_architecture(&self) -> Architecture {
+        self.architecture
+    }
 }
 
 impl Builder {
-    pub fn new(tensors: HashMap<String, Tensor>) -> Builder {
+    pub fn new(architecture: Architecture, tensors: HashMap<String, Tensor>) -> Builder {
         let mut g = Builder {
-            tensors: tensors
+            tensors: tensors,
+            architecture: architecture
         };
 
         // add the placeholder tensor that represents the input
@@ -739,7 +754,7 @@ impl Builder {
 
         g.tensors.insert("00_input/output:0".to_string(), Tensor::default()
             .set_data_type(data_type, format)
-            .set_shape(vec! [0, 32, 19, 19])
+            .set_shape(vec! [0, architecture.num_features as i32, 19, 19])
             .set_scale(1.0)
             .clone()
         );
@@ -792,6 +807,7 @@ impl Builder {
     pub fn get_workspace(&self, batch_size: usize) -> Workspace {
         let mut w = Workspace {
             tensors: HashMap::new(),
+            architecture: self.architecture,
 
             handle_blas: ptr::null(),
             handle_dnn: ptr::null(),
@@ -923,19 +939,27 @@ impl Drop for Workspace {
     }
 }
 
-/// 
+/// Applies the residual tower of the network to the input features, the
+/// output is stored in the `residual_1` slot.
+///
+/// # Arguments
+///
+/// * `graph` - the graph to apply the tower to
+///
 pub fn tower<O: Ops<G>, G: Graph>(graph: &mut G) {
+    let architecture = graph.get_architecture();
+    let (c, f) = (architecture.num_channels as i32, architecture.num_features as i32);
     let batch_size = graph.get_batch_size();
-    let input = graph.get_input(Some(4 * batch_size * 11552));
-    let residual_1 = O::get_slot(graph, "residual_1", 4 * batch_size * 46208);
-    let residual_2 = O::get_slot(graph, "residual_2", 4 * batch_size * 46208);
+    let input = graph.get_input(Some(4 * batch_size * architecture.features_size()));
+    let residual_1 = O::get_slot(graph, "residual_1", 4 * batch_size * architecture.tower_size());
+    let residual_2 = O::get_slot(graph, "residual_2", 4 * batch_size * architecture.tower_size());
     let workspace_size = graph.get_workspace_size();
     let workspace_1 = O::get_slot(graph, "workspace_1", workspace_size);
 
     #[cfg(feature = "trace-cuda")]
     eprintln!("00_input/output:0\n= {:?}", Tensor::default()
         .set_data_type(cudnn::DataType::Int8, cudnn::TensorFormat::NHWC)
-        .set_shape(vec! [batch_size as i32, 32, 19, 19])
+        .set_shape(vec! [batch_size as i32, f, 19, 19])
         .set_scale(1.0)
         .fmt_ptr(input)
     );
@@ -943,119 +967,139 @@ pub fn tower<O: Ops<G>, G: Graph>(graph: &mut G) {
     O::convolution(
         graph,
         "00_input/output:0".to_string(), input,
-        128, 32, 3, 3,
+        c, f, 3, 3,
         "01_upsample/weights:0".to_string(),
         "01_upsample/offset:0".to_string(),
         "01_upsample/output:0".to_string(), residual_1,
         workspace_1, workspace_size
     );
 
-    for i in 2..21 {
-        let input_name = if i == 2 {
+    for i in 0..architecture.num_blocks {
+        let input_name = if i == 0 {
             "01_upsample/output:0".to_string()
         } else {
-            format!("{:02}_residual/output_2:0", i - 1)
+            format!("{}/output_2:0", architecture.residual(i - 1))
         };
+        let residual = architecture.residual(i);
 
         O::residual_block(
             graph,
             input_name, residual_1,
-            128, 128, 3, 3,
-            format!("{:02}_residual/weights_1:0", i),
-            format!("{:02}_residual/weights_2:0", i),
-            format!("{:02}_residual/offset_1:0", i),
-            format!("{:02}_residual/offset_2:0", i),
-            format!("{:02}_residual/output_1:0", i), residual_2,
-            format!("{:02}_residual/output_2:0", i), residual_1,
+            c, c, 3, 3,
+            format!("{}/weights_1:0", residual),
+            format!("{}/weights_2:0", residual),
+            format!("{}/offset_1:0", residual),
+            format!("{}/offset_2:0", residual),
+            format!("{}/output_1:0", residual), residual_2,
+            format!("{}/output_2:0", residual), residual_1,
             workspace_1, workspace_size
         );
     }
 }
 
-/// 
+/// Applies the policy head of the network to the output of the tower, the
+/// output is stored in the policy output.
+///
+/// # Arguments
+///
+/// * `graph` - the graph to apply the policy head to
+///
 pub fn policy<O: Ops<G>, G: Graph>(graph: &mut G) {
+    let architecture = graph.get_architecture();
+    let (c, p) = (architecture.num_channels as i32, architecture.num_policy_channels as i32);
+    let scope = architecture.policy();
     let batch_size = graph.get_batch_size();
-    let residual_1 = O::get_slot(graph, "residual_1", 4 * batch_size * 46208);
+    let residual_1 = O::get_slot(graph, "residual_1", 4 * batch_size * architecture.tower_size());
     let policy_1 = O::get_slot(graph, "policy_1", 4 * batch_size * 362);
-    let policy_out = graph.get_policy_output(Some(4 * batch_size * 722));
+    let policy_out = graph.get_policy_output(Some(4 * batch_size * ::std::cmp::max(361 * p as usize, 362)));
     let workspace_size = graph.get_workspace_size();
     let workspace_1 = O::get_slot(graph, "workspace_1", workspace_size);
 
     O::convolution(
         graph,
-        "20_residual/output_2:0".to_string(), residual_1,
-        2, 128, 1, 1,
-        "21p_policy/downsample:0".to_string(),
-        "21p_policy/offset:0".to_string(),
-        "21p_policy/output_1:0".to_string(), policy_out,
+        architecture.tower_output(), residual_1,
+        p, c, 1, 1,
+        format!("{}/downsample:0", scope),
+        format!("{}/offset:0", scope),
+        format!("{}/output_1:0", scope), policy_out,
         workspace_1, workspace_size
     );
 
     O::linear(
         graph,
-        "21p_policy/output_1:0".to_string(), policy_out,
-        362, 722,
-        "21p_policy/weights:0".to_string(),
-        "21p_policy/bias:0".to_string(),
-        "21p_policy/output_2:0".to_string(), policy_1,
+        format!("{}/output_1:0", scope), policy_out,
+        362, 361 * p,
+        format!("{}/weights:0", scope),
+        format!("{}/bias:0", scope),
+        format!("{}/output_2:0", scope), policy_1,
         workspace_1, workspace_size
     );
 
     O::softmax(
         graph,
-        "21p_policy/output_2:0".to_string(), policy_1,
-        "21p_policy/output_3:0".to_string(), policy_out
+        format!("{}/output_2:0", scope), policy_1,
+        format!("{}/output_3:0", scope), policy_out
     );
 }
 
-/// 
+/// Applies the value head of the network to the output of the tower, the
+/// output is stored in the value output.
+///
+/// # Arguments
+///
+/// * `graph` - the graph to apply the value head to
+///
 pub fn value<O: Ops<G>, G: Graph>(graph: &mut G) {
+    let architecture = graph.get_architecture();
+    let (c, v) = (architecture.num_channels as i32, architecture.num_value_channels as i32);
+    let h = architecture.num_value_hidden as i32;
+    let scope = architecture.value();
     let batch_size = graph.get_batch_size();
-    let residual_1 = O::get_slot(graph, "residual_1", 4 * batch_size * 46208);
-    let value_1 = O::get_slot(graph, "value_1", 4 * batch_size * 361);
-    let value_out = graph.get_value_output(Some(4 * batch_size * 256));
+    let residual_1 = O::get_slot(graph, "residual_1", 4 * batch_size * architecture.tower_size());
+    let value_1 = O::get_slot(graph, "value_1", 4 * batch_size * 361 * v as usize);
+    let value_out = graph.get_value_output(Some(4 * batch_size * h as usize));
     let workspace_size = graph.get_workspace_size();
     let workspace_2 = O::get_slot(graph, "workspace_2", workspace_size);
 
     O::convolution(
         graph,
-        "20_residual/output_2:0".to_string(), residual_1,
-        1, 128, 1, 1,
-        "21v_value/downsample:0".to_string(),
-        "21v_value/offset:0".to_string(),
-        "21v_value/output_1:0".to_string(), value_1,
+        architecture.tower_output(), residual_1,
+        v, c, 1, 1,
+        format!("{}/downsample:0", scope),
+        format!("{}/offset:0", scope),
+        format!("{}/output_1:0", scope), value_1,
         workspace_2, workspace_size
     );
 
     O::linear(
         graph,
-        "21v_value/output_1:0".to_string(), value_1,
-        256, 361,
-        "21v_value/weights_1:0".to_string(),
-        "21v_value/bias_1:0".to_string(),
-        "21v_value/output_2:0".to_string(), value_out,
+        format!("{}/output_1:0", scope), value_1,
+        h, 361 * v,
+        format!("{}/weights_1:0", scope),
+        format!("{}/bias_1:0", scope),
+        format!("{}/output_2:0", scope), value_out,
         workspace_2, workspace_size
     );
 
     O::relu(
         graph,
-        "21v_value/output_2:0".to_string(), value_out,
-        "21v_value/output_3:0".to_string(), value_out
+        format!("{}/output_2:0", scope), value_out,
+        format!("{}/output_3:0", scope), value_out
     );
 
     O::linear(
         graph,
-        "21v_value/output_3:0".to_string(), value_out,
-        1, 256,
-        "21v_value/weights_2:0".to_string(),
-        "21v_value/bias_2:0".to_string(),
-        "21v_value/output_4:0".to_string(), value_1,
+        format!("{}/output_3:0", scope), value_out,
+        1, h,
+        format!("{}/weights_2:0", scope),
+        format!("{}/bias_2:0", scope),
+        format!("{}/output_4:0", scope), value_1,
         workspace_2, workspace_size
     );
 
     O::tanh(
         graph,
-        "21v_value/output_4:0".to_string(), value_1,
-        "21v_value/output_5:0".to_string(), value_out
+        format!("{}/output_4:0", scope), value_1,
+        format!("{}/output_5:0", scope), value_out
     );
 }
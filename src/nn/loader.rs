@@ -1,4 +1,4 @@
-// Copyright 2017 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
 //
 // Licensed under the Apache License, Version 2.0 (the "License");
 // you may not use this file except in compliance with the License.
@@ -13,137 +13,664 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{self, Read};
 use std::path::Path;
-use std::char;
+use std::str;
 
+use nn::architecture::Architecture;
 use nn::ops::Tensor;
 use util::b85;
+use util::binary;
 use util::types::*;
 
-struct JsonTensor {
-    scale: f32,
-    values: Option<Box<[f16]>>
+/// The bytes that every binary weights file starts with.
+pub const MAGIC: &'static [u8; 4] = b"DGNW";
+
+/// The version of the binary weights format.
+pub const VERSION: u32 = 1;
+
+/// The data type of a tensor in a binary weights file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataType {
+    Half = 0,
+    Single = 1
 }
 
-/// Step the iterator forward until the character given `stop` character is
-/// encountered. The character `stop` is also skipped.
-/// 
-/// # Arguments
-/// 
-/// * `iter` - the iterator to step forward
-/// * `stop` - the character to step until
-/// 
-fn skip_until<I>(iter: &mut I, stop: char) -> String
-    where I: Iterator<Item=u8>
-{
-    let mut out: String = String::new();
-
-    loop {
-        if let Some(ch) = iter.next() {
-            let ch = char::from_u32(ch as u32).unwrap();
+impl DataType {
+    /// Returns the data type with the given identifier, if it is known.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the identifier as stored in the file
+    ///
+    fn from_id(id: u8) -> Option<DataType> {
+        match id {
+            0 => Some(DataType::Half),
+            1 => Some(DataType::Single),
+            _ => None
+        }
+    }
 
-            if ch == stop {
-                break
-            }
+    /// Returns the size (in bytes) of a single element of this type.
+    fn size(&self) -> usize {
+        match *self {
+            DataType::Half => 2,
+            DataType::Single => 4
+        }
+    }
+}
 
-            out.push(ch);
-        } else {
-            break
+/// The reasons why loading network weights can fail.
+#[derive(Debug)]
+pub enum LoadError {
+    /// There are no network weights at any of the given paths.
+    NotFound(Vec<String>),
+
+    /// The file could not be read.
+    Io(io::Error),
+
+    /// The file ended before the value at the given offset.
+    Truncated(usize),
+
+    /// The file is written in a version of the format that is not supported.
+    UnsupportedVersion(u32),
+
+    /// The checksum stored in the file does not match its content.
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    /// The file is malformed at the given offset.
+    Malformed { offset: usize, reason: String },
+
+    /// The tensor with the given name is malformed.
+    BadTensor { name: String, reason: String },
+
+    /// The declared architecture is not valid.
+    BadArchitecture(String)
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::NotFound(ref paths) => write!(f, "no network weights found at {}", paths.join(", ")),
+            LoadError::Io(ref err) => write!(f, "{}", err),
+            LoadError::Truncated(offset) => write!(f, "file is truncated at offset {}", offset),
+            LoadError::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            LoadError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum mismatch, expected {:08x} but was {:08x}", expected, actual)
+            },
+            LoadError::Malformed { offset, ref reason } => write!(f, "{} at offset {}", reason, offset),
+            LoadError::BadTensor { ref name, ref reason } => write!(f, "{} -- {}", reason, name),
+            LoadError::BadArchitecture(ref reason) => write!(f, "{}", reason)
         }
     }
+}
 
-    out
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        "could not load network weights"
+    }
 }
 
-/// An iterator that parse entries with the following format:
-/// 
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+/// The architecture and the weights of a network, as read from a file.
+pub type Weights = (Architecture, HashMap<String, Tensor>);
+
+struct JsonTensor {
+    scale: f32,
+    values: Option<Box<[f16]>>,
+    properties: HashMap<String, String>
+}
+
+/// A scanner that parse entries with the following format:
+///
 /// `"name": { "t": "...", v: "..." }`
-/// 
-struct JsonEntryIter<I: Iterator<Item=u8>> {
-    iter: I
+///
+struct JsonEntryIter<'a> {
+    bytes: &'a [u8],
+    position: usize
 }
 
-impl<I: Iterator<Item=u8>> Iterator for JsonEntryIter<I> {
-    type Item = (String, JsonTensor);
+impl<'a> JsonEntryIter<'a> {
+    /// Step the scanner forward until the given `stop` character is
+    /// encountered, and returns the characters that were skipped. The
+    /// character `stop` is also skipped. If the end of the file was reached
+    /// before `stop` was found then the second element is false.
+    ///
+    /// # Arguments
+    ///
+    /// * `stop` - the character to step until
+    ///
+    fn skip_until(&mut self, stop: char) -> (String, bool) {
+        let mut out: String = String::new();
+
+        while self.position < self.bytes.len() {
+            let ch = self.bytes[self.position] as char;
+            self.position += 1;
+
+            if ch == stop {
+                return (out, true);
+            }
+
+            out.push(ch);
+        }
+
+        (out, false)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Step the scanner forward until the given `stop` character is
+    /// encountered, and returns the characters that were skipped. It is an
+    /// error if the end of the file is reached before `stop` is found.
+    ///
+    /// # Arguments
+    ///
+    /// * `stop` - the character to step until
+    ///
+    fn expect(&mut self, stop: char) -> Result<String, LoadError> {
+        match self.skip_until(stop) {
+            (out, true) => Ok(out),
+            (_, false) => Err(LoadError::Truncated(self.position))
+        }
+    }
+
+    /// Returns the next entry in the file, or `None` if there are no more
+    /// entries.
+    fn next_entry(&mut self) -> Result<Option<(String, JsonTensor)>, LoadError> {
         // skip until the quote before the name
-        skip_until(&mut self.iter, '"');
+        if !self.skip_until('"').1 {
+            return Ok(None);
+        }
 
-        let name = skip_until(&mut self.iter, '"');
+        let name = self.expect('"')?;
         if name.is_empty() {
-            return None;
+            return Ok(None);
         }
 
         // skip until the next `{` and then parse the interior of the
         // object by iterating over the properties
-        skip_until(&mut self.iter, '{');
+        self.expect('{')?;
 
         let mut tensor = JsonTensor {
             scale: 1.0,
-            values: None
+            values: None,
+            properties: HashMap::new()
         };
 
         loop {
-            skip_until(&mut self.iter, '"');
-            let key = skip_until(&mut self.iter, '"');
+            self.expect('"')?;
+            let key = self.expect('"')?;
 
-            skip_until(&mut self.iter, '"');
-            let value = skip_until(&mut self.iter, '"');
+            self.expect('"')?;
+            let offset = self.position;
+            let value = self.expect('"')?;
+            let decoded = if key == "s" || key == "v" {
+                b85::decode::<f16>(&value).filter(|array| !array.is_empty()).ok_or_else(|| {
+                    LoadError::Malformed { offset: offset, reason: format!("invalid value of {} in {}", key, name) }
+                })?
+            } else {
+                vec! [].into_boxed_slice()
+            };
 
             if key == "s" {
-                let array = b85::decode::<f16>(&value).unwrap();
+                tensor.scale = f32::from(decoded[0]);
 
-                tensor.scale = f32::from(array[0]);
+                if !(tensor.scale > 0.0) {
+                    return Err(bad_tensor(&name, "non-positive scale"));
+                }
             } else if key == "v" {
-                tensor.values = b85::decode::<f16>(&value);
+                tensor.values = Some(decoded);
+            } else if !key.is_empty() {
+                tensor.properties.insert(key, value);
             } else {
                 break
             }
 
             // check if the object terminated
-            let more = skip_until(&mut self.iter, ',');
+            let (more, _) = self.skip_until(',');
             if more.contains('}') {
                 break
             }
         };
 
-        Some((name, tensor))
+        Ok(Some((name, tensor)))
     }
 }
 
-/// Load all tensors in the given file and returns a map from
-/// their name to description. If we failed to load any tensors
-/// from the given file then `None` is returned.
-/// 
+/// Returns the architecture and tensors stored in the given legacy JSON file.
+///
+/// The architecture is stored as an entry named `architecture`, whose
+/// properties are the fields of `Architecture`. Files without such an entry
+/// are assumed to have the default architecture.
+///
 /// # Arguments
-/// 
-/// * `path` -
-/// 
-pub fn load(path: &Path) -> Option<HashMap<String, Tensor>> {
-    if let Ok(file) = File::open(path) {
-        let mut out: HashMap<String, Tensor> = HashMap::new();
-        let mut iter = JsonEntryIter {
-            iter: BufReader::new(file).bytes().map(|ch| ch.unwrap())
-        };
+///
+/// * `bytes` - the content of the file
+///
+fn load_json(bytes: &[u8]) -> Result<Weights, LoadError> {
+    let mut out: HashMap<String, Tensor> = HashMap::new();
+    let mut architecture = Architecture::default();
+    let mut iter = JsonEntryIter { bytes: bytes, position: 0 };
 
-        for (name, mut json) in iter {
-            debug_assert!(json.scale > 0.0);
+    while let Some((name, mut json)) = iter.next_entry()? {
+        if name == "architecture" {
+            architecture = Architecture::from_properties(&json.properties)?;
+            continue;
+        }
 
-            let mut t = Tensor::default();
+        let mut t = Tensor::default();
 
-            t.set_scale(json.scale);
-            if let Some(host) = json.values.take() {
-                t.set_host(host);
-            }
+        t.set_scale(json.scale);
+        if let Some(host) = json.values.take() {
+            t.set_host(host);
+        }
+
+        out.insert(name, t);
+    }
 
-            out.insert(name, t);
+    Ok((architecture, out))
+}
+
+/// A cursor over the content of a binary weights file, that keeps track of
+/// the current offset so that it can be reported in errors.
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> BinaryReader<'a> {
+    /// Returns the next `n` bytes, or an error if the file is too short.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - the number of bytes to return
+    ///
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadError> {
+        if self.bytes.len() - self.position < n {
+            Err(LoadError::Truncated(self.position))
+        } else {
+            let out = &self.bytes[self.position..(self.position + n)];
+            self.position += n;
+
+            Ok(out)
         }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, LoadError> {
+        Ok(binary::read_u16(&mut self.take(2)?)?)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, LoadError> {
+        Ok(binary::read_u32(&mut self.take(4)?)?)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, LoadError> {
+        Ok(binary::read_u64(&mut self.take(8)?)?)
+    }
+
+    fn read_f32(&mut self) -> Result<f32, LoadError> {
+        Ok(binary::read_f32(&mut self.take(4)?)?)
+    }
+}
+
+/// Returns an error that indicates that the tensor with the given name is
+/// malformed.
+///
+/// # Arguments
+///
+/// * `name` - the name of the tensor
+/// * `reason` - what is wrong with the tensor
+///
+fn bad_tensor(name: &str, reason: &str) -> LoadError {
+    LoadError::BadTensor { name: name.to_string(), reason: reason.to_string() }
+}
+
+/// The index entry of a single tensor in a binary weights file.
+struct BinaryEntry {
+    name: String,
+    data_type: DataType,
+    scale: f32,
+    data_offset: usize,
+    data_size: usize
+}
+
+/// Returns the architecture and tensors stored in the given binary file. The
+/// file has the following layout, where every number is little endian:
+///
+/// * the magic bytes `DGNW`, and the version as an `u32`
+/// * the architecture as six `u32`, in the same order as the fields of
+///   `Architecture`
+/// * the number of tensors as an `u32`, followed by the index entry of each
+///   tensor: its name as an `u16` length and UTF-8 bytes, its data type as
+///   an `u8`, its scale as an `f32`, its rank as an `u8` followed by each
+///   dimension as an `u32`, and the offset and size (in bytes) of its values
+///   as two `u64`
+/// * the values of every tensor
+/// * the CRC-32 of everything before it as an `u32`
+///
+/// The header and the index are checked against the length of the file
+/// before the checksum, so that a truncated file is reported as such.
+///
+/// # Arguments
+///
+/// * `bytes` - the content of the file
+///
+fn load_binary(bytes: &[u8]) -> Result<Weights, LoadError> {
+    let mut f = BinaryReader { bytes: bytes, position: MAGIC.len() };
+    let version = f.read_u32()?;
+
+    if version != VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    } else if bytes.len() < f.position + 4 {
+        return Err(LoadError::Truncated(bytes.len()));
+    }
+
+    let end = bytes.len() - 4;
+
+    f.bytes = &bytes[..end];
+
+    let mut properties = HashMap::new();
 
-        Some(out)
+    for &key in ["num_blocks", "num_channels", "num_features", "num_policy_channels", "num_value_channels", "num_value_hidden"].iter() {
+        properties.insert(key.to_string(), f.read_u32()?.to_string());
+    }
+
+    let architecture = Architecture::from_properties(&properties)?;
+    let shapes = architecture.weights().into_iter().collect::<HashMap<String, Vec<usize>>>();
+    let num_tensors = f.read_u32()?;
+    let mut entries: Vec<BinaryEntry> = vec! [];
+
+    for _ in 0..num_tensors {
+        let name_offset = f.position;
+        let name_len = f.read_u16()? as usize;
+        let name = str::from_utf8(f.take(name_len)?)
+            .map_err(|_| LoadError::Malformed { offset: name_offset, reason: "invalid tensor name".to_string() })?
+            .to_string();
+        let data_type = DataType::from_id(f.read_u8()?)
+            .ok_or_else(|| bad_tensor(&name, "unknown data type"))?;
+        let scale = f.read_f32()?;
+        let rank = f.read_u8()?;
+        let mut dims = vec! [];
+
+        for _ in 0..rank {
+            dims.push(f.read_u32()? as usize);
+        }
+
+        let size_offset = f.position;
+        let data_offset = f.read_u64()? as usize;
+        let data_size = f.read_u64()? as usize;
+        let num_bytes = dims.iter()
+            .fold(Some(data_type.size()), |acc, &dim| acc.and_then(|acc| acc.checked_mul(dim)))
+            .ok_or_else(|| {
+                LoadError::Malformed { offset: size_offset, reason: format!("too many elements in {}", name) }
+            })?;
+
+        if !(scale > 0.0) {
+            return Err(bad_tensor(&name, "non-positive scale"));
+        } else if data_offset > end || data_size > end - data_offset {
+            return Err(LoadError::Truncated(end));
+        } else if data_size != 0 && data_size != num_bytes {
+            return Err(bad_tensor(&name, "size of the values does not match the shape"));
+        } else if data_size != 0 && shapes.get(&name).map_or(false, |shape| *shape != dims) {
+            let reason = format!("has shape {:?}, expected {:?}", dims, shapes[&name]);
+
+            return Err(LoadError::BadTensor { name: name, reason: reason });
+        } else if entries.iter().any(|entry| entry.name == name) {
+            return Err(bad_tensor(&name, "duplicate tensor"));
+        }
+
+        entries.push(BinaryEntry {
+            name: name,
+            data_type: data_type,
+            scale: scale,
+            data_offset: data_offset,
+            data_size: data_size
+        });
+    }
+
+    // verify the checksum before looking at the values, so that a corrupted
+    // file is reported as such
+    let expected = binary::read_u32(&mut &bytes[end..])?;
+    let actual = binary::crc32(&bytes[..end]);
+
+    if expected != actual {
+        return Err(LoadError::ChecksumMismatch { expected: expected, actual: actual });
+    }
+
+    let mut out: HashMap<String, Tensor> = HashMap::new();
+
+    for entry in entries.into_iter() {
+        let mut t = Tensor::default();
+        t.set_scale(entry.scale);
+
+        if entry.data_size > 0 {
+            let data = &bytes[entry.data_offset..(entry.data_offset + entry.data_size)];
+            let host = match entry.data_type {
+                DataType::Half => {
+                    data.chunks(2)
+                        .map(|x| f16::from_bits(binary::read_u16(&mut &x[..]).unwrap()))
+                        .collect::<Vec<f16>>()
+                },
+                DataType::Single => {
+                    data.chunks(4)
+                        .map(|x| f16::from(binary::read_f32(&mut &x[..]).unwrap()))
+                        .collect::<Vec<f16>>()
+                }
+            };
+
+            t.set_host(host.into_boxed_slice());
+        }
+
+        out.insert(entry.name, t);
+    }
+
+    Ok((architecture, out))
+}
+
+/// Returns the architecture and all tensors stored in the given file, which
+/// can be either in the binary format or in the legacy JSON format, together
+/// with the CRC-32 of the whole file. It is an error if the tensors does not
+/// match the declared architecture.
+///
+/// # Arguments
+///
+/// * `path` - the path to the file
+///
+pub fn load(path: &Path) -> Result<(Architecture, HashMap<String, Tensor>, u32), LoadError> {
+    let mut bytes = vec! [];
+
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let (architecture, tensors) = if bytes.starts_with(MAGIC) {
+        load_binary(&bytes)?
     } else {
-        None
+        load_json(&bytes)?
+    };
+
+    architecture.check(&tensors)?;
+
+    Ok((architecture, tensors, binary::crc32(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use nn::loader::*;
+
+    /// Returns a binary weights file that contains the given tensors, where
+    /// each tensor is a vector.
+    fn to_binary(architecture: &Architecture, tensors: &[(&str, Vec<f32>)]) -> Vec<u8> {
+        let tensors = tensors.iter()
+            .map(|&(name, ref values)| (name, vec! [values.len() as u32], values.clone()))
+            .collect::<Vec<_>>();
+
+        to_binary_with_shapes(architecture, &tensors)
+    }
+
+    /// Returns a binary weights file that contains the given tensors, with
+    /// the given shapes.
+    fn to_binary_with_shapes(architecture: &Architecture, tensors: &[(&str, Vec<u32>, Vec<f32>)]) -> Vec<u8> {
+        let fields = [
+            architecture.num_blocks, architecture.num_channels, architecture.num_features,
+            architecture.num_policy_channels, architecture.num_value_channels, architecture.num_value_hidden
+        ];
+        let mut out = vec! [];
+
+        out.extend_from_slice(MAGIC);
+        binary::write_u32(&mut out, VERSION).unwrap();
+        for &field in fields.iter() {
+            binary::write_u32(&mut out, field as u32).unwrap();
+        }
+
+        binary::write_u32(&mut out, tensors.len() as u32).unwrap();
+
+        let index_size = tensors.iter().map(|&(name, ref shape, _)| 2 + name.len() + 1 + 4 + 1 + 4 * shape.len() + 16).sum::<usize>();
+        let mut data_offset = out.len() + index_size;
+
+        for &(name, ref shape, ref values) in tensors.iter() {
+            binary::write_u16(&mut out, name.len() as u16).unwrap();
+            out.extend_from_slice(name.as_bytes());
+            binary::write_u8(&mut out, DataType::Half as u8).unwrap();
+            binary::write_f32(&mut out, 1.0).unwrap();
+            binary::write_u8(&mut out, shape.len() as u8).unwrap();
+            for &dim in shape.iter() {
+                binary::write_u32(&mut out, dim).unwrap();
+            }
+            binary::write_u64(&mut out, data_offset as u64).unwrap();
+            binary::write_u64(&mut out, 2 * values.len() as u64).unwrap();
+
+            data_offset += 2 * values.len();
+        }
+
+        for &(_, _, ref values) in tensors.iter() {
+            for &value in values.iter() {
+                binary::write_u16(&mut out, f16::from(value).to_bits()).unwrap();
+            }
+        }
+
+        let checksum = binary::crc32(&out);
+        binary::write_u32(&mut out, checksum).unwrap();
+        out
+    }
+
+    #[test]
+    fn binary() {
+        let bytes = to_binary(&Architecture::default(), &[("a:0", vec! [1.0, 2.0]), ("b:0", vec! [])]);
+        let (architecture, tensors) = load_binary(&bytes).unwrap();
+
+        assert_eq!(architecture, Architecture::default());
+        assert_eq!(tensors.len(), 2);
+        assert_eq!(tensors["a:0"].host.as_ref().map(|h| h.len()), Some(2));
+        assert_eq!(tensors["a:0"].host.as_ref().map(|h| f32::from(h[1])), Some(2.0));
+        assert!(tensors["b:0"].host.is_none());
+    }
+
+    #[test]
+    fn binary_errors() {
+        let bytes = to_binary(&Architecture::default(), &[("a:0", vec! [1.0, 2.0])]);
+
+        // flip a bit in the values
+        let mut corrupt = bytes.clone();
+        let n = corrupt.len();
+        corrupt[n - 6] ^= 1;
+
+        match load_binary(&corrupt) {
+            Err(LoadError::ChecksumMismatch { .. }) => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        // bump the version
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+
+        match load_binary(&newer) {
+            Err(LoadError::UnsupportedVersion(2)) => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        // cut the file in the middle of the index, or in the middle of the
+        // values
+        match load_binary(&bytes[..40]) {
+            Err(LoadError::Truncated(36)) => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        match load_binary(&bytes[..(n - 3)]) {
+            Err(LoadError::Truncated(_)) => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        // change the data type of the tensor, and update the checksum
+        let mut unknown = bytes[..(n - 4)].to_vec();
+        unknown[41] = 7;
+        let checksum = binary::crc32(&unknown);
+        binary::write_u32(&mut unknown, checksum).unwrap();
+
+        match load_binary(&unknown) {
+            Err(LoadError::BadTensor { ref name, .. }) if name == "a:0" => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        // a shape whose number of elements does not fit in an `usize`
+        let huge = to_binary_with_shapes(&Architecture::default(), &[("a:0", vec! [::std::u32::MAX; 3], vec! [1.0])]);
+
+        match load_binary(&huge) {
+            Err(LoadError::Malformed { ref reason, .. }) if reason.contains("a:0") => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn binary_shapes() {
+        let small = Architecture { num_blocks: 1, num_channels: 4, ..Architecture::default() };
+        let name = "01_upsample/weights:0";
+        let shape = vec! [4, small.num_features as u32, 3, 3];
+        let values = vec! [0.0; 36 * small.num_features];
+
+        assert!(load_binary(&to_binary_with_shapes(&small, &[(name, shape, values.clone())])).is_ok());
+
+        // the right number of elements, but the wrong shape
+        let transposed = vec! [small.num_features as u32, 4, 3, 3];
+
+        match load_binary(&to_binary_with_shapes(&small, &[(name, transposed, values.clone())])) {
+            Err(LoadError::BadTensor { name: ref actual, .. }) if actual == name => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        match load_binary(&to_binary(&small, &[(name, values)])) {
+            Err(LoadError::BadTensor { name: ref actual, .. }) if actual == name => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn json() {
+        let src = b"{\"a:0\": {\"v\": \"00000\"}, \"architecture\": {\"num_blocks\": \"6\"}}";
+        let (architecture, tensors) = load_json(src).unwrap();
+
+        assert_eq!(architecture.num_blocks, 6);
+        assert_eq!(tensors["a:0"].host.as_ref().map(|h| h.len()), Some(2));
+    }
+
+    #[test]
+    fn json_errors() {
+        match load_json(b"{\"a:0\": {\"s\": \"0") {
+            Err(LoadError::Truncated(_)) => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+
+        match load_json(b"{\"a:0\": {\"v\": \"0000\x80\"}}") {
+            Err(LoadError::Malformed { offset: 15, .. }) => {},
+            other => panic!("{:?}", other.map(|_| ()))
+        }
     }
 }
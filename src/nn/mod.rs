@@ -15,6 +15,8 @@
 use libc::{c_void};
 
 #[macro_use] pub mod ffi;
+mod architecture;
+mod cpu;
 mod graph;
 mod loader;
 mod network;
@@ -24,8 +26,24 @@ use self::ffi::cublas;
 use self::ffi::cuda;
 use self::ffi::cudnn;
 use self::graph::Graph;
-pub use self::graph::Workspace;
+pub use self::architecture::Architecture;
+pub use self::loader::LoadError;
 pub use self::network::{Network, WorkspaceGuard};
+use util::config;
+
+/// Returns whether the CUDA libraries could be loaded, and there is at
+/// least one CUDA device available.
+fn has_device() -> bool {
+    if !cuda::is_loaded() || !cublas::is_loaded() || !cudnn::is_loaded() {
+        return false;
+    }
+
+    let mut count: i32 = 0;
+
+    unsafe {
+        cuda::cudaGetDeviceCount(&mut count).is_ok() && count > 0
+    }
+}
 
 /// Returns the version of the CUDA Runtime library.
 fn runtime_version() -> i32 {
@@ -95,6 +113,13 @@ fn should_use_dp4a() -> bool {
     }
 }
 
+/// The supported backends for inference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Cuda,
+    Cpu
+}
+
 /// The supported types for inference.
 pub enum Type {
     Int8,
@@ -103,9 +128,20 @@ pub enum Type {
 }
 
 lazy_static! {
+    /// The backend to use for inference. This is the GPU unless the user
+    /// asked for the CPU, or there is no CUDA device available.
+    pub static ref BACKEND: Backend = if *config::USE_CPU || !has_device() {
+        Backend::Cpu
+    } else {
+        Backend::Cuda
+    };
+
     /// The type that is expected as input to `forward`. This is determined
-    /// based on the _Compute Capability_ of the users GPU.
-    pub static ref TYPE: Type = if should_use_half() || should_use_tensor_core() {
+    /// based on the _Compute Capability_ of the users GPU, or `Single` if
+    /// we are running on the CPU.
+    pub static ref TYPE: Type = if *BACKEND == Backend::Cpu {
+        Type::Single
+    } else if should_use_half() || should_use_tensor_core() {
         Type::Half
     } else if should_use_dp4a() {
         Type::Int8
@@ -114,6 +150,22 @@ lazy_static! {
     };
 }
 
+/// A pre-allocated workspace for one of the supported backends.
+pub enum Workspace {
+    Cuda(graph::Workspace),
+    Cpu(cpu::Workspace)
+}
+
+impl Workspace {
+    /// Returns the batch size of this workspace.
+    pub fn batch_size(&self) -> usize {
+        match *self {
+            Workspace::Cuda(ref workspace) => workspace.batch_size,
+            Workspace::Cpu(ref workspace) => workspace.batch_size
+        }
+    }
+}
+
 /// Returns the value and policy tensors obtained from a forward pass
 /// through the neural network.
 ///
@@ -122,12 +174,33 @@ lazy_static! {
 /// * `ws` - the workspace for the current thread
 /// * `features` - the input features
 ///
-pub fn forward<T: From<f32> + Clone, R: From<f32> + Clone>(
+pub fn forward<T, R>(
     workspace: &mut Workspace,
     features: &Vec<Box<[T]>>
+) -> (Vec<R>, Vec<Box<[R]>>)
+    where T: From<f32> + Clone, f32: From<T>, R: From<f32> + Clone
+{
+    match *workspace {
+        Workspace::Cuda(ref mut workspace) => forward_cuda(workspace, features),
+        Workspace::Cpu(ref mut workspace) => cpu::forward(workspace, features)
+    }
+}
+
+/// Returns the value and policy tensors obtained from a forward pass
+/// through the neural network on the GPU.
+///
+/// # Arguments
+///
+/// * `ws` - the workspace for the current thread
+/// * `features` - the input features
+///
+fn forward_cuda<T: From<f32> + Clone, R: From<f32> + Clone>(
+    workspace: &mut graph::Workspace,
+    features: &Vec<Box<[T]>>
 ) -> (Vec<R>, Vec<Box<[R]>>)
 {
     let batch_size = workspace.batch_size;
+    let features_size = workspace.architecture.features_size();
 
     debug_assert!(batch_size == features.len());
 
@@ -139,10 +212,10 @@ pub fn forward<T: From<f32> + Clone, R: From<f32> + Clone>(
         check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.tower_stream));
 
         for (i, ref feature) in features.iter().enumerate() {
-            assert_eq!(feature.len(), 11552);
+            assert_eq!(feature.len(), features_size);
             assert_eq!(1, ::std::mem::size_of::<c_void>());
 
-            let element_size = ::std::mem::size_of::<T>() * 11552;
+            let element_size = ::std::mem::size_of::<T>() * features_size;
             let input = workspace.get_input(None)
                 .offset((i * element_size) as isize);
 
@@ -164,7 +237,7 @@ pub fn forward<T: From<f32> + Clone, R: From<f32> + Clone>(
         check!(cuda::cudaStreamWaitEvent(workspace.policy_stream, workspace.tower_event, 0));
         check!(cuda::cudaStreamWaitEvent(workspace.value_stream, workspace.tower_event, 0));
 
-        // policy head (21p_policy)
+        // policy head
         check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.policy_stream));
         check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.policy_stream));
 
@@ -184,7 +257,7 @@ pub fn forward<T: From<f32> + Clone, R: From<f32> + Clone>(
             ));
         }
 
-        // value head (21v_value)
+        // value head
         check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.value_stream));
         check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.value_stream));
 
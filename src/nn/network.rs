@@ -15,25 +15,30 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::env;
+use std::io;
 use std::ops::{Deref, DerefMut};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-use nn::graph;
-use nn::loader;
+use nn::architecture::Architecture;
+use nn::{cpu, graph, loader};
+use nn::loader::LoadError;
+use nn::ops::Tensor;
+use nn::{Backend, BACKEND, Type, TYPE, Workspace};
+use util::config;
 
-type WorkspaceQueue = Rc<RefCell<Vec<Rc<graph::Workspace>>>>;
+type WorkspaceQueue = Rc<RefCell<Vec<Rc<Workspace>>>>;
 
 /// Wrapper around a `Workspace` that when dropped returns it to the
 /// pool it was acquired from.
 pub struct WorkspaceGuard<'a> {
-    workspace: Rc<graph::Workspace>,
+    workspace: Rc<Workspace>,
     pool: &'a Mutex<HashMap<usize, WorkspaceQueue>>
 }
 
 impl<'a> Deref for WorkspaceGuard<'a> {
-    type Target = graph::Workspace;
+    type Target = Workspace;
 
     fn deref(&self) -> &Self::Target { &self.workspace }
 }
@@ -45,7 +50,7 @@ impl<'a> DerefMut for WorkspaceGuard<'a> {
 impl<'a> Drop for WorkspaceGuard<'a> {
     fn drop(&mut self) {
         let workspace = self.workspace.clone();
-        let batch_size = workspace.batch_size;
+        let batch_size = workspace.batch_size();
         let workspaces = self.pool.lock().unwrap();
         let mut candidates = workspaces[&batch_size].borrow_mut();
 
@@ -53,9 +58,27 @@ impl<'a> Drop for WorkspaceGuard<'a> {
     }
 }
 
+/// The pre-processed weights for one of the supported backends.
+enum Builder {
+    Cuda(graph::Builder),
+    Cpu(cpu::Builder)
+}
+
+impl Builder {
+    fn get_workspace(&self, batch_size: usize) -> Workspace {
+        match *self {
+            Builder::Cuda(ref builder) => Workspace::Cuda(builder.get_workspace(batch_size)),
+            Builder::Cpu(ref builder) => Workspace::Cpu(builder.get_workspace(batch_size))
+        }
+    }
+}
+
 /// Pool of workspaces that can be used for network evaluations.
 pub struct Network {
-    builder: Arc<graph::Builder>,
+    builder: Arc<Builder>,
+    architecture: Architecture,
+    path: PathBuf,
+    checksum: u32,
     workspaces: Arc<Mutex<HashMap<usize, WorkspaceQueue>>>
 }
 
@@ -63,31 +86,146 @@ unsafe impl Send for Network { }  // this is safe because the Rc<...> is guarded
 unsafe impl Sync for Network { }  // this is safe because the Rc<...> is guarded by a Mutex and/or Arc
 
 impl Network {
-    pub fn new() -> Option<Network> {
+    /// Returns the network stored at the path given on the command-line, or
+    /// at the default paths if no path was given, using the default backend.
+    pub fn new() -> Result<Network, LoadError> {
+        match *config::WEIGHTS {
+            Some(ref path) => Network::from_path(path),
+            None => Network::with_backend(*BACKEND)
+        }
+    }
+
+    /// Returns the network stored at the default paths, using the given
+    /// backend. The first path that exists is used, and if it cannot be
+    /// loaded then the reason is returned instead of trying the rest.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `backend` - the backend to use for inference
+    /// 
+    pub fn with_backend(backend: Backend) -> Result<Network, LoadError> {
         lazy_static! {
-            static ref PATHS: Vec<String> = vec! [
+            static ref PATHS: Vec<String> = {
                 // check for a file named the same as the current executable, but
-                // with a `.json` extension.
-                env::current_exe().ok().and_then(|file| {
-                    let mut json = file.clone();
-                    json.set_extension("json");
-                    json.as_path().to_str().map(|s| s.to_string())
-                }).unwrap_or("dream_go.json".to_string()),
-
+                // with a `.weights` or `.json` extension, and then some
                 // hard-coded paths to make development and deployment easier
-                "dream_go.json".to_string(),
-                "models/dream_go.json".to_string(),
-                "/usr/share/dream_go/dream_go.json".to_string()
-            ];
+                let base_names = vec! [
+                    env::current_exe().ok().and_then(|file| {
+                        let mut base = file.clone();
+                        base.set_extension("");
+                        base.as_path().to_str().map(|s| s.to_string())
+                    }).unwrap_or("dream_go".to_string()),
+
+                    "dream_go".to_string(),
+                    "models/dream_go".to_string(),
+                    "/usr/share/dream_go/dream_go".to_string()
+                ];
+
+                base_names.into_iter()
+                    .flat_map(|base| vec! [format!("{}.weights", base), format!("{}.json", base)])
+                    .collect()
+            };
+        }
+
+        for path in PATHS.iter() {
+            match Network::load(backend, Path::new(path)) {
+                Err(LoadError::NotFound(_)) => {},
+                other => { return other }
+            }
+        }
+
+        Err(LoadError::NotFound(PATHS.clone()))
+    }
+
+    /// Returns the network stored at the given path, using the default
+    /// backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the network weights
+    ///
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Network, LoadError> {
+        Network::load(*BACKEND, path.as_ref())
+    }
+
+    /// Returns the network stored at the given path, using the given
+    /// backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - the backend to use for inference
+    /// * `path` - the path to the network weights
+    ///
+    fn load(backend: Backend, path: &Path) -> Result<Network, LoadError> {
+        match loader::load(path) {
+            Ok((architecture, weights, checksum)) => {
+                Ok(Network::with_weights(backend, path, architecture, weights, checksum))
+            },
+            Err(LoadError::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => {
+                Err(LoadError::NotFound(vec! [path.to_string_lossy().into_owned()]))
+            },
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Returns a network with the given weights, using the given backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - the backend to use for inference
+    /// * `path` - the path the weights were loaded from
+    /// * `architecture` - the architecture of the network
+    /// * `weights` - the weights of the network
+    /// * `checksum` - the CRC-32 of the file the weights were loaded from
+    ///
+    fn with_weights(
+        backend: Backend,
+        path: &Path,
+        architecture: Architecture,
+        weights: HashMap<String, Tensor>,
+        checksum: u32
+    ) -> Network
+    {
+        Network {
+            builder: Arc::new(match backend {
+                Backend::Cuda => Builder::Cuda(graph::Builder::new(architecture, weights)),
+                Backend::Cpu => Builder::Cpu(cpu::Builder::new(architecture, weights))
+            }),
+            architecture: architecture,
+            path: path.to_path_buf(),
+            checksum: checksum,
+            workspaces: Arc::new(Mutex::new(HashMap::new()))
         }
+    }
+
+    /// Returns the architecture of this network.
+    pub fn architecture(&self) -> Architecture {
+        self.architecture
+    }
+
+    /// Returns the path that the weights of this network were loaded from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a name that identifies the weights of this network, which is
+    /// the file name of the weights together with their checksum so that
+    /// different versions of the same file can be told apart.
+    pub fn name(&self) -> String {
+        let file_name = self.path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
 
-        PATHS.iter()
-            .filter_map(|path| loader::load(Path::new(path)))
-            .next()
-            .map(|weights| Network {
-                builder: Arc::new(graph::Builder::new(weights)),
-                workspaces: Arc::new(Mutex::new(HashMap::new()))
-            })
+        format!("{}:{:08x}", file_name, self.checksum)
+    }
+
+    /// Returns whether this network expects its input, and returns its
+    /// output, in half precision.
+    pub fn is_half(&self) -> bool {
+        match (&*self.builder, &*TYPE) {
+            (&Builder::Cuda(_), &Type::Half) => true,
+            _ => false
+        }
     }
 
     /// Returns a `Workspace` with the given batch size.
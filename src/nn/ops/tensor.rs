@@ -84,9 +84,19 @@ impl Drop for Tensor {
             if (*self.ref_count).fetch_sub(1, Ordering::SeqCst) == 1 {
                 Box::from_raw(self.ref_count);
 
-                check!(cuda::cudaFree(self.ptr));
-                check!(cudnn::cudnnDestroyFilterDescriptor(self.filter_desc));
-                check!(cudnn::cudnnDestroyTensorDescriptor(self.tensor_desc));
+                // tensors that were never copied to the device (such as the
+                // ones used by the CPU backend) have nothing to release
+                if !self.ptr.is_null() {
+                    check!(cuda::cudaFree(self.ptr));
+                }
+
+                if !self.filter_desc.is_null() {
+                    check!(cudnn::cudnnDestroyFilterDescriptor(self.filter_desc));
+                }
+
+                if !self.tensor_desc.is_null() {
+                    check!(cudnn::cudnnDestroyTensorDescriptor(self.tensor_desc));
+                }
             }
         }
     }
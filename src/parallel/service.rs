@@ -15,6 +15,7 @@
 use std::thread::{self, JoinHandle};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 use parallel::*;
 
 /// The implementation details of a service that is responsible for actually
@@ -51,6 +52,29 @@ pub trait ServiceImpl {
         resp: OneSender<Self::Response>,
         has_more: bool
     );
+
+    /// Returns the maximum amount of time a worker thread should sleep while
+    /// there are no requests, before it calls `wake_up`. If `None` then the
+    /// worker threads only wake up when a request arrives.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state of the service
+    ///
+    fn get_wake_up_interval(_state: &Self::State) -> Option<Duration> {
+        None
+    }
+
+    /// Called by a worker thread that has been sleeping for the interval
+    /// given by `get_wake_up_interval` without receiving any requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state of the service
+    ///
+    fn wake_up(_state: &Self::State) {
+        // pass
+    }
 }
 
 /// The worker thread that is responsible for receiving requests and dispatching
@@ -84,7 +108,21 @@ fn worker_thread<I: ServiceImpl>(
                 break
             }
 
-            queue_lock = cvar.wait(queue_lock).unwrap();
+            if let Some(interval) = I::get_wake_up_interval(&state) {
+                let (lock, result) = cvar.wait_timeout(queue_lock, interval).unwrap();
+
+                if result.timed_out() && lock.is_empty() {
+                    drop(lock);
+
+                    I::wake_up(&state);
+
+                    queue_lock = queue.lock().unwrap();
+                } else {
+                    queue_lock = lock;
+                }
+            } else {
+                queue_lock = cvar.wait(queue_lock).unwrap();
+            }
         }
     }
 }
@@ -0,0 +1,228 @@
+// Copyright 2018 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and writing of primitive values in little endian byte order.
+
+use std::io;
+
+/// Write the given 8-bit integer to the given formatter.
+///
+/// # Arguments
+///
+/// * `f` - the formatter to write to
+/// * `value` - the value to write
+///
+pub fn write_u8<T: io::Write>(f: &mut T, value: u8) -> io::Result<()> {
+    f.write_all(&[value])
+}
+
+/// Write the given 16-bit integer to the given formatter.
+///
+/// # Arguments
+///
+/// * `f` - the formatter to write to
+/// * `value` - the value to write
+///
+pub fn write_u16<T: io::Write>(f: &mut T, value: u16) -> io::Result<()> {
+    let mut bytes = [0; 2];
+
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (value >> (8 * i)) as u8;
+    }
+
+    f.write_all(&bytes)
+}
+
+/// Write the given 32-bit integer to the given formatter.
+///
+/// # Arguments
+///
+/// * `f` - the formatter to write to
+/// * `value` - the value to write
+///
+pub fn write_u32<T: io::Write>(f: &mut T, value: u32) -> io::Result<()> {
+    let mut bytes = [0; 4];
+
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (value >> (8 * i)) as u8;
+    }
+
+    f.write_all(&bytes)
+}
+
+/// Write the given 64-bit integer to the given formatter.
+///
+/// # Arguments
+///
+/// * `f` - the formatter to write to
+/// * `value` - the value to write
+///
+pub fn write_u64<T: io::Write>(f: &mut T, value: u64) -> io::Result<()> {
+    let mut bytes = [0; 8];
+
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (value >> (8 * i)) as u8;
+    }
+
+    f.write_all(&bytes)
+}
+
+/// Write the given 32-bit floating point number to the given formatter.
+///
+/// # Arguments
+///
+/// * `f` - the formatter to write to
+/// * `value` - the value to write
+///
+pub fn write_f32<T: io::Write>(f: &mut T, value: f32) -> io::Result<()> {
+    write_u32(f, value.to_bits())
+}
+
+/// Returns the next 8-bit integer from the given reader.
+///
+/// # Arguments
+///
+/// * `f` - the reader to read from
+///
+pub fn read_u8<T: io::Read>(f: &mut T) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+
+    f.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+/// Returns the next 16-bit integer from the given reader.
+///
+/// # Arguments
+///
+/// * `f` - the reader to read from
+///
+pub fn read_u16<T: io::Read>(f: &mut T) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+
+    f.read_exact(&mut bytes)?;
+    Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u16))
+}
+
+/// Returns the next 32-bit integer from the given reader.
+///
+/// # Arguments
+///
+/// * `f` - the reader to read from
+///
+pub fn read_u32<T: io::Read>(f: &mut T) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+
+    f.read_exact(&mut bytes)?;
+    Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32))
+}
+
+/// Returns the next 64-bit integer from the given reader.
+///
+/// # Arguments
+///
+/// * `f` - the reader to read from
+///
+pub fn read_u64<T: io::Read>(f: &mut T) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+
+    f.read_exact(&mut bytes)?;
+    Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64))
+}
+
+/// Returns the next 32-bit floating point number from the given reader.
+///
+/// # Arguments
+///
+/// * `f` - the reader to read from
+///
+pub fn read_f32<T: io::Read>(f: &mut T) -> io::Result<f32> {
+    read_u32(f).map(f32::from_bits)
+}
+
+lazy_static! {
+    /// Lookup table of the CRC-32 of every byte.
+    static ref CRC32_TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut c = i as u32;
+
+            for _ in 0..8 {
+                c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            }
+
+            *entry = c;
+        }
+
+        table
+    };
+}
+
+/// Returns the CRC-32 (as used by zlib and PNG) of the given bytes.
+///
+/// # Arguments
+///
+/// * `bytes` - the bytes to compute the checksum of
+///
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let crc = bytes.iter().fold(0xffffffff, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    });
+
+    crc ^ 0xffffffff
+}
+
+/// Returns an error that indicates that the data being read is malformed.
+///
+/// # Arguments
+///
+/// * `message` - a description of what is malformed
+///
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use util::binary::*;
+
+    #[test]
+    fn round_trip() {
+        let mut buf = vec! [];
+
+        write_u8(&mut buf, 0x12).unwrap();
+        write_u16(&mut buf, 0x1234).unwrap();
+        write_u32(&mut buf, 0x12345678).unwrap();
+        write_u64(&mut buf, 0x123456789abcdef0).unwrap();
+        write_f32(&mut buf, ::std::f32::consts::PI).unwrap();
+
+        assert_eq!(&buf[0..3], &[0x12, 0x34, 0x12]);
+
+        let mut f = &buf[..];
+
+        assert_eq!(read_u8(&mut f).unwrap(), 0x12);
+        assert_eq!(read_u16(&mut f).unwrap(), 0x1234);
+        assert_eq!(read_u32(&mut f).unwrap(), 0x12345678);
+        assert_eq!(read_u64(&mut f).unwrap(), 0x123456789abcdef0);
+        assert_eq!(read_f32(&mut f).unwrap(), ::std::f32::consts::PI);
+        assert!(read_u8(&mut f).is_err());
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+}
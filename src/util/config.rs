@@ -12,14 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use rand::{thread_rng, Rng};
 use std::env;
 use std::str::FromStr;
 
+use go::Rules;
+
 #[derive(PartialEq)]
 pub enum Procedure {
     Extract(bool),
     SelfPlay(usize),
     PolicyPlay(usize),
+    Analyze,
+    Match(usize),
     Gtp,
     Help
 }
@@ -49,12 +54,50 @@ impl FromStr for SamplingStrategy {
     }
 }
 
+/// The komi to use for each game, either a single komi or a list of komi
+/// from which one is picked at random for every game.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KomiStrategy {
+    Fixed(f32),
+    Choice(Vec<f32>)
+}
+
+impl FromStr for KomiStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let komi = s.split(',')
+            .map(|k| k.trim().parse::<f32>().map_err(|_| ()))
+            .collect::<Result<Vec<f32>, ()>>()?;
+
+        if komi.len() == 1 {
+            Ok(KomiStrategy::Fixed(komi[0]))
+        } else {
+            Ok(KomiStrategy::Choice(komi))
+        }
+    }
+}
+
+impl KomiStrategy {
+    /// Returns the komi to use for the next game.
+    pub fn sample(&self) -> f32 {
+        match *self {
+            KomiStrategy::Fixed(komi) => komi,
+            KomiStrategy::Choice(ref komi) => *thread_rng().choose(komi).unwrap()
+        }
+    }
+}
+
 lazy_static! {
     /// The main producedure to run during this execution.
     pub static ref PROCEDURE: Procedure = if has_opt("--help") {
         Procedure::Help
     } else if has_opt("--extract") {
         Procedure::Extract(has_opt("--ex-it"))
+    } else if has_opt("--match") {
+        Procedure::Match(get_opt("--match").unwrap_or(400))
+    } else if has_opt("--analyze") {
+        Procedure::Analyze
     } else if has_opt("--policy-play") {
         Procedure::PolicyPlay(get_opt("--policy-play").unwrap_or(::std::usize::MAX))
     } else if has_opt("--self-play") {
@@ -66,12 +109,71 @@ lazy_static! {
     /// The target number of rollouts for each search tree.
     pub static ref NUM_ROLLOUT: usize = get_opt("--num-rollout").unwrap_or(1600);
 
+    /// The maximum amount of memory (in MiB) that each search tree may use,
+    /// the search stops once it has been reached.
+    pub static ref MEMORY_LIMIT: usize = get_opt("--memory-limit").unwrap_or(2048);
+
     /// The maximum batch size to forward to the neural network. A larger batch
     /// size typically result in a faster program but requires more GPU memory.
-    pub static ref BATCH_SIZE: usize = get_opt("--batch-size").unwrap_or(16);
+    pub static ref BATCH_SIZE: usize = {
+        let batch_size = get_opt("--batch-size").unwrap_or(64);
+
+        if batch_size == 0 {
+            eprintln!("The batch size must be at least one");
+            ::std::process::exit(1);
+        }
+
+        batch_size
+    };
+
+    /// The maximum amount of time (in milliseconds) that a request to the
+    /// neural network may wait for a batch to fill up before it is evaluated
+    /// anyway. A larger value typically result in larger batches, at the
+    /// cost of a higher latency.
+    pub static ref BATCH_LATENCY: u64 = {
+        let batch_latency: i64 = get_opt("--batch-latency").unwrap_or(10);
+
+        if batch_latency < 1 || batch_latency > 1000 {
+            eprintln!("The batch latency must be between 1 and 1000 milliseconds");
+            ::std::process::exit(1);
+        }
+
+        batch_latency as u64
+    };
+
+    /// The path to the network weights to use, if not given then the weights
+    /// are searched for in a few default locations. This does not affect the
+    /// players in a `Match`, since their profiles name their own weights.
+    pub static ref WEIGHTS: Option<String> = get_opt("--weights");
+
+    /// The interval (in seconds) between each check whether the network
+    /// weights have changed during `SelfPlay` and `PolicyPlay`. If they have
+    /// changed then every game that starts afterwards use the new weights.
+    pub static ref RELOAD_WEIGHTS: Option<u64> = get_opt("--reload-weights");
+
+    /// Whether to run the neural network on the CPU instead of the GPU. The
+    /// CPU is also used if no CUDA device could be found.
+    pub static ref USE_CPU: bool = has_opt("--cpu");
+
+    /// Whether to keep searching during the opponents turn in `Gtp`.
+    pub static ref PONDER: bool = has_opt("--ponder");
+
+    /// Whether the search should share the statistics of every path in the
+    /// search tree that leads to the same position.
+    pub static ref TRANSPOSITIONS: bool = has_opt("--transpositions");
+
+    /// The rules to play under during `SelfPlay`, `PolicyPlay`, and `Gtp`
+    /// (unless changed by the GTP controller).
+    pub static ref RULES: Rules = get_opt("--rules").unwrap_or(Rules::Chinese);
+
+    /// The komi to play with during `SelfPlay`, `PolicyPlay`, and `Gtp`
+    /// (unless changed by the GTP controller). Defaults to the komi of the
+    /// rules.
+    pub static ref KOMI: KomiStrategy = get_opt("--komi")
+        .unwrap_or_else(|| KomiStrategy::Fixed(RULES.komi()));
 
     /// The maximum number of games to play in parallel during `SelfPlay`,
-    /// `PolicyPlay`, and `Extract` (with expert iteration).
+    /// `PolicyPlay`, `Analyze`, `Match`, and `Extract` (with expert iteration).
     pub static ref NUM_GAMES: usize = get_opt("--num-games")
         .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 1 } else { 16 });
 
@@ -99,13 +201,19 @@ lazy_static! {
     /// tree. A larger value will result in a more random search, which is
     /// typically desirable during training but not during tournament play.
     pub static ref DIRICHLET_NOISE: f32 = get_env("DIRICHLET_NOISE")
-        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.1 } else { 0.25 });
+        .unwrap_or_else(|| match *PROCEDURE {
+            Procedure::Gtp | Procedure::Analyze | Procedure::Match(_) => 0.1,
+            _ => 0.25
+        });
 
     /// The temperature of the move selection during the eight first moves. A
     /// larger values make the engine more likely to pick a sub-optimal
     /// move (according to the search).
     pub static ref TEMPERATURE: f32 = get_env("TEMPERATURE")
-        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.3 } else { 0.6 });
+        .unwrap_or_else(|| match *PROCEDURE {
+            Procedure::Gtp | Procedure::Analyze | Procedure::Match(_) => 0.3,
+            _ => 0.6
+        });
 
     /// The UCT exploration rate.
     pub static ref UCT_EXP: f32 = get_env("UCT_EXP")
@@ -114,6 +222,38 @@ lazy_static! {
     /// The rave bias.
     pub static ref RAVE_BIAS: f32 = get_env("RAVE_BIAS")
         .unwrap_or(0.705811);
+
+    /// The difference in strength (in elo) under the null hypothesis of the
+    /// sequential probability ratio test that stops a `Match`.
+    pub static ref SPRT_ELO0: f64 = get_env("SPRT_ELO0")
+        .unwrap_or(0.0);
+
+    /// The difference in strength (in elo) under the alternative hypothesis
+    /// of the sequential probability ratio test that stops a `Match`. This
+    /// must be larger than `SPRT_ELO0`.
+    pub static ref SPRT_ELO1: f64 = {
+        let elo1: f64 = get_env("SPRT_ELO1").unwrap_or(35.0);
+
+        if elo1.is_nan() || SPRT_ELO0.is_nan() || elo1 <= *SPRT_ELO0 {
+            eprintln!("SPRT_ELO1 ({}) must be larger than SPRT_ELO0 ({})", elo1, *SPRT_ELO0);
+            ::std::process::exit(1);
+        }
+
+        elo1
+    };
+
+    /// The probability of false positives, and false negatives, of the
+    /// sequential probability ratio test that stops a `Match`.
+    pub static ref SPRT_ALPHA: f64 = {
+        let alpha: f64 = get_env("SPRT_ALPHA").unwrap_or(0.05);
+
+        if alpha.is_nan() || alpha <= 0.0 || alpha >= 0.5 {
+            eprintln!("SPRT_ALPHA ({}) must be between zero and one half", alpha);
+            ::std::process::exit(1);
+        }
+
+        alpha
+    };
 }
 
 /// Returns true if any command-line argument with the given name is present.
@@ -145,21 +285,71 @@ pub fn get_env<T: FromStr>(name: &str) -> Option<T> {
     }
 }
 
+/// The command-line options that are always followed by a value, which is
+/// not an unnamed argument.
+const OPTIONS_WITH_VALUE: [&'static str; 11] = [
+    "--num-rollout", "--memory-limit", "--batch-size", "--batch-latency",
+    "--weights", "--reload-weights", "--rules", "--komi", "--num-games",
+    "--num-threads", "--num-samples"
+];
+
 /// Returns all unnamed arguments given to this program.
 pub fn get_args() -> Vec<String> {
-    let mut rest = vec! [];
+    get_args_from(env::args().skip(1))
+}
 
-    for (i, arg) in env::args().enumerate().skip(1) {
-        if !arg.starts_with("--") && !usize::from_str(&arg).is_ok() {
-            rest.push(arg);
-        } else if arg == "--" {
-            for arg in env::args().skip(i + 1) {
-                rest.push(arg);
-            }
+/// Returns all unnamed arguments in the given command-line arguments, which
+/// should not include the name of the program. The value of every option in
+/// `OPTIONS_WITH_VALUE` is skipped, as is any number since it is the value of
+/// some other option.
+///
+/// # Arguments
+///
+/// * `args` - the command-line arguments
+///
+fn get_args_from<I: Iterator<Item=String>>(mut args: I) -> Vec<String> {
+    let mut rest = vec! [];
 
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            rest.extend(args);
             break
+        } else if OPTIONS_WITH_VALUE.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with("--") && !usize::from_str(&arg).is_ok() {
+            rest.push(arg);
         }
     }
 
     rest
 }
+
+#[cfg(test)]
+mod tests {
+    use util::config::*;
+
+    /// Returns the unnamed arguments of the given command-line.
+    fn args(line: &str) -> Vec<String> {
+        get_args_from(line.split_whitespace().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn unnamed_args() {
+        assert_eq!(args("--analyze a.sgf b.sgf"), vec! ["a.sgf", "b.sgf"]);
+        assert_eq!(args("--match 100 weights=a.json weights=b.json"), vec! ["weights=a.json", "weights=b.json"]);
+        assert_eq!(args("--self-play 10 --komi 7.5,6.5 --rules japanese"), Vec::<String>::new());
+        assert_eq!(
+            args("--weights w.json --batch-size 32 --batch-latency 5 --reload-weights 60 --memory-limit 512 --analyze a.sgf"),
+            vec! ["a.sgf"]
+        );
+        assert_eq!(args("--analyze -- --komi 7.5"), vec! ["--komi", "7.5"]);
+    }
+
+    #[test]
+    fn batch_latency_args() {
+        assert_eq!(args("--batch-latency 5 --analyze a.sgf"), vec! ["a.sgf"]);
+        assert_eq!(args("--analyze a.sgf --batch-latency 5"), vec! ["a.sgf"]);
+        assert_eq!(args("--analyze --batch-latency 0 a.sgf"), vec! ["a.sgf"]);
+        assert_eq!(args("--analyze --batch-latency -1 a.sgf"), vec! ["a.sgf"]);
+    }
+}
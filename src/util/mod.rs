@@ -14,6 +14,7 @@
 
 pub mod array;
 pub mod b85;
+pub mod binary;
 pub mod config;
 pub mod types;
 pub mod singleton;
@@ -23,6 +23,7 @@ use ordered_float::OrderedFloat;
 
 thread_local! {
     static NETWORK: nn::Network = nn::Network::new().unwrap();
+    static CPU_NETWORK: nn::Network = nn::Network::with_backend(nn::Backend::Cpu).unwrap();
 }
 
 fn predict(moves: &[(Color, usize, usize)], next_color: Color) -> (f32, Box<[f32]>) {
@@ -223,3 +224,33 @@ fn dead_dragon_1() {
 fn dead_dragon_2() {
     // pass
 }
+
+/// Test that the CPU backend gives the same value and policy as the default
+/// backend, within the precision of the weights.
+#[test]
+fn cpu_same_as_default() {
+    let moves = [
+        (Color::Black, 15,  3), (Color::White,  3,  3), (Color::Black, 15, 15), (Color::White,  3, 15),
+        (Color::Black, 15, 12), (Color::White,  5, 16), (Color::Black,  4, 16), (Color::White,  3, 16),
+        (Color::Black,  5, 15), (Color::White,  4, 15), (Color::Black,  4, 17), (Color::White,  6, 16)
+    ];
+    let (value, policy) = predict(&moves, Color::Black);
+
+    let mut board = Board::new();
+
+    for &(color, x, y) in moves.iter() {
+        board.place(color, x, y);
+    }
+
+    CPU_NETWORK.with(|network| {
+        let mut workspace = network.get_workspace(1);
+        let features = board.get_features::<f32, CHW>(Color::Black, Transform::Identity);
+        let (cpu_value, cpu_policy) = nn::forward::<f32, f32>(&mut workspace, &vec! [features]);
+
+        assert!((value - cpu_value[0]).abs() < 1e-2, "{} ~ {}", value, cpu_value[0]);
+
+        for i in 0..362 {
+            assert!((policy[i] - cpu_policy[0][i]).abs() < 1e-2, "policy[{}]: {} ~ {}", i, policy[i], cpu_policy[0][i]);
+        }
+    });
+}
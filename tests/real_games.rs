@@ -12,50 +12,32 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
-#[macro_use] extern crate lazy_static;
-extern crate regex;
 extern crate dream_go;
 
-use dream_go::go::{Board, Color};
-use regex::Regex;
+use dream_go::go::sgf::{self, Property};
+use dream_go::go::Board;
 
-/// Play each move in the given SGF string and return the final board state,
+/// Play each move in the given SGF sequence and return the final board state,
 /// if any of the moves are invalid then it panic.
 ///
 /// # Arguments
 ///
-/// * `src` - the SGF string
+/// * `src` - the SGF sequence
 ///
 fn playout_game(src: &str) -> Board {
-    lazy_static! {
-        static ref LETTERS: [char; 26] = [
-            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k',
-            'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v',
-            'w', 'x', 'y', 'z'
-        ];
-        static ref MOVE: Regex = Regex::new(r";([BW])\[([a-z]*)\]").unwrap();
-    }
-
+    let games = sgf::parse(&format!("({})", src)).unwrap();
     let mut board = Board::new();
     let mut count = 1;
 
-    for cap in MOVE.captures_iter(src) {
-        let color = match &cap[1] {
-            "B" => Color::Black,
-            "W" => Color::White,
-            _   => { unreachable!(); }
-        };
-        let x = cap[2].chars().nth(0)
-            .and_then(|x| LETTERS.binary_search(&x).ok())
-            .unwrap_or(board.size());
-        let y = cap[2].chars().nth(1)
-            .and_then(|y| LETTERS.binary_search(&y).ok())
-            .unwrap_or(board.size());
-
-        assert!(board.is_valid(color, x, 18 - y), "invalid move {}: {} {} {}\n{}", count, color, x, y, board);
+    for node in games[0].main_line() {
+        for property in node.properties.iter() {
+            if let Property::Move(color, Some((x, y))) = *property {
+                assert!(board.is_valid(color, x, 18 - y), "invalid move {}: {} {} {}\n{}", count, color, x, y, board);
 
-        board.place(color, x, 18 - y);
-        count += 1;
+                board.place(color, x, 18 - y);
+                count += 1;
+            }
+        }
     }
 
     board
@@ -308,9 +308,12 @@ class Tower:
 
     VARIABLES = 'tower_variables'
 
-    def __init__(self, num_features=128):
+    def __init__(self, num_features=128, num_blocks=19):
         glorot_op = tf.glorot_normal_initializer()
 
+        self._num_features = num_features
+        self._num_blocks = num_blocks
+
         with tf.variable_scope('01_upsample') as self._upsample_scope:
             self._upsample = tf.get_variable('weights', (3, 3, 32, num_features), tf.float32, glorot_op)
             self._bn = BatchNorm(num_features, collection=Tower.VARIABLES)
@@ -319,21 +322,33 @@ class Tower:
         tf.add_to_collection(Tower.VARIABLES, self._upsample)
 
         # residual blocks
-        self._residual_scopes = [None] * 19
+        self._residual_scopes = [None] * num_blocks
         self._residuals = []
 
-        for i in range(19):
+        for i in range(num_blocks):
             with tf.variable_scope('{:02d}_residual'.format(2 + i)) as self._residual_scopes[i]:
                 self._residuals += [ResidualBlock(num_features, collection=Tower.VARIABLES)]
 
         # policy head
-        with tf.variable_scope('21p_policy') as self._policy_scope:
+        with tf.variable_scope('{:02d}p_policy'.format(2 + num_blocks)) as self._policy_scope:
             self._policy = PolicyHead(num_features)
 
         # value head
-        with tf.variable_scope('21v_value') as self._value_scope:
+        with tf.variable_scope('{:02d}v_value'.format(2 + num_blocks)) as self._value_scope:
             self._value = ValueHead(num_features)
 
+    def architecture(self):
+        """ Returns the shape of this tower, as it is stored in the weights file. """
+
+        return {
+            'num_blocks': str(self._num_blocks),
+            'num_channels': str(self._num_features),
+            'num_features': '32',
+            'num_policy_channels': '2',
+            'num_value_channels': '1',
+            'num_value_hidden': '256'
+        }
+
     def dump(self, sess, into=None):
         """ Returns a dictionary that contains all model variables of this tower. """
 
@@ -792,10 +807,56 @@ def calibrate(sess, tower, files):
     except KeyboardInterrupt:
         return {}
 
-def dump(args):
+def write_binary(values, shapes, architecture, out):
     """
-    Dump the given (or latest if none is given) checkpoint as a JSON file that
-    is readable by dream-go
+    Write the given JSON entries in the versioned binary weights format that
+    is readable by dream-go, where `shapes` contains the shape of every entry
+    with values. Every tensor is stored in half precision.
+    """
+    import base64
+    import struct
+    import zlib
+
+    def _decode(entry, key):
+        if key in entry:
+            return np.frombuffer(base64.b85decode(entry[key]), 'f2')
+        else:
+            return np.asarray([], 'f2')
+
+    header = b'DGNW' + struct.pack('<I', 1)
+    header += struct.pack('<6I', *[int(architecture[key]) for key in [
+        'num_blocks', 'num_channels', 'num_features',
+        'num_policy_channels', 'num_value_channels', 'num_value_hidden'
+    ]])
+    header += struct.pack('<I', len(values))
+
+    # the offset of the data depends on the size of the index, so compute
+    # the size of the index before writing it
+    names = sorted(values.keys())
+    tensors = [(name.encode('utf-8'), _decode(values[name], 's'), _decode(values[name], 'v')) for name in names]
+    offset = len(header) + sum(2 + len(name) + 1 + 4 + 1 + 4 * len(shapes.get(name.decode('utf-8'), ())) + 8 + 8 for (name, _, _) in tensors)
+    index = b''
+    data = b''
+
+    for (name, scale, value) in tensors:
+        serialized = value.tostring()
+
+        index += struct.pack('<H', len(name)) + name
+        shape = shapes.get(name.decode('utf-8'), ())
+
+        index += struct.pack('<BfB', 0, float(scale[0]) if scale.size > 0 else 1.0, len(shape))
+        index += struct.pack('<{}I'.format(len(shape)), *shape)
+        index += struct.pack('<QQ', offset + len(data), len(serialized))
+        data += serialized
+
+    content = header + index + data
+
+    out.write(content + struct.pack('<I', zlib.crc32(content) & 0xffffffff))
+
+def dump(args, binary=False):
+    """
+    Dump the given (or latest if none is given) checkpoint as a JSON (or
+    binary) file that is readable by dream-go
     """
     tower = Tower()
 
@@ -821,6 +882,7 @@ def dump(args):
         # gather histograms over all of the activations for int8 calibration
         files = [arg for arg in args if arg.endswith('.bin')]
         values = {}
+        shapes = {}
 
         if files:
             values = calibrate(sess, tower, files)
@@ -838,8 +900,14 @@ def dump(args):
                 's': base64.b85encode(scale, pad=True).decode('ascii'),
                 'v': base64.b85encode(serialized, pad=True).decode('ascii')
             }
+            shapes[var.name] = value.shape
+
+        if binary:
+            write_binary(values, shapes, tower.architecture(), sys.stdout.buffer)
+        else:
+            values['architecture'] = tower.architecture()
 
-        json.dump(values, sys.stdout, sort_keys=True)
+            json.dump(values, sys.stdout, sort_keys=True)
 
 
 if __name__ == '__main__':
@@ -848,10 +916,13 @@ if __name__ == '__main__':
 
     if '--dump' in options:
         dump(rest)
+    elif '--dump-binary' in options:
+        dump(rest, binary=True)
     elif not rest:
         print('Usage: bootstrap.py [options] <data...>')
         print()
         print('    --dump         Dump the current weights to STDOUT')
+        print('    --dump-binary  Dump the current weights to STDOUT in the binary format')
         print('    --verify       Dump the accuracy of the given data to STDOUT')
         print('    --reset        Reset the weights to their initial value')
         print('    --reset-lr     Reset the learning rate')